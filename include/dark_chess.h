@@ -0,0 +1,62 @@
+/* C interface to the rust_dark_chess engine. Build the library with
+ *     cargo build --release --no-default-features --features ffi
+ * and link against target/release/librust_dark_chess.so (.dylib, .dll).
+ *
+ * Actions, move lists and boards are JSON strings in the same shapes as the WebSocket server's
+ * messages, e.g. {"kind": "flip", "x": 0, "y": 1} or
+ * {"kind": "move", "from_x": 0, "from_y": 1, "to_x": 1, "to_y": 1}. Every char * returned is
+ * the caller's to release with dc_free_string. Calls that fail return -1 or NULL and leave a
+ * message for dc_last_error. */
+
+#ifndef DARK_CHESS_H
+#define DARK_CHESS_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct DcGame DcGame;
+
+/* Deals a new game under the standard rules; the same seed deals the same layout. */
+DcGame *dc_new_game(uint64_t seed);
+
+/* Starts from a position in FEN-like notation, dealing hidden squares written as '?' from
+ * seed. NULL if the position can't be read. */
+DcGame *dc_game_from_fen(const char *fen, uint64_t seed);
+
+/* Plays an action for the side to move: 0 on success, -1 if it is malformed or illegal. */
+int dc_apply_move(DcGame *game, const char *action_json);
+
+/* Checks an action for the side to move without playing it: 0 if it is legal, -1 if it is
+ * malformed or illegal, with the reason left for dc_last_error. */
+int dc_check_move(DcGame *game, const char *action_json);
+
+/* Every action the side to move may take, as a JSON array. */
+char *dc_legal_moves(const DcGame *game);
+
+/* An action for the side to move from an engine such as "hard", "search:depth=3" or
+ * "ismcts:playouts=500,time=200", not yet played. "null" if there is no legal action, NULL if
+ * the engine can't be read. */
+char *dc_best_move(DcGame *game, const char *engine);
+
+/* The board as JSON rows from y = 0; squares are {"revealed": true, "color": "red",
+ * "piece": "cannon"}, {"revealed": false} or null when empty. */
+char *dc_board_json(const DcGame *game);
+
+/* How the game ended, e.g. "Red wins!", or NULL while it goes on. */
+char *dc_result(const DcGame *game);
+
+/* The message from the game's last failed call, empty if none. Owned by the game and valid
+ * until its next call; do not free it. */
+const char *dc_last_error(const DcGame *game);
+
+void dc_free(DcGame *game);
+void dc_free_string(char *text);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,507 @@
+use crate::belief::{hidden_pool, PiecePool};
+use crate::*;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+pub mod ismcts;
+pub mod playout;
+
+use ismcts::IsMctsConfig;
+
+/// What an agent does with its turn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    Play(ActionType),
+    Resign,
+    OfferDraw, // The opponent has to accept or decline before the turn goes on
+    Leave, // Gone without a result, or nothing left to play
+}
+
+// Bots accept a draw once their evaluation puts them behind by more than this
+pub const DRAW_ACCEPT_MARGIN: f64 = 1.0;
+
+/// Something that can choose the next action for the side to move, so the game loop can
+/// treat a bot and a human at the keyboard the same way.
+pub trait Agent {
+    /// Name shown in prompts and game records.
+    fn name(&self) -> &str;
+
+    /// Picks an action for `game.current_player`, or `None` if there is nothing to play.
+    fn choose_action(&mut self, game: &GameState) -> Option<ActionType>;
+
+    /// The agent's turn. Bots always play what `choose_action` picks; players on the other end
+    /// of a connection may also give up or offer a draw.
+    fn decide(&mut self, game: &GameState) -> Decision {
+        self.choose_action(game).map_or(Decision::Leave, Decision::Play)
+    }
+
+    /// Answers a draw offered by the side to move. Bots accept when they judge their own
+    /// position to be clearly worse.
+    fn accept_draw(&mut self, game: &GameState) -> bool {
+        let own = opponent(game.current_player);
+        !game.colors_unassigned() && evaluate_position(&game.board, own, &game.rules) < -DRAW_ACCEPT_MARGIN
+    }
+
+    /// Called when the opponent declined a draw this agent offered; its turn continues.
+    fn draw_declined(&mut self) {}
+
+    /// Called with the final position once the game has ended, however it ended. `termination`
+    /// is the reason for an ending off the board, as written in game records.
+    fn game_over(&mut self, _game: &GameState, _termination: Option<&str>) {}
+}
+
+/// Plays a uniformly random legal action.
+pub struct RandomAgent {
+    rng: StdRng,
+}
+
+impl RandomAgent {
+    pub fn new(rng: StdRng) -> Self {
+        RandomAgent { rng }
+    }
+}
+
+impl Agent for RandomAgent {
+    fn name(&self) -> &str {
+        "Random AI"
+    }
+
+    fn choose_action(&mut self, game: &GameState) -> Option<ActionType> {
+        game.legal_actions().choose(&mut self.rng).copied()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Engine {
+    Random, // Uniformly random legal actions
+    AlphaBeta, // Fixed-depth expectiminimax search
+    IsMcts, // Information-set Monte Carlo tree search
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlipPolicy {
+    All, // Search every flip at every node
+    NearPieces, // Search flips next to revealed pieces, plus one other standing in for the rest
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+    Max,
+}
+
+/// Everything that decides how strong the bot plays and how long it thinks.
+#[derive(Debug, Clone, Copy)]
+pub struct AiSettings {
+    pub engine: Engine,
+    pub depth: u32, // Plies searched by the alpha-beta engine
+    pub flip_policy: FlipPolicy,
+    pub mcts: IsMctsConfig,
+}
+
+impl AiSettings {
+    pub fn for_difficulty(difficulty: Difficulty) -> Self {
+        let base = AiSettings { engine: Engine::Random, depth: 2, flip_policy: FlipPolicy::All, mcts: IsMctsConfig::default() };
+        match difficulty {
+            Difficulty::Easy => base,
+            Difficulty::Medium => AiSettings { engine: Engine::AlphaBeta, depth: 1, ..base },
+            Difficulty::Hard => AiSettings { engine: Engine::AlphaBeta, depth: 2, flip_policy: FlipPolicy::NearPieces, ..base },
+            Difficulty::Max => AiSettings {
+                engine: Engine::IsMcts,
+                mcts: IsMctsConfig { playouts: usize::MAX, time_budget: Some(Duration::from_secs(3)), ..IsMctsConfig::default() },
+                ..base
+            },
+        }
+    }
+
+    pub fn build_agent(&self, rng: StdRng) -> Box<dyn Agent> {
+        match self.engine {
+            Engine::Random => Box::new(RandomAgent::new(rng)),
+            Engine::AlphaBeta => Box::new(AlphaBetaAgent::new(self.depth, self.flip_policy, rng)),
+            Engine::IsMcts => Box::new(ismcts::IsMctsAgent::new(self.mcts, rng)),
+        }
+    }
+}
+
+pub fn parse_difficulty(name: &str) -> Option<Difficulty> {
+    match name {
+        "easy" => Some(Difficulty::Easy),
+        "medium" => Some(Difficulty::Medium),
+        "hard" => Some(Difficulty::Hard),
+        "max" => Some(Difficulty::Max),
+        _ => None,
+    }
+}
+
+pub fn parse_engine(name: &str) -> Option<Engine> {
+    match name {
+        "random" => Some(Engine::Random),
+        "search" => Some(Engine::AlphaBeta),
+        "ismcts" => Some(Engine::IsMcts),
+        _ => None,
+    }
+}
+
+// Score of a won position, well above any material balance
+pub const WIN_SCORE: f64 = 1000.0;
+
+// Values closer than this are treated as equal
+pub const TIE_MARGIN: f64 = 1e-9;
+
+// Weight of each legal move in the evaluation, relative to piece values
+pub const MOBILITY_WEIGHT: f64 = 0.1;
+
+pub fn piece_value(piece_type: PieceType) -> f64 {
+    // Roughly by strength in play rather than rank: soldiers threaten the general,
+    // and cannons and chariots reach across the board
+    match piece_type {
+        PieceType::General => 6.0,
+        PieceType::Advisor => 5.0,
+        PieceType::Elephant => 4.0,
+        PieceType::Chariot => 3.5,
+        PieceType::Horse => 2.5,
+        PieceType::Cannon => 4.5,
+        PieceType::Soldier => 1.5,
+    }
+}
+
+pub fn evaluate_position<B: Grid>(board: &B, player: Player, rules: &RuleConfig) -> f64 {
+    // Revealed material plus mobility, from the player's point of view
+    let side_score = |side: Player| {
+        let material: f64 = board
+            .squares()
+            .map(|(_, _, cell)| match cell {
+                Cell::Revealed(piece) if piece.player == side => piece_value(piece.piece_type),
+                _ => 0.0,
+            })
+            .sum();
+        let mobility = generate_legal_actions(board, side, rules)
+            .iter()
+            .filter(|action| matches!(action, ActionType::Move { .. }))
+            .count();
+        material + MOBILITY_WEIGHT * mobility as f64
+    };
+    side_score(player) - side_score(opponent(player))
+}
+
+fn next_to_revealed<B: Grid>(board: &B, x: usize, y: usize) -> bool {
+    [(x.wrapping_sub(1), y), (x + 1, y), (x, y.wrapping_sub(1)), (x, y + 1)]
+        .iter()
+        .any(|&(nx, ny)| matches!(board.cell_at(nx, ny), Some(Cell::Revealed(_))))
+}
+
+/// Expectiminimax search: alpha-beta over moves, with flips as chance nodes that average over
+/// the pieces still hidden. Scores are from `root`'s point of view.
+pub struct Search<'a> {
+    pub rules: &'a RuleConfig,
+    pub root: Player,
+    pub flip_policy: FlipPolicy,
+    pub nodes: u64,
+    pub table: HashMap<u64, TableEntry>, // Transposition table keyed by position_hash
+    pub stop: Option<&'a AtomicBool>, // Once set, every node returns at once and the values found are meaningless
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    Lower, // The search failed high, the true value is at least this
+    Upper, // The search failed low, the true value is at most this
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TableEntry {
+    pub depth: u32,
+    pub value: f64,
+    pub bound: Bound,
+}
+
+impl<'a> Search<'a> {
+    pub fn new(rules: &'a RuleConfig, root: Player, flip_policy: FlipPolicy) -> Self {
+        Search { rules, root, flip_policy, nodes: 0, table: HashMap::new(), stop: None }
+    }
+
+    /// Actions worth searching, which with `FlipPolicy::NearPieces` drops all but one quiet flip.
+    pub fn candidate_actions<B: Grid>(&self, board: &B, to_move: Player) -> Vec<ActionType> {
+        let actions = generate_legal_actions(board, to_move, self.rules);
+        if self.flip_policy == FlipPolicy::All {
+            return actions;
+        }
+
+        // Flips next to revealed pieces can set up or spoil captures; any other flip stands in for the rest
+        let mut quiet_flip_kept = false;
+        actions
+            .into_iter()
+            .filter(|&action| match action {
+                ActionType::Flip { x, y } if !next_to_revealed(board, x, y) => !std::mem::replace(&mut quiet_flip_kept, true),
+                _ => true,
+            })
+            .collect()
+    }
+
+    /// Value of playing `action` for `to_move`, searching `depth` further plies.
+    pub fn action_value(&mut self, board: &Board, to_move: Player, action: ActionType, depth: u32, alpha: f64, beta: f64) -> f64 {
+        let mut pool = hidden_pool(board);
+        // The tree is searched on the compact board when the game uses the standard size
+        match Board32::from_board(board) {
+            Some(compact) => self.child_value(&compact, to_move, action, depth, alpha, beta, &mut pool),
+            None => self.child_value(board, to_move, action, depth, alpha, beta, &mut pool),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn child_value<B: Grid>(&mut self, board: &B, to_move: Player, action: ActionType, depth: u32, alpha: f64, beta: f64, pool: &mut PiecePool) -> f64 {
+        match action {
+            ActionType::Flip { x, y } => {
+                // Chance node: average over every piece the square could turn out to be
+                let total: usize = pool.iter().map(|&(_, count)| count).sum();
+                if total == 0 {
+                    return self.value(board, opponent(to_move), depth, alpha, beta, pool);
+                }
+                let mut expected = 0.0;
+                for index in 0..pool.len() {
+                    let (piece, count) = pool[index];
+                    if count == 0 {
+                        continue;
+                    }
+                    let mut child = board.clone();
+                    child.set_cell(x, y, Cell::Revealed(piece));
+                    pool[index].1 -= 1;
+                    // Averages need exact child values, so chance children get the full window
+                    let value = self.value(&child, opponent(to_move), depth, f64::NEG_INFINITY, f64::INFINITY, pool);
+                    pool[index].1 += 1;
+                    expected += value * count as f64 / total as f64;
+                }
+                expected
+            },
+            // A cannon jumping onto a face-down piece is a chance node too, whatever is really there
+            ActionType::Move { from_x, from_y, to_x, to_y }
+                if matches!(board.cell_at(to_x, to_y), Some(Cell::Hidden(_))) && pool.iter().any(|&(_, count)| count > 0) =>
+            {
+                let total: usize = pool.iter().map(|&(_, count)| count).sum();
+                let mut expected = 0.0;
+                for index in 0..pool.len() {
+                    let (piece, count) = pool[index];
+                    if count == 0 {
+                        continue;
+                    }
+                    let mut child = board.clone();
+                    child.set_cell(to_x, to_y, Cell::Hidden(Some(piece)));
+                    if move_piece(&mut child, to_move, from_x, from_y, to_x, to_y, self.rules).is_err() {
+                        return f64::NEG_INFINITY;
+                    }
+                    pool[index].1 -= 1;
+                    let value = self.value(&child, opponent(to_move), depth, f64::NEG_INFINITY, f64::INFINITY, pool);
+                    pool[index].1 += 1;
+                    expected += value * count as f64 / total as f64;
+                }
+                expected
+            },
+            ActionType::Move { from_x, from_y, to_x, to_y } => {
+                let mut child = board.clone();
+                match move_piece(&mut child, to_move, from_x, from_y, to_x, to_y, self.rules) {
+                    Ok(_) => self.value(&child, opponent(to_move), depth, alpha, beta, pool),
+                    Err(_) => f64::NEG_INFINITY,
+                }
+            },
+        }
+    }
+
+    fn value<B: Grid>(&mut self, board: &B, to_move: Player, depth: u32, mut alpha: f64, mut beta: f64, pool: &mut PiecePool) -> f64 {
+        self.nodes += 1;
+        if self.stop.is_some_and(|stop| stop.load(Ordering::Relaxed)) {
+            return 0.0;
+        }
+
+        // The side that just acted is the opponent of the side to move
+        if let Some(result) = check_game_over(board, opponent(to_move), self.rules) {
+            return match result {
+                GameResult::Winner(winner) if winner == self.root => WIN_SCORE + depth as f64,
+                GameResult::Winner(_) => -WIN_SCORE - depth as f64,
+                GameResult::Draw(_) => 0.0,
+            };
+        }
+        if depth == 0 {
+            return evaluate_position(board, self.root, self.rules);
+        }
+
+        // Reuse what an earlier visit of this position learned at the same depth or deeper
+        let key = position_hash(board, to_move);
+        if let Some(entry) = self.table.get(&key).filter(|entry| entry.depth >= depth) {
+            match entry.bound {
+                Bound::Exact => return entry.value,
+                Bound::Lower => alpha = alpha.max(entry.value),
+                Bound::Upper => beta = beta.min(entry.value),
+            }
+            if alpha >= beta {
+                return entry.value;
+            }
+        }
+        let (original_alpha, original_beta) = (alpha, beta);
+
+        let maximizing = to_move == self.root;
+        let mut best = if maximizing { f64::NEG_INFINITY } else { f64::INFINITY };
+        for action in self.candidate_actions(board, to_move) {
+            let value = self.child_value(board, to_move, action, depth - 1, alpha, beta, pool);
+            if maximizing {
+                best = best.max(value);
+                alpha = alpha.max(value);
+            } else {
+                best = best.min(value);
+                beta = beta.min(value);
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        let bound = if best <= original_alpha {
+            Bound::Upper
+        } else if best >= original_beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        self.table.insert(key, TableEntry { depth, value: best, bound });
+        best
+    }
+}
+
+/// Picks the action with the best expectiminimax value at a fixed depth.
+pub struct AlphaBetaAgent {
+    depth: u32,
+    flip_policy: FlipPolicy,
+    rng: StdRng,
+}
+
+impl AlphaBetaAgent {
+    pub fn new(depth: u32, flip_policy: FlipPolicy, rng: StdRng) -> Self {
+        AlphaBetaAgent { depth: depth.max(1), flip_policy, rng }
+    }
+}
+
+impl Agent for AlphaBetaAgent {
+    fn name(&self) -> &str {
+        "Alpha-beta AI"
+    }
+
+    fn choose_action(&mut self, game: &GameState) -> Option<ActionType> {
+        best_action(game, self.depth, self.flip_policy, &mut self.rng).map(|suggestion| suggestion.action)
+    }
+}
+
+/// An action picked by the search, with its value for the side to move.
+#[derive(Debug, Clone, Copy)]
+pub struct Suggestion {
+    pub action: ActionType,
+    pub value: f64,
+    pub nodes: u64, // Positions visited to find it
+}
+
+/// Searches `depth` plies for the current player's best action.
+pub fn best_action(game: &GameState, depth: u32, flip_policy: FlipPolicy, rng: &mut StdRng) -> Option<Suggestion> {
+    // Until the opening flip settles the colors there is no side to search for
+    if game.colors_unassigned() {
+        let action = game.legal_actions().choose(rng).copied()?;
+        return Some(Suggestion { action, value: 0.0, nodes: 0 });
+    }
+    root_action(game, &mut Search::new(&game.rules, game.current_player, flip_policy), depth, rng)
+}
+
+/// Like `best_action`, but searches one ply deeper at a time up to `depth` and stops as soon as
+/// `stop` is set, e.g. from another thread. Returns the choice of the deepest search that
+/// finished, with that depth; the first ply always finishes.
+pub fn best_action_until(game: &GameState, depth: u32, flip_policy: FlipPolicy, rng: &mut StdRng, stop: &AtomicBool) -> Option<(Suggestion, u32)> {
+    let mut best = Some((best_action(game, 1, flip_policy, rng)?, 1));
+    if game.colors_unassigned() {
+        return best;
+    }
+    for deeper in 2..=depth {
+        let mut search = Search::new(&game.rules, game.current_player, flip_policy);
+        search.stop = Some(stop);
+        let suggestion = root_action(game, &mut search, deeper, rng);
+        if stop.load(Ordering::Relaxed) {
+            break;
+        }
+        if let Some(suggestion) = suggestion {
+            best = Some((suggestion, deeper));
+        }
+    }
+    best
+}
+
+fn root_action(game: &GameState, search: &mut Search, depth: u32, rng: &mut StdRng) -> Option<Suggestion> {
+    let mut best_actions = Vec::new();
+    let mut best_value = f64::NEG_INFINITY;
+
+    for action in search.candidate_actions(&game.board, game.current_player) {
+        // Search just below the best value so a cut-off line can't pass for a tie
+        let value = search.action_value(&game.board, game.current_player, action, depth.max(1) - 1, best_value - TIE_MARGIN, f64::INFINITY);
+        if value > best_value {
+            best_value = value;
+            best_actions.clear();
+        }
+        if value >= best_value {
+            best_actions.push(action);
+        }
+    }
+
+    // Break ties at random so equal flips aren't always made in the same corner
+    let action = match best_actions.choose(rng).copied()? {
+        // Under NearPieces the searched quiet flip stood in for all of them, so any quiet flip will do
+        ActionType::Flip { x, y } if search.flip_policy == FlipPolicy::NearPieces && !next_to_revealed(&game.board, x, y) => game
+            .legal_actions()
+            .into_iter()
+            .filter(|&action| matches!(action, ActionType::Flip { x, y } if !next_to_revealed(&game.board, x, y)))
+            .collect::<Vec<_>>()
+            .choose(rng)
+            .copied()?,
+        best => best,
+    };
+    Some(Suggestion { action, value: best_value, nodes: search.nodes })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn midgame() -> GameState {
+        let mut rng = StdRng::seed_from_u64(767);
+        let mut game = GameState::with_rng(RuleConfig::default(), &mut rng).unwrap();
+        for _ in 0..6 {
+            let action = *game.legal_actions().choose(&mut rng).unwrap();
+            game.apply_action(action).unwrap();
+        }
+        game
+    }
+
+    #[test]
+    fn depth_search_finishes_every_ply_unless_stopped() {
+        let game = midgame();
+        let (suggestion, reached) = best_action_until(&game, 2, FlipPolicy::NearPieces, &mut StdRng::seed_from_u64(0), &AtomicBool::new(false)).unwrap();
+        assert_eq!(reached, 2);
+        assert!(game.legal_actions().contains(&suggestion.action));
+
+        // Stopped before it starts, the search still answers from the first ply
+        let (suggestion, reached) = best_action_until(&game, 30, FlipPolicy::All, &mut StdRng::seed_from_u64(0), &AtomicBool::new(true)).unwrap();
+        assert_eq!(reached, 1);
+        assert!(game.legal_actions().contains(&suggestion.action));
+    }
+
+    #[test]
+    fn searching_every_flip_keeps_the_flip_it_valued() {
+        // Black's cannon can jump onto the quiet flip beyond its screen, the other quiet flips are safe
+        let game = GameState::from_fen("c*p*P5/8/4*p3/6*K1 r", RuleConfig::default(), &mut StdRng::seed_from_u64(765)).unwrap();
+        for seed in 0..8 {
+            let suggestion = best_action(&game, 2, FlipPolicy::All, &mut StdRng::seed_from_u64(seed)).unwrap();
+            let mut search = Search::new(&game.rules, game.current_player, FlipPolicy::All);
+            let value = search.action_value(&game.board, game.current_player, suggestion.action, 1, f64::NEG_INFINITY, f64::INFINITY);
+            assert!((value - suggestion.value).abs() < 1e-9, "{:?} is worth {}, not {}", suggestion.action, value, suggestion.value);
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use super::Agent;
+use crate::*;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant; // The standard clock panics in the browser
+
+// Information-set Monte Carlo tree search (single observer). Each iteration deals the hidden
+// pieces at random, consistent with everything revealed so far, and walks one shared tree
+// using only the actions legal in that deal. Flips therefore collect statistics over every
+// piece they might reveal.
+
+#[derive(Debug, Clone, Copy)]
+pub struct IsMctsConfig {
+    pub playouts: usize, // Iterations per decision
+    pub time_budget: Option<Duration>, // Stops early once this much time has passed
+    pub exploration: f64, // UCB exploration constant
+    pub playout_depth: usize, // Plies of random play before a playout is scored by material
+}
+
+impl Default for IsMctsConfig {
+    fn default() -> Self {
+        IsMctsConfig { playouts: 1000, time_budget: None, exploration: 0.7, playout_depth: 60 }
+    }
+}
+
+struct Node {
+    action: Option<ActionType>, // None at the root
+    mover: Option<Player>, // Color of the player who made the action
+    parent: Option<usize>,
+    children: Vec<usize>,
+    visits: u32,
+    reward: f64, // Total reward from the mover's point of view
+    availability: u32, // Times the action was legal when its parent was visited
+}
+
+pub fn determinize(game: &GameState, rng: &mut StdRng) -> GameState {
+    // Shuffle the hidden pieces among the hidden squares; only the multiset is public
+    let mut sample = game.clone();
+    let mut pool = hidden_pieces(&sample.board);
+    pool.shuffle(rng);
+    for cell in sample.board.iter_mut().flatten() {
+        if let Cell::Hidden(Some(_)) = cell {
+            *cell = Cell::Hidden(pool.pop());
+        }
+    }
+    sample
+}
+
+fn reward_for(state: &GameState, player: Player) -> f64 {
+    match state.result() {
+        Some(GameResult::Winner(winner)) => f64::from(u8::from(winner == player)),
+        Some(GameResult::Draw(_)) => 0.5,
+        // Unfinished playouts lean towards whoever is ahead on material
+        None => 0.5 + 0.5 * (f64::from(evaluate(&state.board, player)) / 10.0).tanh(),
+    }
+}
+
+/// Runs the search and returns the most visited action at the root.
+pub fn ismcts_search(game: &GameState, config: &IsMctsConfig, rng: &mut StdRng) -> Option<ActionType> {
+    ismcts_search_until(game, config, rng, &AtomicBool::new(false))
+}
+
+/// Like `ismcts_search`, but also stops as soon as `stop` is set, e.g. from another thread.
+pub fn ismcts_search_until(game: &GameState, config: &IsMctsConfig, rng: &mut StdRng, stop: &AtomicBool) -> Option<ActionType> {
+    let root_actions = game.legal_actions();
+    if root_actions.len() <= 1 {
+        return root_actions.first().copied();
+    }
+
+    let started = Instant::now();
+    let mut nodes = vec![Node { action: None, mover: None, parent: None, children: Vec::new(), visits: 0, reward: 0.0, availability: 0 }];
+
+    for _ in 0..config.playouts {
+        if stop.load(Ordering::Relaxed) || config.time_budget.is_some_and(|budget| started.elapsed() >= budget) {
+            break;
+        }
+        let mut state = determinize(game, rng);
+        let mut current = 0;
+
+        // Selection and expansion
+        while state.result().is_none() {
+            let legal = state.legal_actions();
+            let compatible: Vec<usize> = nodes[current]
+                .children
+                .iter()
+                .copied()
+                .filter(|&child| nodes[child].action.is_some_and(|action| legal.contains(&action)))
+                .collect();
+            for &child in &compatible {
+                nodes[child].availability += 1;
+            }
+
+            let untried: Vec<ActionType> = legal
+                .iter()
+                .copied()
+                .filter(|action| !compatible.iter().any(|&child| nodes[child].action == Some(*action)))
+                .collect();
+            if let Some(&action) = untried.choose(rng) {
+                if state.apply_action(action).is_err() {
+                    break;
+                }
+                nodes.push(Node {
+                    action: Some(action),
+                    mover: Some(opponent(state.current_player)),
+                    parent: Some(current),
+                    children: Vec::new(),
+                    visits: 0,
+                    reward: 0.0,
+                    availability: 1,
+                });
+                let child = nodes.len() - 1;
+                nodes[current].children.push(child);
+                current = child;
+                break;
+            }
+
+            let ucb = |child: usize| {
+                let node = &nodes[child];
+                node.reward / f64::from(node.visits) + config.exploration * (f64::from(node.availability).ln() / f64::from(node.visits)).sqrt()
+            };
+            let Some(&chosen) = compatible.iter().max_by(|&&a, &&b| ucb(a).total_cmp(&ucb(b))) else {
+                break;
+            };
+            let action = nodes[chosen].action.expect("Only the root has no action");
+            if state.apply_action(action).is_err() {
+                break;
+            }
+            current = chosen;
+        }
+
+        // Random playout from the new node
+        for _ in 0..config.playout_depth {
+            if state.result().is_some() {
+                break;
+            }
+            match state.legal_actions().choose(rng) {
+                Some(&action) if state.apply_action(action).is_ok() => {},
+                _ => break,
+            }
+        }
+
+        // Backpropagation
+        let mut walk = Some(current);
+        while let Some(index) = walk {
+            let node = &mut nodes[index];
+            node.visits += 1;
+            if let Some(mover) = node.mover {
+                node.reward += reward_for(&state, mover);
+            }
+            walk = node.parent;
+        }
+    }
+
+    nodes[0].children.iter().max_by_key(|&&child| nodes[child].visits).and_then(|&child| nodes[child].action)
+}
+
+/// Plays the action chosen by ISMCTS.
+pub struct IsMctsAgent {
+    config: IsMctsConfig,
+    rng: StdRng,
+}
+
+impl IsMctsAgent {
+    pub fn new(config: IsMctsConfig, rng: StdRng) -> Self {
+        IsMctsAgent { config, rng }
+    }
+}
+
+impl Agent for IsMctsAgent {
+    fn name(&self) -> &str {
+        "ISMCTS AI"
+    }
+
+    fn choose_action(&mut self, game: &GameState) -> Option<ActionType> {
+        ismcts_search(game, &self.config, &mut self.rng)
+    }
+}
@@ -0,0 +1,94 @@
+use super::ismcts::determinize;
+use super::piece_value;
+use crate::*;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+
+// Win chances estimated by playing the position out many times. Every playout deals the hidden
+// pieces afresh, consistent with everything revealed so far, so the estimate doesn't lean on
+// the one deal actually on the board.
+
+/// Plies after which a playout is stopped and counted as a draw.
+pub const PLAYOUT_PLY_LIMIT: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayoutPolicy {
+    Random, // Uniformly random legal actions
+    Captures, // The most valuable capture on offer, otherwise a random action
+}
+
+pub fn parse_playout_policy(name: &str) -> Option<PlayoutPolicy> {
+    match name {
+        "random" => Some(PlayoutPolicy::Random),
+        "captures" => Some(PlayoutPolicy::Captures),
+        _ => None,
+    }
+}
+
+/// How a number of playouts ended.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WinRate {
+    pub playouts: usize,
+    pub red_wins: usize,
+    pub black_wins: usize,
+    pub draws: usize,
+    pub unfinished: usize, // Draws that were stopped at the ply limit
+}
+
+impl WinRate {
+    /// The shares of the playouts `player` won, drew and lost, as percentages.
+    pub fn percentages(&self, player: Player) -> (f64, f64, f64) {
+        let (wins, losses) = match player {
+            Player::Red => (self.red_wins, self.black_wins),
+            Player::Black => (self.black_wins, self.red_wins),
+        };
+        let percent = |count: usize| if self.playouts == 0 { 0.0 } else { 100.0 * count as f64 / self.playouts as f64 };
+        (percent(wins), percent(self.draws), percent(losses))
+    }
+}
+
+fn playout_action(game: &GameState, policy: PlayoutPolicy, rng: &mut StdRng) -> Option<ActionType> {
+    let actions = game.legal_actions();
+    let capture_value = |action: &ActionType| match *action {
+        ActionType::Move { to_x, to_y, .. } => match game.board.cell_at(to_x, to_y) {
+            Some(Cell::Revealed(piece)) => Some(piece_value(piece.piece_type)),
+            _ => None,
+        },
+        ActionType::Flip { .. } => None,
+    };
+    match policy {
+        PlayoutPolicy::Captures => actions
+            .iter()
+            .filter_map(|action| capture_value(action).map(|value| (action, value)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(&action, _)| action)
+            .or_else(|| actions.choose(rng).copied()),
+        PlayoutPolicy::Random => actions.choose(rng).copied(),
+    }
+}
+
+/// Plays `playouts` games out from `game`, each from its own deal of the hidden pieces, and
+/// counts how they ended.
+pub fn estimate_winrate(game: &GameState, playouts: usize, policy: PlayoutPolicy, rng: &mut StdRng) -> WinRate {
+    let mut tally = WinRate { playouts, ..WinRate::default() };
+    for _ in 0..playouts {
+        let mut state = determinize(game, rng);
+        let start = state.history.len();
+        while state.result().is_none() && state.history.len() - start < PLAYOUT_PLY_LIMIT {
+            match playout_action(&state, policy, rng) {
+                Some(action) if state.apply_action(action).is_ok() => {},
+                _ => break,
+            }
+        }
+        match state.result() {
+            Some(GameResult::Winner(Player::Red)) => tally.red_wins += 1,
+            Some(GameResult::Winner(Player::Black)) => tally.black_wins += 1,
+            Some(GameResult::Draw(_)) => tally.draws += 1,
+            None => {
+                tally.draws += 1;
+                tally.unfinished += 1;
+            },
+        }
+    }
+    tally
+}
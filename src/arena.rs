@@ -0,0 +1,267 @@
+use crate::ai::{parse_difficulty, parse_engine, Agent, AiSettings};
+use crate::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+// Games between bots with nobody at the keyboard, for measuring one engine against another.
+
+/// Reads an engine description: a difficulty (`easy`, `medium`, `hard`, `max`) or an engine
+/// (`random`, `search`, `ismcts`), optionally followed by settings that override its defaults,
+/// e.g. `search:depth=3` or `ismcts:playouts=500,time=200`.
+pub fn parse_engine_spec(spec: &str) -> Result<AiSettings, String> {
+    let (name, options) = spec.split_once(':').unwrap_or((spec, ""));
+    let mut settings = match (parse_difficulty(name), parse_engine(name)) {
+        (Some(difficulty), _) => AiSettings::for_difficulty(difficulty),
+        (None, Some(engine)) => AiSettings { engine, ..AiSettings::for_difficulty(ai::Difficulty::Easy) },
+        (None, None) => return Err(format!("unknown engine {:?}: expected a difficulty or random, search or ismcts", name)),
+    };
+    for option in options.split(',').filter(|option| !option.is_empty()) {
+        let (key, value) = option.split_once('=').ok_or_else(|| format!("expected key=value in {:?}", option))?;
+        let number: u64 = value.parse().map_err(|_| format!("{} must be a whole number, not {:?}", key, value))?;
+        match key {
+            "depth" => settings.depth = number as u32,
+            "playouts" => settings.mcts.playouts = number as usize,
+            "time" => settings.mcts.time_budget = Some(Duration::from_millis(number)),
+            _ => return Err(format!("unknown engine setting {:?}: expected depth, playouts or time", key)),
+        }
+    }
+    Ok(settings)
+}
+
+/// How one arena game went.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ArenaGame {
+    pub points: Option<[f64; 2]>, // For the opening seat and the other, None if it was stopped
+    pub plies: usize,
+}
+
+/// Plays `game` to the end between two agents, the first making the opening flip. A game still
+/// going after `max_plies`, or one an agent gives up on, is stopped without a result.
+pub fn play_game(mut game: GameState, agents: &mut [Box<dyn Agent>; 2], max_plies: usize) -> ArenaGame {
+    while !game.is_game_over() && game.history.len() < max_plies {
+        let seat = if game.first_player_to_move() { 0 } else { 1 };
+        let Some(action) = agents[seat].choose_action(&game) else { break };
+        if game.apply_action(action).is_err() {
+            break;
+        }
+    }
+    for agent in agents.iter_mut() {
+        agent.game_over(&game, None);
+    }
+    ArenaGame { points: game.seat_points(), plies: game.history.len() }
+}
+
+/// One engine's results over a series of games.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Tally {
+    pub games: usize,
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+    pub unfinished: usize, // Stopped at the ply limit, counted as draws
+    pub plies: usize,
+}
+
+impl Tally {
+    /// Adds a game in which this engine scored `points`, `None` for a stopped game.
+    pub fn add(&mut self, points: Option<f64>, plies: usize) {
+        self.games += 1;
+        self.plies += plies;
+        match points {
+            Some(points) if points > 0.5 => self.wins += 1,
+            Some(points) if points < 0.5 => self.losses += 1,
+            Some(_) => self.draws += 1,
+            None => {
+                self.draws += 1;
+                self.unfinished += 1;
+            },
+        }
+    }
+
+    /// Wins plus half the draws.
+    pub fn score(&self) -> f64 {
+        self.wins as f64 + self.draws as f64 / 2.0
+    }
+
+    pub fn average_length(&self) -> f64 {
+        if self.games == 0 { 0.0 } else { self.plies as f64 / self.games as f64 }
+    }
+}
+
+/// Plays `games` games between two engines, each dealt from its own seed counting up from
+/// `seed`. The engines take turns making the opening flip. `report` is called after every
+/// game with its number and result, and the returned tally is from the first engine's side.
+pub fn run_selfplay(
+    engines: [AiSettings; 2],
+    games: usize,
+    seed: u64,
+    rules: RuleConfig,
+    max_plies: usize,
+    mut report: impl FnMut(usize, &ArenaGame, bool),
+) -> Result<Tally, GameError> {
+    let mut tally = Tally::default();
+    for number in 0..games {
+        let mut rng = StdRng::seed_from_u64(seed.wrapping_add(number as u64));
+        let game = GameState::with_rng(rules, &mut rng)?;
+        // Odd-numbered games give the opening flip to the second engine
+        let swapped = number % 2 == 1;
+        let order = if swapped { [engines[1], engines[0]] } else { engines };
+        let mut agents = order.map(|settings| settings.build_agent(StdRng::from_rng(&mut rng).expect("Seeded RNG cannot fail")));
+        let played = play_game(game, &mut agents, max_plies);
+        tally.add(played.points.map(|points| points[usize::from(swapped)]), played.plies);
+        report(number + 1, &played, swapped);
+    }
+    Ok(tally)
+}
+
+/// One game of a tournament, with the engines by their index in the field.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TournamentGame {
+    pub engines: [usize; 2], // The engine making the opening flip, then the other
+    pub seed: u64,
+    pub played: ArenaGame,
+}
+
+/// Points each engine scored against each other engine, and the games they played.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Crosstable {
+    pub points: Vec<Vec<f64>>, // points[a][b] is what a scored against b
+    pub games: Vec<Vec<usize>>,
+}
+
+impl Crosstable {
+    pub fn new(engines: usize) -> Self {
+        Crosstable { points: vec![vec![0.0; engines]; engines], games: vec![vec![0; engines]; engines] }
+    }
+
+    /// Adds a game, counting one stopped at the ply limit as a draw.
+    pub fn add(&mut self, game: &TournamentGame) {
+        let [first, second] = game.engines;
+        let points = game.played.points.unwrap_or([0.5, 0.5]);
+        self.points[first][second] += points[0];
+        self.points[second][first] += points[1];
+        self.games[first][second] += 1;
+        self.games[second][first] += 1;
+    }
+
+    pub fn score(&self, engine: usize) -> f64 {
+        self.points[engine].iter().sum()
+    }
+
+    pub fn games_played(&self, engine: usize) -> usize {
+        self.games[engine].iter().sum()
+    }
+
+    /// Elo performance ratings that explain the results, averaging `INITIAL_RATING` over the
+    /// field. Each engine's rating is its opponents' average plus what its score against them
+    /// is worth, repeated until the ratings settle.
+    pub fn performance_ratings(&self) -> Vec<f64> {
+        let count = self.points.len();
+        let mut ratings = vec![profiles::INITIAL_RATING; count];
+        for _ in 0..100 {
+            let next: Vec<f64> = (0..count)
+                .map(|engine| {
+                    let games = self.games_played(engine);
+                    if games == 0 {
+                        return ratings[engine];
+                    }
+                    let opponents: f64 = (0..count).map(|other| ratings[other] * self.games[engine][other] as f64).sum::<f64>() / games as f64;
+                    // A perfect or zero score has no finite rating, so it is held just short of one
+                    let share = (self.score(engine) / games as f64).clamp(0.01, 0.99);
+                    opponents + 400.0 * (share / (1.0 - share)).log10()
+                })
+                .collect();
+            let shift = profiles::INITIAL_RATING - next.iter().sum::<f64>() / count.max(1) as f64;
+            // Moving halfway each time keeps two engines from swapping ratings back and forth
+            ratings = ratings.iter().zip(&next).map(|(old, new)| (old + new + shift) / 2.0).collect();
+        }
+        ratings
+    }
+}
+
+/// Plays every pair of `engines` against each other `games_per_pairing` times, alternating the
+/// opening flip, on up to `threads` threads at once. Game `n` is dealt from `seed + n` however
+/// the games are scheduled. `report` is called on this thread as each game finishes.
+pub fn run_tournament(
+    engines: &[AiSettings],
+    games_per_pairing: usize,
+    seed: u64,
+    rules: RuleConfig,
+    max_plies: usize,
+    threads: usize,
+    mut report: impl FnMut(&TournamentGame),
+) -> Result<Crosstable, GameError> {
+    // Check the rules can deal a game before any thread tries
+    GameState::with_rng(rules, &mut StdRng::seed_from_u64(seed))?;
+
+    let mut schedule = Vec::new();
+    for first in 0..engines.len() {
+        for second in first + 1..engines.len() {
+            for round in 0..games_per_pairing {
+                schedule.push(if round % 2 == 0 { [first, second] } else { [second, first] });
+            }
+        }
+    }
+
+    // Each thread takes the next unplayed game until the schedule runs out
+    let next = AtomicUsize::new(0);
+    let mut table = Crosstable::new(engines.len());
+    thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+        for _ in 0..threads.clamp(1, schedule.len().max(1)) {
+            let (sender, schedule, next) = (sender.clone(), &schedule, &next);
+            scope.spawn(move || loop {
+                let number = next.fetch_add(1, Ordering::Relaxed);
+                let Some(&pairing) = schedule.get(number) else { break };
+                let game_seed = seed.wrapping_add(number as u64);
+                let mut rng = StdRng::seed_from_u64(game_seed);
+                let game = GameState::with_rng(rules, &mut rng).expect("The deal was checked before the threads started");
+                let mut agents = pairing.map(|engine| engines[engine].build_agent(StdRng::from_rng(&mut rng).expect("Seeded RNG cannot fail")));
+                let played = play_game(game, &mut agents, max_plies);
+                if sender.send(TournamentGame { engines: pairing, seed: game_seed, played }).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+        for game in receiver {
+            table.add(&game);
+            report(&game);
+        }
+    });
+    Ok(table)
+}
+
+/// The crosstable as text: one row per engine with its score against each opponent, its total
+/// and its performance rating, strongest first.
+pub fn format_crosstable(names: &[String], table: &Crosstable) -> String {
+    let ratings = table.performance_ratings();
+    let mut order: Vec<usize> = (0..names.len()).collect();
+    order.sort_by(|&a, &b| table.score(b).total_cmp(&table.score(a)).then(ratings[b].total_cmp(&ratings[a])));
+
+    let width = names.iter().map(|name| name.len()).max().unwrap_or(0).max("Engine".len());
+    let mut header = format!("{:>3}  {:<width$}", "#", "Engine", width = width);
+    for column in 1..=names.len() {
+        header.push_str(&format!(" {:>5}", column));
+    }
+    header.push_str(&format!(" {:>9} {:>6}", "Score", "Perf"));
+
+    let mut lines = vec![header];
+    for (rank, &engine) in order.iter().enumerate() {
+        let mut line = format!("{:>3}  {:<width$}", rank + 1, names[engine], width = width);
+        for &other in &order {
+            if other == engine {
+                line.push_str(&format!(" {:>5}", "-"));
+            } else {
+                line.push_str(&format!(" {:>5}", table.points[engine][other]));
+            }
+        }
+        line.push_str(&format!(" {:>9} {:>6.0}", format!("{}/{}", table.score(engine), table.games_played(engine)), ratings[engine]));
+        lines.push(line);
+    }
+    lines.join("\n")
+}
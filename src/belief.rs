@@ -0,0 +1,102 @@
+use crate::*;
+
+/// Pieces not yet turned face up, with how many of each remain. Which square holds which piece
+/// is unknown to the players, so only these counts can be reasoned about.
+pub type PiecePool = Vec<(Piece, usize)>;
+
+fn add_piece(pool: &mut PiecePool, piece: Piece) {
+    match pool.iter_mut().find(|(p, _)| *p == piece) {
+        Some((_, count)) => *count += 1,
+        None => pool.push((piece, 1)),
+    }
+}
+
+/// The pool as the board's hidden squares actually hold it.
+pub fn hidden_pool<B: Grid>(board: &B) -> PiecePool {
+    let mut pool = PiecePool::new();
+    for piece in hidden_pieces(board) {
+        add_piece(&mut pool, piece);
+    }
+    pool
+}
+
+/// The pool as the players can work it out: the set in play, less every piece they have seen
+/// face up on the board or in a capture. A position not dealt from that set, such as an edited
+/// one, may hide fewer pieces than this; the pool then holds every piece that could still be
+/// face down, since which ones are missing can't be known without looking.
+pub fn unseen_pool(game: &GameState) -> PiecePool {
+    let mut pool = PiecePool::new();
+    for player in [Player::Red, Player::Black] {
+        for &(piece_type, count) in &game.rules.composition {
+            let piece = Piece { player, piece_type };
+            let seen = game.board.squares().filter(|&(_, _, cell)| cell == Cell::Revealed(piece)).count()
+                + game.captured.iter().flatten().filter(|&&captured| captured == piece).count();
+            if count > seen {
+                pool.push((piece, count - seen));
+            }
+        }
+    }
+    pool
+}
+
+/// The chance of each piece type, either color, turning up on any one hidden square, most
+/// likely first.
+pub fn flip_odds(pool: &PiecePool) -> Vec<(PieceType, f64)> {
+    let total: usize = pool.iter().map(|(_, count)| count).sum();
+    let mut odds: Vec<(PieceType, f64)> = STANDARD_COMPOSITION
+        .iter()
+        .map(|&(piece_type, _)| {
+            let count: usize = pool.iter().filter(|(piece, _)| piece.piece_type == piece_type).map(|(_, count)| count).sum();
+            (piece_type, count as f64 / total.max(1) as f64)
+        })
+        .filter(|&(_, chance)| chance > 0.0)
+        .collect();
+    // The sort is stable, so equal chances stay in rank order
+    odds.sort_by(|a, b| b.1.total_cmp(&a.1));
+    odds
+}
+
+/// The chance of a flip revealing one of `player`'s pieces.
+pub fn color_odds(pool: &PiecePool, player: Player) -> f64 {
+    let total: usize = pool.iter().map(|(_, count)| count).sum();
+    let own: usize = pool.iter().filter(|(piece, _)| piece.player == player).map(|(_, count)| count).sum();
+    own as f64 / total.max(1) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn sorted(mut pool: PiecePool) -> PiecePool {
+        pool.sort_by_key(|&(piece, _)| (piece.player == Player::Black, piece_rank(piece.piece_type)));
+        pool
+    }
+
+    #[test]
+    fn unseen_pool_matches_the_hidden_squares_of_a_dealt_game() {
+        let mut rng = StdRng::seed_from_u64(787);
+        let mut game = GameState::with_rng(RuleConfig::default(), &mut rng).unwrap();
+        for (x, y) in [(0, 0), (3, 1), (7, 3)] {
+            game.apply_action(ActionType::Flip { x, y }).unwrap();
+        }
+        assert_eq!(sorted(unseen_pool(&game)), sorted(hidden_pool(&game.board)));
+    }
+
+    #[test]
+    fn unseen_pool_never_reads_the_hidden_squares() {
+        // Two face-down squares on an edited board: the pool can't tell which two pieces they are
+        let mut board = vec![vec![Cell::Empty; BOARD_WIDTH]; BOARD_HEIGHT];
+        board[0][0] = Cell::Revealed(Piece { player: Player::Red, piece_type: PieceType::General });
+        board[1][1] = Cell::Hidden(Some(Piece { player: Player::Black, piece_type: PieceType::General }));
+        board[2][2] = Cell::Hidden(Some(Piece { player: Player::Red, piece_type: PieceType::Soldier }));
+        let game = GameState::from_board(board.clone(), Player::Red, RuleConfig::default());
+        let pool = unseen_pool(&game);
+        assert_eq!(pool.iter().map(|(_, count)| count).sum::<usize>(), 31);
+        assert!(!pool.iter().any(|(piece, _)| *piece == Piece { player: Player::Red, piece_type: PieceType::General }));
+
+        board[1][1] = Cell::Hidden(Some(Piece { player: Player::Black, piece_type: PieceType::Horse }));
+        assert_eq!(unseen_pool(&GameState::from_board(board, Player::Red, RuleConfig::default())), pool);
+    }
+}
@@ -0,0 +1,447 @@
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use rust_dark_chess::ai::*;
+use rust_dark_chess::display::Theme;
+use rust_dark_chess::locale::Language;
+use rust_dark_chess::review::MISTAKE_THRESHOLD;
+use rust_dark_chess::*;
+use std::path::PathBuf;
+
+#[derive(Debug, Parser)]
+#[command(name = "rust_dark_chess", version, about = "Chinese Dark Chess (banqi) in the terminal")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    // Without a subcommand the game flags apply to `play`
+    #[command(flatten)]
+    pub play: PlayArgs,
+
+    #[command(flatten)]
+    pub rules: RuleArgs,
+
+    #[command(flatten)]
+    pub display: DisplayArgs,
+
+    /// Reads defaults from this file instead of ~/.config/dark_chess/config.toml
+    #[arg(long, global = true, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Plays a game at the keyboard (the default without a subcommand)
+    Play(PlayArgs),
+    /// Plays against the computer, like `play --vs-ai`
+    Ai(PlayArgs),
+    /// Hosts a game for a player joining over the network; the host makes the opening flip
+    Serve {
+        #[arg(long, default_value_t = 7878)]
+        port: u16,
+        #[command(flatten)]
+        play: PlayArgs,
+    },
+    /// Joins a game hosted with `serve`
+    Connect {
+        /// Host and port to join, e.g. 192.168.1.20:7878
+        address: String,
+        /// Full-screen interface
+        #[arg(long)]
+        tui: bool,
+    },
+    /// Hosts games for browser or app clients over WebSockets, with a lobby and spectators
+    ServeWs {
+        #[arg(long, default_value_t = 8080)]
+        port: u16,
+        /// Shows spectators each update this many seconds after the players
+        #[arg(long, value_name = "SECONDS", default_value_t = 0)]
+        spectator_delay: u64,
+        #[command(flatten)]
+        setup: SetupArgs,
+    },
+    /// Steps through a saved game record
+    Replay { file: String },
+    /// Searches a position and reports the best action for the side to move
+    Analyze(AnalyzeArgs),
+    /// Counts the action sequences from a position, for checking the move generator
+    Perft(PerftArgs),
+    /// Plays two bots against each other over a series of games and reports the results
+    Selfplay(SelfplayArgs),
+    /// Plays every pair of bots in a list against each other and ranks them
+    Tournament(TournamentArgs),
+    /// Speaks a line-based protocol modelled on UCI, for GUIs and match runners
+    Engine,
+    /// Shows the settings in effect after reading the config file and flags
+    Config,
+    /// Shows the Elo ratings of named players
+    Ratings,
+    /// Shows lifetime statistics of named players
+    Stats {
+        /// Only this player
+        name: Option<String>,
+    },
+    /// Lists and shows the finished games kept in the game database
+    History {
+        #[command(subcommand)]
+        command: HistoryCommand,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum HistoryCommand {
+    /// Lists the stored games, oldest first, with the score over them
+    List(HistoryFilterArgs),
+    /// Prints the record of one stored game
+    Show {
+        /// Game number from `history list`
+        id: i64,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct HistoryFilterArgs {
+    /// Only games this player took part in; the score is then from their side
+    #[arg(long, value_name = "NAME")]
+    pub player: Option<String>,
+    /// Only games against this player, or with them on either side without --player
+    #[arg(long, value_name = "NAME")]
+    pub opponent: Option<String>,
+    /// Only games that ended this way
+    #[arg(long, value_enum)]
+    pub result: Option<HistoryResult>,
+    /// Only games played on or after this date, e.g. 2026-10-01
+    #[arg(long, value_name = "DATE", value_parser = date_arg)]
+    pub since: Option<String>,
+    /// Only games played on or before this date
+    #[arg(long, value_name = "DATE", value_parser = date_arg)]
+    pub until: Option<String>,
+}
+
+/// How a stored game ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum HistoryResult {
+    /// Red won
+    Red,
+    /// Black won
+    Black,
+    Draw,
+}
+
+impl HistoryResult {
+    pub fn token(self) -> &'static str {
+        match self {
+            HistoryResult::Red => "1-0",
+            HistoryResult::Black => "0-1",
+            HistoryResult::Draw => "1/2-1/2",
+        }
+    }
+}
+
+/// Regional rules for how pieces move and capture.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum RuleVariant {
+    #[default]
+    Taiwanese,
+    HongKong,
+}
+
+/// Optional house rules.
+#[derive(Debug, Args)]
+pub struct RuleArgs {
+    /// Which regional rules to play by
+    #[arg(long, global = true, value_enum, default_value_t = RuleVariant::Taiwanese)]
+    pub variant: RuleVariant,
+    /// Cannons may also capture a hidden piece by jumping onto it
+    #[arg(long, global = true)]
+    pub cannon_captures_hidden: bool,
+    /// Cannons move only one square when they aren't capturing
+    #[arg(long, global = true)]
+    pub cannon_single_step: bool,
+    /// An action that eliminates both sides wins for its player instead of drawing
+    #[arg(long, global = true)]
+    pub elimination_last_mover_wins: bool,
+    /// Leave squares the piece set doesn't fill empty instead of refusing to start
+    #[arg(long, global = true)]
+    pub pad_board: bool,
+    /// Draw after this many plies in a row without a capture or flip
+    #[arg(long, global = true, value_name = "PLIES")]
+    pub no_progress_limit: Option<usize>,
+    /// Pieces each player starts with, e.g. "K1A2E2R2H2C2P5" [default: the standard set]
+    #[arg(long, global = true, value_name = "SET", value_parser = composition_arg)]
+    pub pieces: Option<Composition>,
+}
+
+impl RuleArgs {
+    pub fn rules(&self) -> RuleConfig {
+        RuleConfig {
+            variant: match self.variant {
+                RuleVariant::Taiwanese => Variant::Taiwanese,
+                RuleVariant::HongKong => Variant::HongKong,
+            },
+            cannon_can_capture_hidden: self.cannon_captures_hidden,
+            cannon_single_step: self.cannon_single_step,
+            simultaneous_elimination: if self.elimination_last_mover_wins { EliminationOutcome::LastMoverWins } else { EliminationOutcome::Draw },
+            board_fill: if self.pad_board { BoardFill::PadWithEmpty } else { BoardFill::Exact },
+            no_progress_limit: self.no_progress_limit,
+            composition: self.pieces.unwrap_or(STANDARD_COMPOSITION),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SymbolSet {
+    Chinese,
+    English,
+    Letters,
+}
+
+/// How the board and moves are shown.
+#[derive(Debug, Args)]
+pub struct DisplayArgs {
+    /// Color theme: classic, high-contrast or colorblind [default: classic]
+    #[arg(long, global = true, value_name = "NAME", value_parser = theme_arg)]
+    pub theme: Option<Theme>,
+    /// ANSI SGR code for Red pieces, e.g. "1;91"
+    #[arg(long, global = true, value_name = "CODE")]
+    pub red_color: Option<String>,
+    /// ANSI SGR code for Black pieces
+    #[arg(long, global = true, value_name = "CODE")]
+    pub black_color: Option<String>,
+    /// Disables all colored output
+    #[arg(long, global = true)]
+    pub no_color: bool,
+    /// Piece symbols on the board and in move lists [default: chinese]
+    #[arg(long, global = true, value_enum)]
+    pub symbols: Option<SymbolSet>,
+    /// Describes the board and moves in words for screen readers
+    #[arg(long, global = true)]
+    pub narrate: bool,
+    /// Draws the board with ASCII only, for terminals without Chinese fonts
+    #[arg(long, global = true)]
+    pub ascii: bool,
+    /// Labels the board and prints squares as algebraic names like c2
+    #[arg(long, global = true)]
+    pub algebraic: bool,
+    /// Draws the material balance under the board
+    #[arg(long, global = true)]
+    pub material_bar: bool,
+    /// Shows the engine's evaluation of each position beside the board, searched in the background
+    #[arg(long, global = true)]
+    pub eval_bar: bool,
+    /// Language of prompts, messages and help: en or zh-TW [default: en]
+    #[arg(long, global = true, value_name = "TAG", value_parser = language_arg)]
+    pub lang: Option<Language>,
+}
+
+/// Where a game starts from.
+#[derive(Debug, Clone, Args)]
+pub struct SetupArgs {
+    /// Seeds the shuffle so the same number deals the same layout
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// Starts with this many randomly chosen pieces face up
+    #[arg(long, value_name = "COUNT", default_value_t = 0)]
+    pub reveal: usize,
+    /// Starts from a position in FEN-like notation instead of a fresh deal
+    #[arg(long, value_name = "POSITION")]
+    pub fen: Option<String>,
+    /// Starts from the position saved in a file, like --fen
+    #[arg(long = "position", value_name = "FILE", value_parser = position_file_arg, conflicts_with = "fen")]
+    pub position_file: Option<String>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct PlayArgs {
+    #[command(flatten)]
+    pub setup: SetupArgs,
+    /// Plays against a bot
+    #[arg(long)]
+    pub vs_ai: bool,
+    /// Names a player for ratings, in turn order: first the opening flip, then the other
+    #[arg(long = "player", value_name = "NAME", num_args = 1, action = clap::ArgAction::Append)]
+    pub players: Vec<String>,
+    /// Time control as minutes plus increment seconds, e.g. 5+3; untimed without it
+    #[arg(long, value_name = "MIN+INC")]
+    pub time: Option<String>,
+    /// Plays a best-of match of this many games, swapping who makes the opening flip each game
+    #[arg(long = "match", value_name = "GAMES", value_parser = match_length_arg)]
+    pub match_length: Option<u32>,
+    /// Opens the position editor before the first action
+    #[arg(long, conflicts_with = "match_length")]
+    pub edit: bool,
+    /// Clears the screen between turns of two players sharing the keyboard
+    #[arg(long)]
+    pub hot_seat: bool,
+    /// Full-screen interface: pick squares with the arrow keys and Enter instead of typing them
+    #[arg(long)]
+    pub tui: bool,
+    /// Reviews the finished game, flagging mistakes and summing up each side's accuracy
+    #[arg(long)]
+    pub review: bool,
+    #[command(flatten)]
+    pub ai: AiArgs,
+}
+
+/// Choice and strength of the bot.
+#[derive(Debug, Clone, Args)]
+pub struct AiArgs {
+    /// Lets the bot make the opening flip
+    #[arg(long)]
+    pub ai_first: bool,
+    /// Strength preset: easy, medium, hard or max [default: easy]
+    #[arg(long, value_parser = difficulty_arg)]
+    pub difficulty: Option<Difficulty>,
+    /// Bot engine: random, search or ismcts
+    #[arg(long, value_parser = engine_arg)]
+    pub ai: Option<Engine>,
+    /// Plies searched by the search bot
+    #[arg(long, value_name = "PLIES")]
+    pub ai_depth: Option<u32>,
+    /// Playouts per move for the ismcts bot
+    #[arg(long, value_name = "COUNT")]
+    pub ai_playouts: Option<usize>,
+    /// Time per move for the ismcts bot, in milliseconds
+    #[arg(long, value_name = "MS")]
+    pub ai_time: Option<u64>,
+}
+
+impl AiArgs {
+    pub fn settings(&self, default_difficulty: Difficulty) -> AiSettings {
+        // Start from the difficulty preset, then apply any finer-grained overrides
+        let mut settings = AiSettings::for_difficulty(self.difficulty.unwrap_or(default_difficulty));
+        if let Some(engine) = self.ai {
+            settings.engine = engine;
+        }
+        if let Some(depth) = self.ai_depth {
+            settings.depth = depth;
+        }
+        if let Some(playouts) = self.ai_playouts {
+            settings.mcts.playouts = playouts;
+        }
+        if let Some(millis) = self.ai_time {
+            settings.mcts.time_budget = Some(std::time::Duration::from_millis(millis));
+        }
+        settings
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct AnalyzeArgs {
+    /// Position to analyze, in FEN-like notation
+    #[arg(conflicts_with = "record")]
+    pub position: Option<String>,
+    /// Analyzes the final position of a saved game record instead
+    #[arg(long, value_name = "FILE")]
+    pub record: Option<String>,
+    /// Plies to search
+    #[arg(long, default_value_t = 2)]
+    pub depth: u32,
+    /// Seeds the deal of hidden squares written as '?'
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// Sets up the position in the editor first, starting from an empty board without a position
+    #[arg(long)]
+    pub edit: bool,
+    /// Goes over every action of the --record game instead, flagging mistakes and summing up each side's accuracy
+    #[arg(long, requires = "record", conflicts_with_all = ["position", "edit"])]
+    pub review: bool,
+    /// Loss in evaluation beyond which --review flags an action as a mistake
+    #[arg(long, value_name = "LOSS", default_value_t = MISTAKE_THRESHOLD, requires = "review")]
+    pub threshold: f64,
+}
+
+#[derive(Debug, Args)]
+pub struct PerftArgs {
+    /// Deepest ply to count
+    pub depth: u32,
+    /// Counts from this position instead of a fresh deal
+    #[arg(long, value_name = "POSITION")]
+    pub fen: Option<String>,
+    /// Counts from the position saved in a file
+    #[arg(long = "position", value_name = "FILE", value_parser = position_file_arg, conflicts_with = "fen")]
+    pub position_file: Option<String>,
+    /// Seeds the deal
+    #[arg(long)]
+    pub seed: Option<u64>,
+}
+
+#[derive(Debug, Args)]
+pub struct SelfplayArgs {
+    /// The two engines: a difficulty, or random, search or ismcts with optional settings, e.g. search:depth=3
+    #[arg(num_args = 2, required = true, value_name = "ENGINE")]
+    pub engines: Vec<String>,
+    /// Games to play; the engines take turns making the opening flip
+    #[arg(long, default_value_t = 10)]
+    pub games: usize,
+    /// Seeds the first deal; each further game uses the next number
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// Stops a game that has gone on this long, counting it as a draw
+    #[arg(long, value_name = "PLIES", default_value_t = 1000)]
+    pub max_plies: usize,
+}
+
+#[derive(Debug, Args)]
+pub struct TournamentArgs {
+    /// Two or more engines, written as for selfplay
+    #[arg(num_args = 2.., required = true, value_name = "ENGINE")]
+    pub engines: Vec<String>,
+    /// Games each pair plays; the opening flip alternates between them
+    #[arg(long, default_value_t = 2)]
+    pub games: usize,
+    /// Seeds the first deal; each further game uses the next number
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// Stops a game that has gone on this long, counting it as a draw
+    #[arg(long, value_name = "PLIES", default_value_t = 1000)]
+    pub max_plies: usize,
+    /// Games played at once [default: one per CPU]
+    #[arg(long)]
+    pub threads: Option<usize>,
+    /// Also writes the games and crosstable to this file
+    #[arg(long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+}
+
+pub fn language_arg(tag: &str) -> Result<Language, String> {
+    Language::parse(tag).ok_or_else(|| String::from("expected en or zh-TW"))
+}
+
+pub fn theme_arg(name: &str) -> Result<Theme, String> {
+    Theme::parse(name).ok_or_else(|| String::from("expected classic, high-contrast or colorblind"))
+}
+
+pub fn difficulty_arg(name: &str) -> Result<Difficulty, String> {
+    parse_difficulty(name).ok_or_else(|| String::from("expected easy, medium, hard or max"))
+}
+
+fn match_length_arg(text: &str) -> Result<u32, String> {
+    match text.parse::<u32>() {
+        Ok(games) if games % 2 == 1 => Ok(games),
+        _ => Err(String::from("expected an odd number of games, such as 3, 5 or 7")),
+    }
+}
+
+fn composition_arg(text: &str) -> Result<Composition, String> {
+    notation::parse_composition(text).map_err(String::from)
+}
+
+/// Reads a position file, keeping the position and dropping the comments.
+fn position_file_arg(path: &str) -> Result<String, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path, e))?;
+    notation::parse_position_file(&text).map(str::to_string).map_err(String::from)
+}
+
+/// Reads a YYYY-MM-DD date into the YYYY.MM.DD form of record Date headers, so dates compare as text.
+fn date_arg(text: &str) -> Result<String, String> {
+    let parts: Vec<&str> = text.split(['-', '.']).collect();
+    match parts[..] {
+        [year, month, day] if year.len() == 4 && month.len() == 2 && day.len() == 2 && parts.iter().all(|part| part.bytes().all(|b| b.is_ascii_digit())) => {
+            Ok(format!("{}.{}.{}", year, month, day))
+        },
+        _ => Err(String::from("expected a date like 2026-10-01")),
+    }
+}
+
+fn engine_arg(name: &str) -> Result<Engine, String> {
+    parse_engine(name).ok_or_else(|| String::from("expected random, search or ismcts"))
+}
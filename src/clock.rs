@@ -0,0 +1,164 @@
+use crate::{opponent, Player};
+use std::time::{Duration, Instant};
+
+/// Starting time per player and the time added after each of their moves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeControl {
+    pub base: Duration,
+    pub increment: Duration,
+}
+
+impl TimeControl {
+    /// Parses minutes plus an increment in seconds, e.g. "5+3"; "10" means no increment.
+    pub fn parse(text: &str) -> Result<Self, &'static str> {
+        let (minutes, increment) = text.trim().split_once('+').unwrap_or((text.trim(), "0"));
+        let minutes: f64 = minutes.parse().map_err(|_| "Time control must be minutes plus increment seconds, e.g. 5+3.")?;
+        let increment: u64 = increment.parse().map_err(|_| "The increment must be a whole number of seconds.")?;
+        if !minutes.is_finite() || minutes <= 0.0 {
+            return Err("The base time must be more than zero minutes.");
+        }
+        let base = Duration::try_from_secs_f64(minutes * 60.0).map_err(|_| "The base time is too long.")?;
+        Ok(TimeControl { base, increment: Duration::from_secs(increment) })
+    }
+
+    /// Reads the PGN TimeControl tag written by `to_pgn`.
+    pub fn from_pgn(text: &str) -> Result<Self, &'static str> {
+        let (base, increment) = text.trim().split_once('+').unwrap_or((text.trim(), "0"));
+        match (base.parse(), increment.parse()) {
+            (Ok(base), Ok(increment)) => Ok(TimeControl { base: Duration::from_secs(base), increment: Duration::from_secs(increment) }),
+            _ => Err("Invalid TimeControl header."),
+        }
+    }
+
+    /// The PGN TimeControl tag: base and increment in seconds, e.g. "300+3".
+    pub fn to_pgn(&self) -> String {
+        format!("{}+{}", self.base.as_secs(), self.increment.as_secs())
+    }
+}
+
+/// A two-sided game clock. At most one side's time runs at once.
+#[derive(Debug, Clone)]
+pub struct Clock {
+    pub control: TimeControl,
+    remaining: [Duration; 2], // Red, Black, as of when the running side's clock was last started
+    running: Option<(Player, Instant)>,
+}
+
+fn slot(player: Player) -> usize {
+    match player {
+        Player::Red => 0,
+        Player::Black => 1,
+    }
+}
+
+impl Clock {
+    pub fn new(control: TimeControl) -> Self {
+        Clock { control, remaining: [control.base; 2], running: None }
+    }
+
+    pub fn remaining(&self, player: Player) -> Duration {
+        let stored = self.remaining[slot(player)];
+        match self.running {
+            Some((side, since)) if side == player => stored.saturating_sub(since.elapsed()),
+            _ => stored,
+        }
+    }
+
+    /// The side whose time is running, if any.
+    pub fn running(&self) -> Option<Player> {
+        self.running.map(|(side, _)| side)
+    }
+
+    /// Starts `player`'s time, stopping the other side's first.
+    pub fn start(&mut self, player: Player) {
+        self.stop();
+        self.running = Some((player, Instant::now()));
+    }
+
+    /// Stops whichever side is running, charging it the time used.
+    pub fn stop(&mut self) {
+        if let Some((side, _)) = self.running {
+            self.remaining[slot(side)] = self.remaining(side);
+            self.running = None;
+        }
+    }
+
+    /// Ends `mover`'s turn: charges their time, adds the increment and starts the opponent's time.
+    pub fn press(&mut self, mover: Player) {
+        if self.running() == Some(mover) {
+            self.stop();
+            self.remaining[slot(mover)] = self.remaining[slot(mover)].saturating_add(self.control.increment);
+        }
+        self.start(opponent(mover));
+    }
+
+    /// The side that has run out of time, if either has.
+    pub fn flagged(&self) -> Option<Player> {
+        [Player::Red, Player::Black].into_iter().find(|&player| self.remaining(player).is_zero())
+    }
+
+    /// Both sides' stopped times, for rewinding the clock on undo.
+    pub fn times(&self) -> [Duration; 2] {
+        [self.remaining(Player::Red), self.remaining(Player::Black)]
+    }
+
+    /// Sets both sides' times and leaves the clock stopped.
+    pub fn set_times(&mut self, times: [Duration; 2]) {
+        self.running = None;
+        self.remaining = times;
+    }
+}
+
+/// Remaining time as m:ss, with tenths under ten seconds.
+pub fn format_clock(time: Duration) -> String {
+    let secs = time.as_secs();
+    if secs < 10 {
+        format!("0:{:02}.{}", secs, time.subsec_millis() / 100)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+/// Reads a time written by `format_clock`, e.g. "4:05" or "0:07.3".
+pub fn parse_clock(text: &str) -> Option<Duration> {
+    let (minutes, seconds) = text.trim().split_once(':')?;
+    let (seconds, tenths) = seconds.split_once('.').unwrap_or((seconds, "0"));
+    let minutes: u64 = minutes.parse().ok()?;
+    let seconds: u64 = seconds.parse().ok().filter(|&seconds| seconds < 60)?;
+    let tenths: u64 = tenths.parse().ok().filter(|&tenths| tenths < 10)?;
+    minutes.checked_mul(60)?.checked_add(seconds).map(|secs| Duration::from_secs(secs) + Duration::from_millis(tenths * 100))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_rejects_base_times_too_long_for_a_duration() {
+        assert_eq!(TimeControl::parse("1e20"), Err("The base time is too long."));
+        assert_eq!(TimeControl::parse("1e20+3"), Err("The base time is too long."));
+        assert!(TimeControl::parse("inf").is_err());
+        assert_eq!(TimeControl::parse("5+3"), Ok(TimeControl { base: Duration::from_secs(300), increment: Duration::from_secs(3) }));
+    }
+
+    #[test]
+    fn saved_clocks_read_back() {
+        let control = TimeControl::parse("2.5+1").unwrap();
+        assert_eq!(TimeControl::from_pgn(&control.to_pgn()), Ok(control));
+        assert!(TimeControl::from_pgn("5 minutes").is_err());
+
+        for time in [Duration::from_secs(754), Duration::from_millis(7300), Duration::ZERO] {
+            assert_eq!(parse_clock(&format_clock(time)), Some(time));
+        }
+        assert_eq!(parse_clock("4:75"), None);
+        assert_eq!(parse_clock("four"), None);
+    }
+
+    #[test]
+    fn huge_increments_saturate_instead_of_overflowing() {
+        let mut clock = Clock::new(TimeControl { base: Duration::from_secs(60), increment: Duration::MAX });
+        clock.start(Player::Red);
+        clock.press(Player::Red);
+        assert_eq!(clock.remaining(Player::Red), Duration::MAX);
+    }
+}
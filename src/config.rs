@@ -0,0 +1,215 @@
+use crate::cli::{difficulty_arg, language_arg, theme_arg, DisplayArgs, SymbolSet};
+use clap::ValueEnum;
+use rust_dark_chess::ai::Difficulty;
+use rust_dark_chess::display::*;
+use rust_dark_chess::locale::Language;
+use rust_dark_chess::{parse_piece_type, STANDARD_COMPOSITION};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::io::IsTerminal;
+use std::path::PathBuf;
+
+/// Defaults read from the user's config file. Every key is optional, and command-line flags
+/// take precedence over anything set here.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub symbols: Option<String>, // "chinese", "english" or "letters"
+    pub ascii: Option<bool>, // Same as --ascii
+    pub narrate: Option<bool>, // Same as --narrate
+    pub language: Option<String>, // "en" or "zh-TW"
+    pub color: Option<bool>,
+    pub theme: Option<String>, // "classic", "high-contrast" or "colorblind"; the colors below override it
+    pub red_color: Option<String>,
+    pub black_color: Option<String>,
+    pub highlight_color: Option<String>,
+    pub cursor_color: Option<String>,
+    pub difficulty: Option<String>, // Strength of the bot in `ai` and `play --vs-ai`
+    pub time_control: Option<String>, // Minutes plus increment in seconds, e.g. "5+3"
+    pub review: Option<bool>, // Same as play --review
+    pub autosave: Option<PathBuf>, // Where the record of the last game is written when it ends
+    pub game_log: Option<PathBuf>, // Directory every finished game's record is added to
+    pub profiles: Option<PathBuf>, // Ratings of named players, next to the config file by default
+    pub database: Option<PathBuf>, // Finished games for `history`, next to the config file by default
+    pub piece_values: BTreeMap<String, f64>, // Material indicator values by piece type, e.g. soldier = 2
+}
+
+/// `$XDG_CONFIG_HOME/dark_chess`, falling back to `~/.config/dark_chess`.
+fn config_dir() -> Option<PathBuf> {
+    let base = match std::env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("dark_chess"))
+}
+
+pub fn default_config_path() -> Option<PathBuf> {
+    Some(config_dir()?.join("config.toml"))
+}
+
+pub fn default_profiles_path() -> Option<PathBuf> {
+    Some(config_dir()?.join("profiles.toml"))
+}
+
+pub fn default_database_path() -> Option<PathBuf> {
+    Some(config_dir()?.join("games.db"))
+}
+
+/// Colors are on unless NO_COLOR is set (see no-color.org) or the output isn't a terminal,
+/// e.g. when it is piped to a file or another program.
+fn color_by_default() -> bool {
+    std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()) && std::io::stdout().is_terminal()
+}
+
+/// Loads the file given with `--config`, or the default file if there is one. Only a missing
+/// default file is quietly treated as empty.
+pub fn load_config(path: Option<&PathBuf>) -> Result<(Config, Option<PathBuf>), String> {
+    let (path, required) = match path {
+        Some(path) => (path.clone(), true),
+        None => match default_config_path() {
+            Some(path) => (path, false),
+            None => return Ok((Config::default(), None)),
+        },
+    };
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && !required => return Ok((Config::default(), None)),
+        Err(e) => return Err(format!("could not read {}: {}", path.display(), e)),
+    };
+    let config = toml::from_str(&text).map_err(|e| format!("invalid config file {}: {}", path.display(), e))?;
+    Ok((config, Some(path)))
+}
+
+/// The display and default settings in effect once flags are applied over the config file.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub file: Option<PathBuf>, // Config file the settings were read from, if any
+    pub symbol_set: SymbolSet,
+    pub language: Language,
+    pub theme: Theme,
+    pub colors: ColorConfig,
+    pub style: CoordinateStyle,
+    pub charset: Charset,
+    pub narrate: bool,
+    pub material_bar: bool,
+    pub eval_bar: bool,
+    pub difficulty: Difficulty,
+    pub time_control: Option<String>,
+    pub review: bool,
+    pub autosave: Option<PathBuf>,
+    pub game_log: Option<PathBuf>,
+    pub profiles: Option<PathBuf>,
+    pub database: Option<PathBuf>,
+    pub piece_values: PieceValues,
+}
+
+impl Settings {
+    pub fn resolve(display: &DisplayArgs, config: &Config, file: Option<PathBuf>) -> Result<Settings, String> {
+        let symbol_set = match (display.symbols, &config.symbols) {
+            (Some(set), _) => set,
+            (None, Some(name)) => SymbolSet::from_str(name, true).map_err(|_| format!("config: symbols must be chinese, english or letters, not {:?}", name))?,
+            (None, None) => SymbolSet::Chinese,
+        };
+        let language = match (display.lang, &config.language) {
+            (Some(language), _) => language,
+            (None, Some(tag)) => language_arg(tag).map_err(|e| format!("config: language {}, not {:?}", e, tag))?,
+            (None, None) => Language::English,
+        };
+        let difficulty = match &config.difficulty {
+            Some(name) => difficulty_arg(name).map_err(|e| format!("config: difficulty {}", e))?,
+            None => Difficulty::Easy,
+        };
+
+        // Types left out of the table keep their default value
+        let mut piece_values = default_piece_values();
+        for (name, &value) in &config.piece_values {
+            let piece_type = parse_piece_type(name).ok_or_else(|| format!("config: piece_values has no piece type {:?}", name))?;
+            if !value.is_finite() || value < 0.0 {
+                return Err(format!("config: piece_values.{} must be a number of at least 0, not {}", name, value));
+            }
+            piece_values.insert(piece_type, value);
+        }
+
+        let theme = match (display.theme, &config.theme) {
+            (Some(theme), _) => theme,
+            (None, Some(name)) => theme_arg(name).map_err(|e| format!("config: theme {}, not {:?}", e, name))?,
+            (None, None) => Theme::Classic,
+        };
+        let defaults = theme.colors();
+        let colors = ColorConfig {
+            red: display.red_color.clone().or_else(|| config.red_color.clone()).unwrap_or(defaults.red),
+            black: display.black_color.clone().or_else(|| config.black_color.clone()).unwrap_or(defaults.black),
+            highlight: config.highlight_color.clone().unwrap_or(defaults.highlight),
+            cursor: config.cursor_color.clone().unwrap_or(defaults.cursor),
+            enabled: !display.no_color && config.color.unwrap_or_else(color_by_default),
+        };
+
+        Ok(Settings {
+            file,
+            symbol_set,
+            language,
+            theme,
+            colors,
+            style: if display.algebraic { CoordinateStyle::Algebraic } else { CoordinateStyle::Numeric },
+            narrate: display.narrate || config.narrate.unwrap_or(false),
+            charset: if display.ascii || config.ascii.unwrap_or(false) { Charset::Ascii } else { Charset::Unicode },
+            material_bar: display.material_bar,
+            eval_bar: display.eval_bar,
+            difficulty,
+            time_control: config.time_control.clone(),
+            review: config.review.unwrap_or(false),
+            autosave: config.autosave.clone(),
+            game_log: config.game_log.clone(),
+            profiles: config.profiles.clone().or_else(default_profiles_path),
+            database: config.database.clone().or_else(default_database_path),
+            piece_values,
+        })
+    }
+
+    pub fn symbols(&self) -> Symbols {
+        match self.symbol_set {
+            // The other sets are ASCII already
+            SymbolSet::Chinese if self.charset == Charset::Ascii => piece_symbols_ascii(),
+            SymbolSet::Chinese => piece_symbols(),
+            SymbolSet::English => piece_symbols_eng(),
+            SymbolSet::Letters => piece_symbols_letters(),
+        }
+    }
+
+    /// One `key = value` line per setting, in the config file's own syntax. Unset keys are commented out.
+    pub fn describe(&self) -> String {
+        let piece_values: Vec<String> = STANDARD_COMPOSITION
+            .iter()
+            .map(|&(piece_type, _)| format!("{} = {}", piece_type_name(piece_type, 1).to_lowercase(), self.piece_values[&piece_type]))
+            .collect();
+        let optional = |key: &str, value: Option<String>| match value {
+            Some(value) => format!("{} = {:?}", key, value),
+            None => format!("# {} is not set", key),
+        };
+        [
+            match &self.file {
+                Some(path) => format!("# Read from {}", path.display()),
+                None => String::from("# No config file, using the built-in defaults"),
+            },
+            format!("symbols = \"{:?}\"", self.symbol_set).to_lowercase(),
+            format!("ascii = {}", self.charset == Charset::Ascii),
+            format!("narrate = {}", self.narrate),
+            format!("language = {:?}", self.language.tag()),
+            format!("color = {}", self.colors.enabled),
+            format!("theme = {:?}", self.theme.name()),
+            format!("red_color = {:?}", self.colors.red),
+            format!("black_color = {:?}", self.colors.black),
+            format!("highlight_color = {:?}", self.colors.highlight),
+            format!("cursor_color = {:?}", self.colors.cursor),
+            format!("difficulty = \"{:?}\"", self.difficulty).to_lowercase(),
+            optional("time_control", self.time_control.clone()),
+            format!("review = {}", self.review),
+            optional("autosave", self.autosave.as_ref().map(|path| path.display().to_string())),
+            optional("game_log", self.game_log.as_ref().map(|path| path.display().to_string())),
+            optional("profiles", self.profiles.as_ref().map(|path| path.display().to_string())),
+            optional("database", self.database.as_ref().map(|path| path.display().to_string())),
+            format!("piece_values = {{ {} }}", piece_values.join(", ")),
+        ]
+        .join("\n")
+    }
+}
@@ -0,0 +1,957 @@
+use crate::belief::{color_odds, flip_odds, unseen_pool};
+use crate::*;
+use std::collections::HashMap;
+
+pub const RESET: &str = "\x1b[0m"; // Resets the color to default
+pub const REVEAL_MARKER: &str = "^"; // Prefix marking a flip in move notation so it can't be mistaken for a move
+pub const MATERIAL_BAR_WIDTH: usize = 32;
+pub const EVAL_BAR_RANGE: f64 = 10.0; // Evaluation that fills one side of the evaluation bar
+pub const MOVE_MARKER: &str = "*"; // Marks an empty square a selected piece can move to
+pub const CAPTURE_MARKER: &str = "x"; // Marks a capture target when colors are off
+
+pub type Symbols = HashMap<(Player, PieceType), &'static str>;
+
+/// What each piece type is worth in the material indicator.
+pub type PieceValues = HashMap<PieceType, f64>;
+
+/// The values the bot plays by.
+pub fn default_piece_values() -> PieceValues {
+    STANDARD_COMPOSITION.iter().map(|&(piece_type, _)| (piece_type, crate::ai::piece_value(piece_type))).collect()
+}
+
+/// How squares are written in output: "(x, y)" pairs or algebraic names like "c2".
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CoordinateStyle {
+    #[default]
+    Numeric,
+    Algebraic,
+}
+
+/// Which characters the board is drawn with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Charset {
+    #[default]
+    Unicode,
+    Ascii, // For terminals without Chinese fonts: `??` for hidden squares and `..` for empty ones
+}
+
+impl Charset {
+    pub fn hidden(self) -> &'static str {
+        match self {
+            Charset::Unicode => " ?",
+            Charset::Ascii => "??",
+        }
+    }
+
+    fn empty(self) -> &'static str {
+        match self {
+            Charset::Unicode => "  ",
+            Charset::Ascii => "..",
+        }
+    }
+}
+
+pub fn format_square(x: usize, y: usize, style: CoordinateStyle) -> String {
+    match style {
+        CoordinateStyle::Numeric => format!("({}, {})", x, y),
+        CoordinateStyle::Algebraic => notation::square_name(x, y),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ColorConfig {
+    pub red: String, // ANSI SGR parameters for Red, e.g. "31" or "1;91"; empty for no color
+    pub black: String, // ANSI SGR parameters for Black
+    pub highlight: String, // Squares a selected piece can move to or capture on
+    pub cursor: String, // The square under the full-screen interface's cursor
+    pub enabled: bool, // False disables all coloring regardless of the codes
+}
+
+impl Default for ColorConfig {
+    fn default() -> Self {
+        Theme::Classic.colors()
+    }
+}
+
+/// Built-in sets of colors. A theme sets every color of `ColorConfig`, and single colors can
+/// still be changed on top of it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Theme {
+    #[default]
+    Classic, // Red in red, Black in the terminal's own color
+    HighContrast, // Bold bright pieces for dark terminals
+    Colorblind, // Orange against blue, which stay apart with red-green color blindness
+}
+
+impl Theme {
+    pub const ALL: [Theme; 3] = [Theme::Classic, Theme::HighContrast, Theme::Colorblind];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Theme::Classic => "classic",
+            Theme::HighContrast => "high-contrast",
+            Theme::Colorblind => "colorblind",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Theme> {
+        Theme::ALL.into_iter().find(|theme| theme.name().eq_ignore_ascii_case(name.trim()))
+    }
+
+    pub fn colors(self) -> ColorConfig {
+        let (red, black, highlight, cursor) = match self {
+            Theme::Classic => ("31", "", "100", "7"),
+            Theme::HighContrast => ("1;91", "1;97", "44", "1;7"),
+            Theme::Colorblind => ("38;5;208", "38;5;39", "100", "7"),
+        };
+        ColorConfig { red: red.to_string(), black: black.to_string(), highlight: highlight.to_string(), cursor: cursor.to_string(), enabled: true }
+    }
+}
+
+pub fn piece_symbols() -> Symbols {
+    use PieceType::*;
+    use Player::*;
+
+    let mut symbols = HashMap::new();
+
+    symbols.insert((Red, General), "帥");
+    symbols.insert((Black, General), "將");
+    symbols.insert((Red, Advisor), "仕");
+    symbols.insert((Black, Advisor), "士");
+    symbols.insert((Red, Elephant), "相");
+    symbols.insert((Black, Elephant), "象");
+    symbols.insert((Red, Chariot), "俥");
+    symbols.insert((Black, Chariot), "車");
+    symbols.insert((Red, Horse), "傌");
+    symbols.insert((Black, Horse), "馬");
+    symbols.insert((Red, Cannon), "炮");
+    symbols.insert((Black, Cannon), "砲");
+    symbols.insert((Red, Soldier), "兵");
+    symbols.insert((Black, Soldier), "卒");
+
+    symbols
+}
+
+pub fn piece_symbols_eng() -> Symbols {
+    use PieceType::*;
+    use Player::*;
+
+    let mut symbols = HashMap::new();
+
+    symbols.insert((Red, General), "RG");
+    symbols.insert((Black, General), "BG");
+    symbols.insert((Red, Advisor), "RA");
+    symbols.insert((Black, Advisor), "BA");
+    symbols.insert((Red, Elephant), "RE");
+    symbols.insert((Black, Elephant), "BE");
+    symbols.insert((Red, Chariot), "RR");
+    symbols.insert((Black, Chariot), "BR");
+    symbols.insert((Red, Horse), "RH");
+    symbols.insert((Black, Horse), "BH");
+    symbols.insert((Red, Cannon), "RC");
+    symbols.insert((Black, Cannon), "BC");
+    symbols.insert((Red, Soldier), "RS");
+    symbols.insert((Black, Soldier), "BS");
+
+    symbols
+}
+
+/// Side letter and piece initial for `--ascii`, e.g. "rG" for the Red General.
+pub fn piece_symbols_ascii() -> Symbols {
+    use PieceType::*;
+    use Player::*;
+
+    let mut symbols = HashMap::new();
+
+    symbols.insert((Red, General), "rG");
+    symbols.insert((Black, General), "bG");
+    symbols.insert((Red, Advisor), "rA");
+    symbols.insert((Black, Advisor), "bA");
+    symbols.insert((Red, Elephant), "rE");
+    symbols.insert((Black, Elephant), "bE");
+    symbols.insert((Red, Chariot), "rR");
+    symbols.insert((Black, Chariot), "bR");
+    symbols.insert((Red, Horse), "rH");
+    symbols.insert((Black, Horse), "bH");
+    symbols.insert((Red, Cannon), "rC");
+    symbols.insert((Black, Cannon), "bC");
+    symbols.insert((Red, Soldier), "rS");
+    symbols.insert((Black, Soldier), "bS");
+
+    symbols
+}
+
+/// The letters of the position notation, upper case for Red and lower case for Black.
+pub fn piece_symbols_letters() -> Symbols {
+    use PieceType::*;
+    use Player::*;
+
+    let mut symbols = HashMap::new();
+
+    symbols.insert((Red, General), "K");
+    symbols.insert((Black, General), "k");
+    symbols.insert((Red, Advisor), "A");
+    symbols.insert((Black, Advisor), "a");
+    symbols.insert((Red, Elephant), "E");
+    symbols.insert((Black, Elephant), "e");
+    symbols.insert((Red, Chariot), "R");
+    symbols.insert((Black, Chariot), "r");
+    symbols.insert((Red, Horse), "H");
+    symbols.insert((Black, Horse), "h");
+    symbols.insert((Red, Cannon), "C");
+    symbols.insert((Black, Cannon), "c");
+    symbols.insert((Red, Soldier), "P");
+    symbols.insert((Black, Soldier), "p");
+
+    symbols
+}
+
+pub fn player_name(player: Player) -> &'static str {
+    match player {
+        Player::Red => "Red",
+        Player::Black => "Black",
+    }
+}
+
+pub fn piece_type_name(piece_type: PieceType, count: usize) -> &'static str {
+    use PieceType::*;
+
+    match (piece_type, count == 1) {
+        (General, true) => "General",
+        (General, false) => "Generals",
+        (Advisor, true) => "Advisor",
+        (Advisor, false) => "Advisors",
+        (Elephant, true) => "Elephant",
+        (Elephant, false) => "Elephants",
+        (Chariot, true) => "Chariot",
+        (Chariot, false) => "Chariots",
+        (Horse, true) => "Horse",
+        (Horse, false) => "Horses",
+        (Cannon, true) => "Cannon",
+        (Cannon, false) => "Cannons",
+        (Soldier, true) => "Soldier",
+        (Soldier, false) => "Soldiers",
+    }
+}
+
+pub fn paint(text: &str, player: Player, colors: &ColorConfig) -> String {
+    // All player coloring goes through here so the codes and --no-color apply everywhere
+    let code = match player {
+        Player::Red => &colors.red,
+        Player::Black => &colors.black,
+    };
+    paint_code(text, code, colors)
+}
+
+/// Wraps `text` in the SGR `code`, unless colors are off or there is no code.
+pub fn paint_code(text: &str, code: &str, colors: &ColorConfig) -> String {
+    if !colors.enabled || code.is_empty() || text.is_empty() {
+        return text.to_string();
+    }
+    format!("\x1b[{}m{}{}", code, text, RESET)
+}
+
+pub fn colored_symbol(piece: Piece, symbols: &Symbols, colors: &ColorConfig) -> String {
+    if !colors.enabled {
+        return plain_symbol(piece, symbols);
+    }
+    let piece_symbol = symbols.get(&(piece.player, piece.piece_type)).unwrap_or(&" ");
+    paint(piece_symbol, piece.player, colors)
+}
+
+/// A piece's symbol for output without colors. The two sides' Chinese characters are easy to
+/// mix up in plain text, so they get a side marker like `r帥` or `b將`; ASCII symbols already
+/// show the side.
+pub fn plain_symbol(piece: Piece, symbols: &Symbols) -> String {
+    let symbol = symbols.get(&(piece.player, piece.piece_type)).unwrap_or(&" ");
+    if symbol.is_ascii() { symbol.to_string() } else { format!("{}{}", side_marker(piece.player), symbol) }
+}
+
+pub fn side_marker(player: Player) -> char {
+    match player {
+        Player::Red => 'r',
+        Player::Black => 'b',
+    }
+}
+
+// Terminal columns taken by plain text, with Chinese characters two columns wide
+fn text_width(text: &str) -> usize {
+    text.chars().map(|c| if c.is_ascii() { 1 } else { 2 }).sum()
+}
+
+/// A piece's symbol filling a two-column board cell. Chinese characters and two-letter symbols
+/// already fill it; a single letter is right-aligned like the `?` of a hidden square.
+pub fn cell_symbol(piece: Piece, symbols: &Symbols) -> String {
+    let symbol = symbols.get(&(piece.player, piece.piece_type)).unwrap_or(&"  ");
+    if symbol.is_ascii() { format!("{:>2}", symbol) } else { symbol.to_string() }
+}
+
+pub fn render_board(board: &Board, symbols: &Symbols, colors: &ColorConfig, style: CoordinateStyle, charset: Charset) -> String {
+    render_board_marked(board, symbols, colors, &[], style, charset)
+}
+
+pub fn render_board_marked(board: &Board, symbols: &Symbols, colors: &ColorConfig, marks: &[(usize, usize)], style: CoordinateStyle, charset: Charset) -> String {
+    let mut out = String::new();
+
+    // Without colors, Chinese pieces carry a side marker and the cells widen to fit it
+    let marked_sides = !colors.enabled && symbols.values().any(|symbol| !symbol.is_ascii());
+    let cell_width = if marked_sides { 3 } else { 2 };
+
+    // Column headers, with a margin for row labels
+    out.push_str("   ");
+    for x in 0..board[0].len() {
+        let header = match style {
+            CoordinateStyle::Numeric => x.to_string(),
+            CoordinateStyle::Algebraic => notation::square_name(x, 0)[..1].to_string(),
+        };
+        out.push_str(&format!("{:>width$} ", header, width = cell_width));
+    }
+    out.push('\n');
+
+    // Top border, adjusted for double-width characters
+    let border = format!("  +{}\n", format!("{}+", "-".repeat(cell_width)).repeat(board[0].len()));
+    out.push_str(&border);
+
+    for (y, row) in board.iter().enumerate() {
+        // Row label with space for alignment
+        let label = match style {
+            CoordinateStyle::Numeric => y,
+            CoordinateStyle::Algebraic => y + 1,
+        };
+        out.push_str(&format!("{:<2}|", label));
+
+        // Each cell with the appropriate symbol followed by a vertical separator
+        for (x, cell) in row.iter().enumerate() {
+            let marked = marks.contains(&(x, y));
+            let symbol = match cell {
+                Cell::Hidden(_) => charset.hidden().to_string(),
+                // Marked pieces are capture targets: highlighted, or a plain marker without color
+                Cell::Revealed(_) if marked && (!colors.enabled || colors.highlight.is_empty()) => format!(" {}", CAPTURE_MARKER),
+                Cell::Revealed(piece) if marked => paint_code(&paint(&cell_symbol(*piece, symbols), piece.player, colors), &colors.highlight, colors),
+                Cell::Revealed(piece) if marked_sides => plain_symbol(*piece, symbols),
+                Cell::Revealed(piece) => paint(&cell_symbol(*piece, symbols), piece.player, colors),
+                Cell::Empty if marked => format!(" {}", MOVE_MARKER),
+                Cell::Empty => charset.empty().to_string(),
+            };
+            if marked_sides {
+                // Only plain text gets here, so its width can be counted
+                out.push_str(&" ".repeat(cell_width - text_width(&symbol)));
+            }
+            out.push_str(&symbol);
+            out.push('|');
+        }
+        out.push('\n');
+
+        // Horizontal separator between rows
+        out.push_str(&border);
+    }
+    out
+}
+
+pub fn render_game_state(board: &Board, symbols: &Symbols) -> String {
+    let mut out = String::from("Game State:");
+    for row in board {
+        let row_state: Vec<String> = row.iter().map(|cell| match cell {
+            Cell::Hidden(_) => String::from("?"),
+            Cell::Revealed(piece) => symbols.get(&(piece.player, piece.piece_type)).unwrap_or(&" ").to_string(),
+            Cell::Empty => String::from("."),
+        }).collect();
+
+        // Join the cell states with a comma for readability
+        out.push('\n');
+        out.push_str(&row_state.join(", "));
+    }
+    out
+}
+
+pub fn render_move_history(moves_history: &[GameMove], symbols: &Symbols, style: CoordinateStyle) -> String {
+    let mut out = String::from("Move History:");
+    for (index, game_move) in moves_history.iter().enumerate() {
+        let player = match game_move.piece {
+            Some(piece) => piece.player,
+            None => continue,
+        };
+
+        let player_symbol = player_name(player);
+
+        let piece_symbol = game_move.piece.map_or("Unknown", |p| symbols.get(&(p.player, p.piece_type)).unwrap_or(&"Unknown"));
+
+        let action_description = match game_move.action_type {
+            ActionType::Flip { x, y } => format!("{}Flip at {}", REVEAL_MARKER, format_square(x, y, style)),
+            ActionType::Move { from_x, from_y, to_x, to_y } => format!("Move from {} to {}", format_square(from_x, from_y, style), format_square(to_x, to_y, style)),
+        };
+
+        let capture_description = match (game_move.captured_piece, game_move.revealed_own) {
+            (Some(captured_piece), _) => format!(", captured {}", symbols.get(&(captured_piece.player, captured_piece.piece_type)).unwrap_or(&"Unknown")),
+            (None, Some(own)) => format!(", turned up own {}", symbols.get(&(own.player, own.piece_type)).unwrap_or(&"Unknown")),
+            (None, None) => String::new(),
+        };
+
+        out.push_str(&format!("\n{}. {} {} made a {}{}", index + 1, player_symbol, piece_symbol, action_description, capture_description));
+    }
+    out
+}
+
+pub fn describe_action(board: &Board, action: ActionType, rules: &RuleConfig, style: CoordinateStyle) -> String {
+    // A move is described by what it would do on this board; a flip's outcome is still unknown,
+    // and so is what a cannon jumping onto a face-down piece will find
+    match action {
+        ActionType::Flip { x, y } => format!("flip at {}", format_square(x, y, style)),
+        ActionType::Move { to_x, to_y, .. } if matches!(board.cell_at(to_x, to_y), Some(Cell::Hidden(_))) => {
+            format!("cannon jump onto the hidden piece at {}", format_square(to_x, to_y, style))
+        },
+        ActionType::Move { from_x, from_y, to_x, to_y } => match check_move(board, from_x, from_y, to_x, to_y, rules) {
+            Ok(game_move) => describe_move(&game_move, style),
+            Err(_) => format!("move from {} to {}", format_square(from_x, from_y, style), format_square(to_x, to_y, style)),
+        },
+    }
+}
+
+pub fn describe_move(game_move: &GameMove, style: CoordinateStyle) -> String {
+    let piece_description = |piece: Option<Piece>| match piece {
+        Some(piece) => format!("{} {}", player_name(piece.player), piece_type_name(piece.piece_type, 1)),
+        None => String::from("Unknown piece"),
+    };
+
+    match (game_move.action_type, game_move.captured_piece) {
+        (ActionType::Flip { x, y }, _) => format!("{} flip at {}", piece_description(game_move.piece), format_square(x, y, style)),
+        (ActionType::Move { to_x, to_y, .. }, None) if game_move.revealed_own.is_some() => format!(
+            "{} jump onto {} turns up own {}",
+            piece_description(game_move.piece),
+            format_square(to_x, to_y, style),
+            piece_description(game_move.revealed_own)
+        ),
+        (ActionType::Move { to_x, to_y, .. }, Some(victim)) => format!(
+            "{} capture of {} at {}",
+            piece_description(game_move.piece),
+            piece_description(Some(victim)),
+            format_square(to_x, to_y, style)
+        ),
+        (ActionType::Move { from_x, from_y, to_x, to_y }, None) => format!(
+            "{} move from {} to {}",
+            piece_description(game_move.piece),
+            format_square(from_x, from_y, style),
+            format_square(to_x, to_y, style)
+        ),
+    }
+}
+
+// "red soldier", the way narration names a piece
+fn spoken_piece(piece: Piece) -> String {
+    format!("{} {}", player_name(piece.player), piece_type_name(piece.piece_type, 1)).to_lowercase()
+}
+
+/// The board in words for screen readers, one line per row from left to right, e.g.
+/// "Row 2: hidden, red soldier, empty, ...".
+pub fn narrate_board(board: &Board, style: CoordinateStyle) -> String {
+    let rows: Vec<String> = board
+        .iter()
+        .enumerate()
+        .map(|(y, row)| {
+            let label = match style {
+                CoordinateStyle::Numeric => format!("Row {}", y),
+                CoordinateStyle::Algebraic => format!("Rank {}", y + 1),
+            };
+            let cells: Vec<String> = row
+                .iter()
+                .map(|cell| match cell {
+                    Cell::Hidden(_) => String::from("hidden"),
+                    Cell::Revealed(piece) => spoken_piece(*piece),
+                    Cell::Empty => String::from("empty"),
+                })
+                .collect();
+            format!("{}: {}.", label, cells.join(", "))
+        })
+        .collect();
+    rows.join("\n")
+}
+
+/// The pieces each side has taken, in words.
+pub fn narrate_captured(captured: &[Vec<Piece>; 2]) -> String {
+    [(Player::Red, &captured[0]), (Player::Black, &captured[1])]
+        .into_iter()
+        .map(|(captor, pieces)| {
+            let taken: Vec<String> = pieces.iter().map(|&piece| spoken_piece(piece)).collect();
+            format!("{} has captured {}.", player_name(captor), if taken.is_empty() { String::from("nothing") } else { taken.join(", ") })
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// An action as a full sentence, e.g. "Red moves the chariot from (0, 3) to (0, 2) and captures
+/// the black soldier." `mover` is who acted, or `None` for the opening flip.
+pub fn narrate_move(game_move: &GameMove, mover: Option<Player>, style: CoordinateStyle) -> String {
+    let piece = game_move.piece.map_or_else(|| String::from("unknown piece"), spoken_piece);
+    match game_move.action_type {
+        ActionType::Flip { x, y } => {
+            let mover = mover.map_or("The first player", player_name);
+            format!("{} flips the piece at {}, revealing a {}.", mover, format_square(x, y, style), piece)
+        },
+        ActionType::Move { from_x, from_y, to_x, to_y } => {
+            let (player, piece_type) = match game_move.piece {
+                Some(moved) => (player_name(moved.player), piece_type_name(moved.piece_type, 1).to_lowercase()),
+                None => ("Someone", piece),
+            };
+            if let Some(own) = game_move.revealed_own {
+                return format!(
+                    "{} jumps the {} at {} onto {} but turns up its own {}; the capture is voided.",
+                    player,
+                    piece_type,
+                    format_square(from_x, from_y, style),
+                    format_square(to_x, to_y, style),
+                    piece_type_name(own.piece_type, 1).to_lowercase()
+                );
+            }
+            let capture = match game_move.captured_piece {
+                Some(victim) if game_move.captured_hidden => format!(" and captures a hidden piece, a {}", spoken_piece(victim)),
+                Some(victim) => format!(" and captures the {}", spoken_piece(victim)),
+                None => String::new(),
+            };
+            format!("{} moves the {} from {} to {}{}.", player, piece_type, format_square(from_x, from_y, style), format_square(to_x, to_y, style), capture)
+        },
+    }
+}
+
+pub fn describe_capture(game_move: &GameMove, symbols: &Symbols, colors: &ColorConfig, style: CoordinateStyle) -> Option<String> {
+    // Only moves that took a piece get a capture line
+    let (attacker, victim) = (game_move.piece?, game_move.captured_piece?);
+    let (from_x, from_y, to_x, to_y) = match game_move.action_type {
+        ActionType::Move { from_x, from_y, to_x, to_y } => (from_x, from_y, to_x, to_y),
+        ActionType::Flip { .. } => return None,
+    };
+
+    // A cannon taking from a distance has jumped a screen, everything else captures directly
+    let adjacent = from_x.abs_diff(to_x) + from_y.abs_diff(to_y) == 1;
+    let verb = if attacker.piece_type == PieceType::Cannon && !adjacent { "jumped and captured" } else { "captured" };
+
+    Some(format!(
+        "{} {} {} {} {} at {}",
+        player_name(attacker.player),
+        colored_symbol(attacker, symbols, colors),
+        verb,
+        player_name(victim.player),
+        colored_symbol(victim, symbols, colors),
+        format_square(to_x, to_y, style)
+    ))
+}
+
+pub fn describe_threats(threats: &[Threat], symbols: &Symbols, colors: &ColorConfig, style: CoordinateStyle) -> String {
+    if threats.is_empty() {
+        return String::from("No pieces under threat.");
+    }
+
+    let mut out = String::from("Threatened pieces:");
+    for threat in threats {
+        out.push_str(&format!(
+            "\n  {} at {} can be captured by {} at {}",
+            colored_symbol(threat.target, symbols, colors),
+            format_square(threat.target_pos.0, threat.target_pos.1, style),
+            colored_symbol(threat.attacker, symbols, colors),
+            format_square(threat.attacker_pos.0, threat.attacker_pos.1, style)
+        ));
+    }
+    out
+}
+
+pub fn describe_destinations(board: &Board, x: usize, y: usize, rules: &RuleConfig, symbols: &Symbols, colors: &ColorConfig, style: CoordinateStyle) -> Result<String, GameError> {
+    let moves = moves_from(board, x, y, rules)?;
+    if moves.is_empty() {
+        return Ok(format!("No legal moves from {}.", format_square(x, y, style)));
+    }
+    let lines: Vec<String> = moves
+        .iter()
+        .filter_map(|game_move| match (game_move.action_type, game_move.captured_piece) {
+            (ActionType::Move { to_x, to_y, .. }, Some(victim)) => Some(format!("  {} captures {}", format_square(to_x, to_y, style), colored_symbol(victim, symbols, colors))),
+            (ActionType::Move { to_x, to_y, .. }, None) => Some(format!("  {}", format_square(to_x, to_y, style))),
+            _ => None,
+        })
+        .collect();
+    Ok(format!("Legal destinations from {}:\n{}", format_square(x, y, style), lines.join("\n")))
+}
+
+pub fn inspect_cell(board: &Board, x: usize, y: usize, rules: &RuleConfig, symbols: &Symbols, colors: &ColorConfig, style: CoordinateStyle) -> Result<String, GameError> {
+    if y >= board.len() || x >= board[0].len() {
+        return Err(GameError::OutOfBounds);
+    }
+
+    let square = format_square(x, y, style);
+    let piece = match board[y][x] {
+        Cell::Empty => return Ok(format!("{}: empty", square)),
+        Cell::Hidden(Some(_)) => return Ok(format!("{}: hidden piece", square)),
+        Cell::Hidden(None) => return Ok(format!("{}: hidden, nothing concealed", square)),
+        Cell::Revealed(piece) => piece,
+    };
+
+    // Split every legal destination into plain moves and captures
+    let mut moves = Vec::new();
+    let mut captures = Vec::new();
+    for (to_y, row) in board.iter().enumerate() {
+        for to_x in 0..row.len() {
+            if let Ok(game_move) = check_move(board, x, y, to_x, to_y, rules) {
+                match game_move.captured_piece {
+                    Some(victim) => captures.push(format!("{} {}", format_square(to_x, to_y, style), colored_symbol(victim, symbols, colors))),
+                    None => moves.push(format_square(to_x, to_y, style)),
+                }
+            }
+        }
+    }
+    let list = |entries: &[String]| if entries.is_empty() { String::from("none") } else { entries.join(", ") };
+
+    Ok(format!(
+        "{}: {} {} {}, rank {}\n  Moves: {}\n  Captures: {}",
+        square,
+        player_name(piece.player),
+        piece_type_name(piece.piece_type, 1),
+        colored_symbol(piece, symbols, colors),
+        piece_rank(piece.piece_type),
+        list(&moves),
+        list(&captures)
+    ))
+}
+
+pub fn material_bar(board: &Board, colors: &ColorConfig) -> String {
+    let red_material = material_count(board, Player::Red);
+    let black_material = material_count(board, Player::Black);
+    let total = (red_material + black_material) as usize;
+    let half = MATERIAL_BAR_WIDTH / 2;
+
+    // The leader's share grows out from the centre: Red's advantage to the left, Black's to the right
+    let lead = ((red_material - black_material).unsigned_abs() as usize * half).checked_div(total).unwrap_or(0);
+    let (red_lead, black_lead) = if red_material >= black_material { (lead, 0) } else { (0, lead) };
+
+    format!(
+        "Red [{}{}|{}{}] Black ({}:{})",
+        " ".repeat(half - red_lead),
+        paint(&"#".repeat(red_lead), Player::Red, colors),
+        paint(&"#".repeat(black_lead), Player::Black, colors),
+        " ".repeat(half - black_lead),
+        red_material,
+        black_material
+    )
+}
+
+/// The search's evaluation for Red drawn like the material bar, with Red's lead growing to
+/// the left and Black's to the right, e.g. "Eval: Red [    ##|        ] Black (Red +1.2)".
+pub fn evaluation_bar(value: f64, colors: &ColorConfig) -> String {
+    let half = MATERIAL_BAR_WIDTH / 2;
+    let lead = ((value.abs() / EVAL_BAR_RANGE).min(1.0) * half as f64).round() as usize;
+    let (red_lead, black_lead) = if value >= 0.0 { (lead, 0) } else { (0, lead) };
+    let leader = if value >= 0.0 { Player::Red } else { Player::Black };
+    let verdict = if value.abs() >= ai::WIN_SCORE {
+        format!("{} wins by force", player_name(leader))
+    } else if value.abs() < 0.05 {
+        String::from("even")
+    } else {
+        format!("{} +{:.1}", player_name(leader), value.abs())
+    };
+
+    format!(
+        "Eval: Red [{}{}|{}{}] Black ({})",
+        " ".repeat(half - red_lead),
+        paint(&"#".repeat(red_lead), Player::Red, colors),
+        paint(&"#".repeat(black_lead), Player::Black, colors),
+        " ".repeat(half - black_lead),
+        verdict
+    )
+}
+
+/// The revealed material of each side and who is ahead, with the pieces still face down, e.g.
+/// "Material: Red 14.5 - Black 9 (Red +5.5), unknown: 12".
+pub fn material_line(board: &Board, values: &PieceValues) -> String {
+    let side_value = |player: Player| -> f64 {
+        board
+            .iter()
+            .flatten()
+            .map(|cell| match cell {
+                Cell::Revealed(piece) if piece.player == player => values.get(&piece.piece_type).copied().unwrap_or(0.0),
+                _ => 0.0,
+            })
+            .sum()
+    };
+    // Rounded so fractional values don't print their binary representation error
+    let (red, black) = (side_value(Player::Red), side_value(Player::Black));
+    let (red, black) = ((red * 100.0).round() / 100.0, (black * 100.0).round() / 100.0);
+    let lead = if red > black {
+        format!("Red +{}", ((red - black) * 100.0).round() / 100.0)
+    } else if black > red {
+        format!("Black +{}", ((black - red) * 100.0).round() / 100.0)
+    } else {
+        String::from("even")
+    };
+    format!("Material: Red {} - Black {} ({}), unknown: {}", red, black, lead, hidden_count(board))
+}
+
+/// Captured pieces grouped by type in rank order, with a count after any taken more than once,
+/// e.g. "車 炮x2 卒x3".
+pub fn capture_tray(captured: &[Piece], symbols: &Symbols) -> String {
+    let mut groups = Vec::new();
+    for &(piece_type, _) in &STANDARD_COMPOSITION {
+        let pieces: Vec<&Piece> = captured.iter().filter(|piece| piece.piece_type == piece_type).collect();
+        let Some(first) = pieces.first() else { continue };
+        let symbol = symbols.get(&(first.player, piece_type)).copied().unwrap_or("?").trim();
+        groups.push(if pieces.len() > 1 { format!("{}x{}", symbol, pieces.len()) } else { symbol.to_string() });
+    }
+    if groups.is_empty() {
+        String::from("-")
+    } else {
+        groups.join(" ")
+    }
+}
+
+/// Both sides' capture trays, one line each, in the captured pieces' colors.
+pub fn capture_tray_lines(captured: &[Vec<Piece>; 2], symbols: &Symbols, colors: &ColorConfig) -> Vec<String> {
+    [(Player::Red, &captured[0]), (Player::Black, &captured[1])]
+        .into_iter()
+        .map(|(captor, pieces)| format!("{:<6} took: {}", player_name(captor), paint(&capture_tray(pieces, symbols), opponent(captor), colors)))
+        .collect()
+}
+
+/// Appends `side` to the board's lines, starting beside the first row of squares.
+pub fn beside_board(board: &str, side: &[String]) -> String {
+    let mut out = String::new();
+    for (index, line) in board.lines().enumerate() {
+        out.push_str(line);
+        if let Some(extra) = index.checked_sub(2).and_then(|row| side.get(row)) {
+            out.push_str("   ");
+            out.push_str(extra);
+        }
+        out.push('\n');
+    }
+    out
+}
+
+pub fn capture_summary(moves_history: &[GameMove], player: Player) -> String {
+    let tally = capture_tally(moves_history, player);
+    if tally.is_empty() {
+        return format!("{} captured: nothing", player_name(player));
+    }
+
+    let parts: Vec<String> = tally
+        .iter()
+        .map(|&(piece_type, count)| format!("{} {}", count, piece_type_name(piece_type, count)))
+        .collect();
+    format!("{} captured: {}", player_name(player), parts.join(", "))
+}
+
+pub fn summary_line(board: &Board, moves_history: &[GameMove], current_player: Player) -> String {
+    let red_material = material_count(board, Player::Red);
+    let black_material = material_count(board, Player::Black);
+
+    format!(
+        "Ply {} | {} to move | Material Red {} - Black {} ({:+}) | Captures Red {}, Black {} | Hidden {}",
+        moves_history.len(),
+        player_name(current_player),
+        red_material,
+        black_material,
+        red_material - black_material,
+        capture_count(moves_history, Player::Red),
+        capture_count(moves_history, Player::Black),
+        hidden_count(board)
+    )
+}
+
+/// The `odds` report, e.g. "Next flip (12 hidden): 42% soldier, 17% cannon, ...", worked out
+/// from the pieces the players haven't seen.
+pub fn describe_odds(game: &GameState) -> String {
+    let pool = &unseen_pool(game);
+    let hidden = hidden_count(&game.board);
+    if hidden == 0 {
+        return String::from("No pieces are left to flip.");
+    }
+    let chances: Vec<String> = flip_odds(pool)
+        .iter()
+        .map(|&(piece_type, chance)| format!("{:.0}% {}", chance * 100.0, piece_type_name(piece_type, 1).to_lowercase()))
+        .collect();
+    format!(
+        "Next flip ({} hidden): {}\n  Red {:.0}%, Black {:.0}%",
+        hidden,
+        chances.join(", "),
+        color_odds(pool, Player::Red) * 100.0,
+        color_odds(pool, Player::Black) * 100.0
+    )
+}
+
+/// The `remaining` report: for each side, how many of each piece type are still in the game
+/// and how many of those are face down, e.g. "Red: 13 left, 9 hidden - general 1 (1 hidden), ...".
+/// On an edited position holding fewer pieces than the set, only how many of each could still be
+/// face down is known, e.g. "Red: 1 face up, up to 15 hidden - general 1 face up (up to 0 hidden), ...".
+pub fn describe_remaining(game: &GameState) -> String {
+    let pool = unseen_pool(game);
+    let exact = pool.iter().map(|(_, count)| count).sum::<usize>() == hidden_count(&game.board);
+    let side_line = |player: Player| {
+        let mut parts = Vec::new();
+        let (mut face_up_total, mut hidden) = (0, 0);
+        for &(piece_type, _) in &STANDARD_COMPOSITION {
+            let piece = Piece { player, piece_type };
+            let face_down = pool.iter().find(|(p, _)| *p == piece).map_or(0, |&(_, count)| count);
+            let face_up = game.board.iter().flatten().filter(|&&cell| cell == Cell::Revealed(piece)).count();
+            let name = piece_type_name(piece_type, 1).to_lowercase();
+            if face_down + face_up > 0 {
+                parts.push(if exact {
+                    format!("{} {} ({} hidden)", name, face_down + face_up, face_down)
+                } else {
+                    format!("{} {} face up (up to {} hidden)", name, face_up, face_down)
+                });
+            }
+            face_up_total += face_up;
+            hidden += face_down;
+        }
+        if parts.is_empty() {
+            format!("{}: nothing left", player_name(player))
+        } else if exact {
+            format!("{}: {} left, {} hidden - {}", player_name(player), face_up_total + hidden, hidden, parts.join(", "))
+        } else {
+            format!("{}: {} face up, up to {} hidden - {}", player_name(player), face_up_total, hidden, parts.join(", "))
+        }
+    };
+    format!("{}\n{}", side_line(Player::Red), side_line(Player::Black))
+}
+
+/// The result along with how an off-board ending came about, e.g. "Red resigns. Black wins!".
+/// `termination` is the reason written in game records.
+pub fn describe_ending(result: GameResult, termination: Option<&str>) -> String {
+    match (result, termination) {
+        (GameResult::Winner(winner), Some("resignation")) => format!("{} resigns. {}", player_name(opponent(winner)), describe_result(result)),
+        (GameResult::Winner(winner), Some("time forfeit")) => format!("{} ran out of time. {}", player_name(opponent(winner)), describe_result(result)),
+        _ => describe_result(result),
+    }
+}
+
+pub fn describe_result(result: GameResult) -> String {
+    match result {
+        GameResult::Winner(player) => format!("{} wins!", player_name(player)),
+        GameResult::Draw(DrawReason::Elimination) => String::from("The game is a draw: both sides were eliminated at once."),
+        GameResult::Draw(DrawReason::NoProgress) => String::from("The game is a draw: no capture or flip within the move limit."),
+        GameResult::Draw(DrawReason::Repetition) => format!("The game is a draw: the same position occurred {} times.", REPETITION_LIMIT),
+        GameResult::Draw(DrawReason::Agreement) => String::from("The game is a draw by agreement."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn piece(player: Player, piece_type: PieceType) -> Piece {
+        Piece { player, piece_type }
+    }
+
+    fn no_color() -> ColorConfig {
+        ColorConfig { enabled: false, ..ColorConfig::default() }
+    }
+
+    #[test]
+    fn capture_line_names_attacker_victim_and_square() {
+        let jump = GameMove {
+            action_type: ActionType::Move { from_x: 0, from_y: 1, to_x: 2, to_y: 1 },
+            piece: Some(piece(Player::Red, PieceType::Cannon)),
+            captured_piece: Some(piece(Player::Black, PieceType::Soldier)),
+            captured_hidden: false,
+            revealed_own: None,
+        };
+        let line = describe_capture(&jump, &piece_symbols(), &no_color(), CoordinateStyle::Numeric).unwrap();
+        assert_eq!(line, "Red r炮 jumped and captured Black b卒 at (2, 1)");
+
+        let english = describe_capture(&jump, &piece_symbols_eng(), &ColorConfig::default(), CoordinateStyle::Algebraic).unwrap();
+        assert!(english.contains(&paint(piece_symbols_eng()[&(Player::Red, PieceType::Cannon)], Player::Red, &ColorConfig::default())));
+        assert!(english.ends_with("at c2"), "{}", english);
+
+        let quiet = GameMove { captured_piece: None, ..jump };
+        assert_eq!(describe_capture(&quiet, &piece_symbols(), &no_color(), CoordinateStyle::Numeric), None);
+    }
+
+    // Red's chariot has taken a soldier and Black has flipped a horse, with a red advisor still face down
+    fn mid_game() -> GameState {
+        let mut board = vec![vec![Cell::Empty; BOARD_WIDTH]; BOARD_HEIGHT];
+        board[0][0] = Cell::Revealed(piece(Player::Red, PieceType::Chariot));
+        board[0][1] = Cell::Revealed(piece(Player::Black, PieceType::Soldier));
+        board[0][7] = Cell::Revealed(piece(Player::Black, PieceType::General));
+        board[3][5] = Cell::Hidden(Some(piece(Player::Black, PieceType::Horse)));
+        board[3][6] = Cell::Hidden(Some(piece(Player::Red, PieceType::Advisor)));
+        let mut game = GameState::from_board(board, Player::Red, RuleConfig::default());
+        game.apply_action(ActionType::Move { from_x: 0, from_y: 0, to_x: 1, to_y: 0 }).unwrap();
+        game.apply_action(ActionType::Flip { x: 5, y: 3 }).unwrap();
+        game
+    }
+
+    #[test]
+    fn summary_line_reports_ply_turn_material_captures_and_hidden() {
+        let game = mid_game();
+        assert_eq!(
+            summary_line(&game.board, &game.history, game.current_player),
+            "Ply 2 | Red to move | Material Red 4 - Black 10 (-6) | Captures Red 1, Black 0 | Hidden 1"
+        );
+    }
+
+    #[test]
+    fn material_bar_grows_toward_the_side_ahead() {
+        // Red 4 against Black 10 puts Black 6/14 of the half bar ahead, rounded down to 6 cells
+        let game = mid_game();
+        let bar = material_bar(&game.board, &no_color());
+        assert_eq!(bar, format!("Red [{}|{}{}] Black (4:10)", " ".repeat(16), "#".repeat(6), " ".repeat(10)));
+
+        let colored = material_bar(&game.board, &Theme::HighContrast.colors());
+        assert!(colored.contains(&format!("\x1b[1;97m{}{}", "#".repeat(6), RESET)));
+    }
+
+    #[test]
+    fn move_history_marks_flips_and_not_moves() {
+        let game = mid_game();
+        let history = render_move_history(&game.history, &piece_symbols(), CoordinateStyle::Numeric);
+        let lines: Vec<&str> = history.lines().filter(|line| line.starts_with(char::is_numeric)).collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("made a Move from (0, 0) to (1, 0)") && !lines[0].contains(REVEAL_MARKER), "{}", lines[0]);
+        assert!(lines[1].contains(&format!("made a {}Flip at (5, 3)", REVEAL_MARKER)), "{}", lines[1]);
+    }
+
+    #[test]
+    fn capture_summary_lists_captures_by_type() {
+        let mut game = mid_game();
+        assert_eq!(capture_summary(&game.history, Player::Red), "Red captured: 1 Soldier");
+        assert_eq!(capture_summary(&game.history, Player::Black), "Black captured: nothing");
+
+        // The red chariot steps down and takes a second soldier
+        game.board[2][1] = Cell::Revealed(piece(Player::Black, PieceType::Soldier));
+        game.apply_action(ActionType::Move { from_x: 1, from_y: 0, to_x: 1, to_y: 1 }).unwrap();
+        game.apply_action(ActionType::Flip { x: 6, y: 3 }).unwrap();
+        game.apply_action(ActionType::Move { from_x: 1, from_y: 1, to_x: 1, to_y: 2 }).unwrap();
+        assert_eq!(capture_summary(&game.history, Player::Red), "Red captured: 2 Soldiers");
+    }
+
+    #[test]
+    fn inspect_lists_a_chariot_s_rank_moves_and_captures() {
+        let mut board = vec![vec![Cell::Empty; BOARD_WIDTH]; BOARD_HEIGHT];
+        board[0][0] = Cell::Revealed(piece(Player::Red, PieceType::Chariot));
+        board[0][2] = Cell::Hidden(Some(piece(Player::Black, PieceType::Horse)));
+        board[2][0] = Cell::Revealed(piece(Player::Black, PieceType::Soldier));
+        let rules = RuleConfig::default();
+
+        let report = inspect_cell(&board, 0, 0, &rules, &piece_symbols(), &no_color(), CoordinateStyle::Numeric).unwrap();
+        assert_eq!(report, "(0, 0): Red Chariot r俥, rank 4\n  Moves: (1, 0), (0, 1)\n  Captures: (0, 2) b卒");
+        assert_eq!(inspect_cell(&board, 2, 0, &rules, &piece_symbols(), &no_color(), CoordinateStyle::Numeric).unwrap(), "(2, 0): hidden piece");
+    }
+
+    #[test]
+    fn custom_color_codes_reach_the_board_unless_colors_are_off() {
+        let mut board = vec![vec![Cell::Empty; BOARD_WIDTH]; BOARD_HEIGHT];
+        board[0][0] = Cell::Revealed(piece(Player::Red, PieceType::Chariot));
+        board[0][1] = Cell::Revealed(piece(Player::Black, PieceType::Horse));
+        let custom = ColorConfig { red: String::from("1;95"), black: String::from("36"), ..ColorConfig::default() };
+
+        let colored = render_board(&board, &piece_symbols(), &custom, CoordinateStyle::Numeric, Charset::Unicode);
+        assert!(colored.contains(&format!("\x1b[1;95m俥{}", RESET)), "{}", colored);
+        assert!(colored.contains(&format!("\x1b[36m馬{}", RESET)), "{}", colored);
+
+        let plain = render_board(&board, &piece_symbols(), &ColorConfig { enabled: false, ..custom }, CoordinateStyle::Numeric, Charset::Unicode);
+        assert!(!plain.contains('\x1b'), "{}", plain);
+        assert!(plain.contains("俥") && plain.contains("馬"));
+    }
+
+    #[test]
+    fn remaining_and_odds_only_bound_the_pieces_of_an_edited_position() {
+        // One square is face down but the rest of the set is off the board, so which piece it is stays open
+        let game = mid_game();
+        let remaining = describe_remaining(&game);
+        assert!(remaining.starts_with("Red: 1 face up, up to 15 hidden - general 0 face up (up to 1 hidden)"), "{}", remaining);
+        assert!(describe_odds(&game).starts_with("Next flip (1 hidden): "));
+    }
+}
@@ -0,0 +1,157 @@
+use crate::ai::ismcts::{ismcts_search_until, IsMctsConfig};
+use crate::ai::{best_action_until, AiSettings, Difficulty};
+use crate::notation::{move_token, parse_move_token, parse_piece_letter};
+use crate::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::io::{self, BufRead};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+// Line-based engine protocol on stdin/stdout, modelled on UCI:
+//   uci                                  -> id name ..., uciok
+//   isready                              -> readyok
+//   newgame                              resets to a fresh deal
+//   position startpos [moves <tokens>]   a fresh deal, then the given actions
+//   position fen <rows> <side> [moves <tokens>]
+//   go [depth <plies> | movetime <ms> | playouts <n> | infinite]
+//                                        -> info ..., bestmove <token>
+//   stop                                 ends a search early with the best move found so far
+//   quit
+// Actions use the game record tokens. Flips in a moves list should name the revealed piece
+// (^3,1=K) so the engine's deal matches the game; a bare ^3,1 keeps whatever was dealt.
+
+pub const ENGINE_NAME: &str = "rust_dark_chess";
+
+fn new_game(rules: RuleConfig, rng: &mut StdRng) -> GameState {
+    GameState::with_rng(rules, rng).expect("The standard set always fills the standard board")
+}
+
+fn apply_token(game: &mut GameState, token: &str) -> Result<(), &'static str> {
+    let action = parse_move_token(token)?;
+    if let ActionType::Flip { x, y } = action {
+        if let Some((_, letter)) = token.split_once('=') {
+            let piece = letter.chars().next().and_then(parse_piece_letter).ok_or("Unknown piece letter in flip.")?;
+            swap_hidden_piece(&mut game.board, x, y, piece).map_err(GameError::message)?;
+        }
+    }
+    game.apply_action(action).map_err(GameError::message)?;
+    Ok(())
+}
+
+fn parse_position(words: &[&str], rules: RuleConfig, rng: &mut StdRng) -> Result<GameState, &'static str> {
+    let (mut game, rest) = match words {
+        ["startpos", rest @ ..] => (new_game(rules, rng), rest),
+        ["fen", rows, side, rest @ ..] => {
+            (GameState::from_fen(&format!("{} {}", rows, side), rules, rng).map_err(GameError::message)?, rest)
+        },
+        _ => return Err("Expected 'startpos' or 'fen <rows> <side>'."),
+    };
+    match rest {
+        [] => {},
+        ["moves", tokens @ ..] => {
+            for token in tokens {
+                apply_token(&mut game, token)?;
+            }
+        },
+        _ => return Err("Expected 'moves' after the position."),
+    }
+    Ok(game)
+}
+
+pub fn action_token(board: &Board, action: ActionType, rules: &RuleConfig) -> String {
+    match action {
+        ActionType::Flip { x, y } => format!("^{},{}", x, y),
+        ActionType::Move { from_x, from_y, to_x, to_y } => match check_move(board, from_x, from_y, to_x, to_y, rules) {
+            Ok(game_move) => move_token(&game_move),
+            Err(_) => format!("{},{}-{},{}", from_x, from_y, to_x, to_y),
+        },
+    }
+}
+
+fn start_search(game: &GameState, words: &[&str], stop: Arc<AtomicBool>, seed: u64) -> Result<JoinHandle<()>, &'static str> {
+    enum Limit {
+        Depth(u32),
+        Mcts(IsMctsConfig),
+    }
+    let hard = AiSettings::for_difficulty(Difficulty::Hard);
+    let number = |value: &str| value.parse::<u64>().map_err(|_| "Expected a number after the search limit.");
+    let limit = match words {
+        [] => Limit::Depth(hard.depth),
+        ["depth", value] => Limit::Depth(number(value)? as u32),
+        ["movetime", value] => Limit::Mcts(IsMctsConfig { playouts: usize::MAX, time_budget: Some(Duration::from_millis(number(value)?)), ..IsMctsConfig::default() }),
+        ["playouts", value] => Limit::Mcts(IsMctsConfig { playouts: number(value)? as usize, ..IsMctsConfig::default() }),
+        ["infinite"] => Limit::Mcts(IsMctsConfig { playouts: usize::MAX, ..IsMctsConfig::default() }),
+        _ => return Err("Expected depth, movetime, playouts or infinite."),
+    };
+
+    let game = game.clone();
+    Ok(std::thread::spawn(move || {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let action = match limit {
+            Limit::Depth(depth) => best_action_until(&game, depth, hard.flip_policy, &mut rng, &stop).map(|(suggestion, reached)| {
+                println!("info depth {} score {:.2} nodes {}", reached, suggestion.value, suggestion.nodes);
+                suggestion.action
+            }),
+            Limit::Mcts(config) => ismcts_search_until(&game, &config, &mut rng, &stop),
+        };
+        match action {
+            Some(action) => println!("bestmove {}", action_token(&game.board, action, &game.rules)),
+            None => println!("bestmove (none)"),
+        }
+    }))
+}
+
+/// Speaks the engine protocol on stdin and stdout until `quit` or end of input.
+pub fn run_engine(rules: RuleConfig, seed: u64) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut game = new_game(rules, &mut rng);
+    let stop = Arc::new(AtomicBool::new(false));
+    let mut search: Option<JoinHandle<()>> = None;
+
+    // Stops any running search and waits for it to report its best move
+    let finish_search = |search: &mut Option<JoinHandle<()>>| {
+        if let Some(handle) = search.take() {
+            stop.store(true, Ordering::Relaxed);
+            let _ = handle.join();
+        }
+        stop.store(false, Ordering::Relaxed);
+    };
+
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else { break };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            [] => {},
+            ["uci"] => {
+                println!("id name {}", ENGINE_NAME);
+                println!("uciok");
+            },
+            ["isready"] => println!("readyok"),
+            ["newgame"] => {
+                finish_search(&mut search);
+                game = new_game(rules, &mut rng);
+            },
+            ["position", rest @ ..] => {
+                finish_search(&mut search);
+                match parse_position(rest, rules, &mut rng) {
+                    Ok(position) => game = position,
+                    Err(e) => println!("info string error: {}", e),
+                }
+            },
+            ["go", rest @ ..] => {
+                finish_search(&mut search);
+                match start_search(&game, rest, Arc::clone(&stop), rand::Rng::gen(&mut rng)) {
+                    Ok(handle) => search = Some(handle),
+                    Err(e) => println!("info string error: {}", e),
+                }
+            },
+            ["stop"] => finish_search(&mut search),
+            ["quit"] => break,
+            _ => println!("info string unknown command: {}", line.trim()),
+        }
+    }
+    finish_search(&mut search);
+}
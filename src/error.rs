@@ -0,0 +1,76 @@
+use std::fmt;
+
+/// Why an action, edit or position was refused. Front-ends can match on the cause, e.g. to
+/// word it in another language; `message` is the English text the terminal game shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameError {
+    OutOfBounds,
+    GameOver,
+    FirstActionMustFlip, // Before the colors are decided only flips are allowed
+    NothingToUndo,
+    NothingToRedo,
+    ColorsUndecided, // Nobody owns a color yet, e.g. to resign with
+    SquareNotHidden, // A flip of a square that is empty or already face up
+    EmptyHiddenSquare, // A face-down square whose contents were never dealt
+    NoPieceToMove,
+    NoRevealedPiece, // The square doesn't hold a face-up piece to inspect
+    NotYourPiece,
+    CaptureOwnPiece,
+    OntoHiddenPiece,
+    NotInLine, // Moves go along a row or column
+    TooFar, // The piece only steps one square
+    PathBlocked,
+    NoScreenForCannon, // A cannon capture must jump exactly one piece
+    CannotCaptureHigherRank,
+    CaptureNotAllowed, // Ranked low enough, but the rules forbid it, like a General taking a Soldier
+    PieceNotHidden, // No face-down square holds the piece asked for
+    TooManyPieces,
+    BoardNotFilled,
+    BoardNotRectangle,
+    PieceLimit, // More of a piece than the piece set allows
+    DuplicateGeneral,
+    InvalidPosition(&'static str), // Position text that can't be read, with what is wrong with it
+}
+
+impl GameError {
+    pub fn message(self) -> &'static str {
+        use GameError::*;
+
+        match self {
+            OutOfBounds => "Coordinates out of bounds.",
+            GameOver => "The game is already over.",
+            FirstActionMustFlip => "The first action must be a flip, which decides the colors.",
+            NothingToUndo => "No moves to undo.",
+            NothingToRedo => "No moves to redo.",
+            ColorsUndecided => "Nobody has a color to resign yet.",
+            SquareNotHidden => "There is no hidden piece to flip there.",
+            EmptyHiddenSquare => "A hidden square has no piece under it.",
+            NoPieceToMove => "No piece to move.",
+            NoRevealedPiece => "There is no revealed piece on that square.",
+            NotYourPiece => "You can only move your own pieces.",
+            CaptureOwnPiece => "Cannot capture your own piece.",
+            OntoHiddenPiece => "Cannot move onto a hidden piece.",
+            NotInLine => "Pieces move along a row or column.",
+            TooFar => "That piece moves only one square at a time.",
+            PathBlocked => "The path is blocked.",
+            NoScreenForCannon => "A cannon captures by jumping exactly one piece.",
+            CannotCaptureHigherRank => "Cannot capture a piece that outranks yours.",
+            CaptureNotAllowed => "Cannot capture this piece.",
+            PieceNotHidden => "That piece is not among the hidden pieces.",
+            TooManyPieces => "Too many pieces for the board.",
+            BoardNotFilled => "The piece set does not fill the board.",
+            BoardNotRectangle => "The board must be a non-empty rectangle.",
+            PieceLimit => "That player already has the maximum number of this piece.",
+            DuplicateGeneral => "A player has more than one general.",
+            InvalidPosition(reason) => reason,
+        }
+    }
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for GameError {}
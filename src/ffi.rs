@@ -0,0 +1,198 @@
+use crate::arena::parse_engine_spec;
+use crate::display::describe_result;
+use crate::web::{wire_square, WireAction};
+use crate::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::ptr;
+
+// C interface, declared in include/dark_chess.h. Games are opaque pointers from dc_new_game or
+// dc_game_from_fen, released with dc_free. Actions, legal move lists and boards travel as JSON
+// in the same shapes as the WebSocket messages, e.g. {"kind": "flip", "x": 0, "y": 1}. Strings
+// returned to the caller are theirs to release with dc_free_string. Calls that can fail return
+// -1 or NULL and leave a message for dc_last_error.
+
+/// A game held for a C caller, with the message from its last failed call.
+pub struct DcGame {
+    state: GameState,
+    rng: StdRng,
+    error: CString,
+}
+
+impl DcGame {
+    fn fail(&mut self, message: &str) {
+        // Messages never hold a NUL, but a bad one shouldn't take the caller down
+        self.error = CString::new(message).unwrap_or_default();
+    }
+}
+
+fn into_c_string(text: String) -> *mut c_char {
+    CString::new(text).map_or(ptr::null_mut(), CString::into_raw)
+}
+
+/// Reads a NUL-terminated UTF-8 argument, or `None` for NULL or invalid UTF-8.
+///
+/// # Safety
+/// `text` must be NULL or point to a NUL-terminated string.
+unsafe fn read_c_str<'a>(text: *const c_char) -> Option<&'a str> {
+    if text.is_null() {
+        return None;
+    }
+    CStr::from_ptr(text).to_str().ok()
+}
+
+/// Deals a new game under the standard rules; the same `seed` deals the same layout.
+#[no_mangle]
+pub extern "C" fn dc_new_game(seed: u64) -> *mut DcGame {
+    let mut rng = StdRng::seed_from_u64(seed);
+    match GameState::with_rng(RuleConfig::default(), &mut rng) {
+        Ok(state) => Box::into_raw(Box::new(DcGame { state, rng, error: CString::default() })),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Starts from a position in FEN-like notation, dealing hidden squares written as '?' from
+/// `seed`. Returns NULL if the position can't be read.
+///
+/// # Safety
+/// `fen` must be NULL or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn dc_game_from_fen(fen: *const c_char, seed: u64) -> *mut DcGame {
+    let mut rng = StdRng::seed_from_u64(seed);
+    match read_c_str(fen).map(|fen| GameState::from_fen(fen, RuleConfig::default(), &mut rng)) {
+        Some(Ok(state)) => Box::into_raw(Box::new(DcGame { state, rng, error: CString::default() })),
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Plays an action given as JSON for the side to move. Returns 0 on success and -1 if the
+/// action is malformed or illegal.
+///
+/// # Safety
+/// `game` must come from this library and not yet be freed; `action` must be NULL or point to
+/// a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn dc_apply_move(game: *mut DcGame, action: *const c_char) -> c_int {
+    let Some(game) = game.as_mut() else { return -1 };
+    let parsed = read_c_str(action)
+        .ok_or_else(|| String::from("The action is not a UTF-8 string."))
+        .and_then(|text| serde_json::from_str::<WireAction>(text).map_err(|e| format!("Invalid action: {}", e)));
+    match parsed.and_then(|action| game.state.apply_action(action.into()).map_err(|e| e.to_string())) {
+        Ok(_) => 0,
+        Err(e) => {
+            game.fail(&e);
+            -1
+        },
+    }
+}
+
+/// Checks an action for the side to move without playing it: 0 if it is legal, -1 if it is
+/// malformed or illegal, with the reason for dc_last_error.
+///
+/// # Safety
+/// `game` must come from this library and not yet be freed; `action` must be NULL or point to
+/// a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn dc_check_move(game: *mut DcGame, action: *const c_char) -> c_int {
+    let Some(game) = game.as_mut() else { return -1 };
+    let parsed = read_c_str(action)
+        .ok_or_else(|| String::from("The action is not a UTF-8 string."))
+        .and_then(|text| serde_json::from_str::<WireAction>(text).map_err(|e| format!("Invalid action: {}", e)));
+    match parsed.and_then(|action| game.state.validate_action(action.into()).map_err(|e| e.to_string())) {
+        Ok(()) => 0,
+        Err(e) => {
+            game.fail(&e);
+            -1
+        },
+    }
+}
+
+/// Every action the side to move may take, as a JSON array.
+///
+/// # Safety
+/// `game` must come from this library and not yet be freed.
+#[no_mangle]
+pub unsafe extern "C" fn dc_legal_moves(game: *const DcGame) -> *mut c_char {
+    let Some(game) = game.as_ref() else { return ptr::null_mut() };
+    let actions: Vec<WireAction> = game.state.legal_actions().into_iter().map(WireAction::from).collect();
+    into_c_string(serde_json::to_string(&actions).expect("Actions always serialize"))
+}
+
+/// Searches for an action for the side to move with an engine such as "hard", "search:depth=3"
+/// or "ismcts:playouts=500,time=200", and returns it as JSON without playing it. Returns the
+/// string "null" when there is no legal action, and NULL if the engine can't be read.
+///
+/// # Safety
+/// `game` must come from this library and not yet be freed; `engine` must be NULL or point to
+/// a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn dc_best_move(game: *mut DcGame, engine: *const c_char) -> *mut c_char {
+    let Some(game) = game.as_mut() else { return ptr::null_mut() };
+    let settings = match read_c_str(engine).ok_or_else(|| String::from("The engine is not a UTF-8 string.")).and_then(parse_engine_spec) {
+        Ok(settings) => settings,
+        Err(e) => {
+            game.fail(&e);
+            return ptr::null_mut();
+        },
+    };
+    let mut agent = settings.build_agent(StdRng::from_rng(&mut game.rng).expect("Seeded RNG cannot fail"));
+    let action = agent.choose_action(&game.state).map(WireAction::from);
+    into_c_string(serde_json::to_string(&action).expect("Actions always serialize"))
+}
+
+/// The board as JSON rows from y = 0, each square {"revealed": true, "color": "red",
+/// "piece": "cannon"}, {"revealed": false} or null when empty.
+///
+/// # Safety
+/// `game` must come from this library and not yet be freed.
+#[no_mangle]
+pub unsafe extern "C" fn dc_board_json(game: *const DcGame) -> *mut c_char {
+    let Some(game) = game.as_ref() else { return ptr::null_mut() };
+    let rows: Vec<Vec<_>> = game.state.board.iter().map(|row| row.iter().map(wire_square).collect()).collect();
+    into_c_string(serde_json::to_string(&rows).expect("Squares always serialize"))
+}
+
+/// How the game ended, e.g. "Red wins!", or NULL while it goes on.
+///
+/// # Safety
+/// `game` must come from this library and not yet be freed.
+#[no_mangle]
+pub unsafe extern "C" fn dc_result(game: *const DcGame) -> *mut c_char {
+    match game.as_ref().and_then(|game| game.state.result()) {
+        Some(result) => into_c_string(describe_result(result)),
+        None => ptr::null_mut(),
+    }
+}
+
+/// The message from the game's last failed call, owned by the game and valid until its next
+/// call. Empty if nothing has failed.
+///
+/// # Safety
+/// `game` must come from this library and not yet be freed.
+#[no_mangle]
+pub unsafe extern "C" fn dc_last_error(game: *const DcGame) -> *const c_char {
+    game.as_ref().map_or(ptr::null(), |game| game.error.as_ptr())
+}
+
+/// Releases a game. NULL is ignored.
+///
+/// # Safety
+/// `game` must be NULL or come from this library, and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn dc_free(game: *mut DcGame) {
+    if !game.is_null() {
+        drop(Box::from_raw(game));
+    }
+}
+
+/// Releases a string returned by this library. NULL is ignored.
+///
+/// # Safety
+/// `text` must be NULL or a string returned by this library, and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn dc_free_string(text: *mut c_char) {
+    if !text.is_null() {
+        drop(CString::from_raw(text));
+    }
+}
@@ -0,0 +1,101 @@
+use crate::{Board, Cell, BOARD_HEIGHT, BOARD_WIDTH};
+
+/// Read and write access to the squares of a board, so the rules can run on the nested
+/// `Board` used for play and display as well as the compact `Board32` used by the search.
+pub trait Grid: Clone {
+    fn width(&self) -> usize;
+    fn height(&self) -> usize;
+
+    /// The cell at (x, y). Panics if the square is off the board.
+    fn cell(&self, x: usize, y: usize) -> Cell;
+
+    fn set_cell(&mut self, x: usize, y: usize, cell: Cell);
+
+    /// The cell at (x, y), or `None` off the board.
+    fn cell_at(&self, x: usize, y: usize) -> Option<Cell> {
+        (x < self.width() && y < self.height()).then(|| self.cell(x, y))
+    }
+
+    /// Every square as (x, y, cell), row by row from the top.
+    fn squares(&self) -> impl Iterator<Item = (usize, usize, Cell)> + '_ {
+        (0..self.height()).flat_map(move |y| (0..self.width()).map(move |x| (x, y, self.cell(x, y))))
+    }
+}
+
+impl Grid for Board {
+    fn width(&self) -> usize {
+        self.first().map_or(0, |row| row.len())
+    }
+
+    fn height(&self) -> usize {
+        self.len()
+    }
+
+    fn cell(&self, x: usize, y: usize) -> Cell {
+        self[y][x]
+    }
+
+    fn set_cell(&mut self, x: usize, y: usize, cell: Cell) {
+        self[y][x] = cell;
+    }
+}
+
+/// The standard 8x4 board in one fixed-size array, so copies never allocate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Board32 {
+    pub cells: [Cell; BOARD_WIDTH * BOARD_HEIGHT],
+}
+
+impl Board32 {
+    pub fn index(x: usize, y: usize) -> usize {
+        y * BOARD_WIDTH + x
+    }
+
+    pub fn coordinates(index: usize) -> (usize, usize) {
+        (index % BOARD_WIDTH, index / BOARD_WIDTH)
+    }
+
+    /// Packs a nested board, or returns `None` unless it is exactly the standard size.
+    pub fn from_board(board: &Board) -> Option<Self> {
+        if board.height() != BOARD_HEIGHT || board.iter().any(|row| row.len() != BOARD_WIDTH) {
+            return None;
+        }
+        let mut cells = [Cell::Empty; BOARD_WIDTH * BOARD_HEIGHT];
+        for (x, y, cell) in board.squares() {
+            cells[Board32::index(x, y)] = cell;
+        }
+        Some(Board32 { cells })
+    }
+
+    /// Unpacks into the nested board used for display and play.
+    pub fn to_board(&self) -> Board {
+        self.cells.chunks(BOARD_WIDTH).map(|row| row.to_vec()).collect()
+    }
+}
+
+impl Grid for Board32 {
+    fn width(&self) -> usize {
+        BOARD_WIDTH
+    }
+
+    fn height(&self) -> usize {
+        BOARD_HEIGHT
+    }
+
+    fn cell(&self, x: usize, y: usize) -> Cell {
+        debug_assert!(x < BOARD_WIDTH, "Column out of bounds.");
+        self.cells[Board32::index(x, y)]
+    }
+
+    fn set_cell(&mut self, x: usize, y: usize, cell: Cell) {
+        debug_assert!(x < BOARD_WIDTH, "Column out of bounds.");
+        self.cells[Board32::index(x, y)] = cell;
+    }
+
+    fn squares(&self) -> impl Iterator<Item = (usize, usize, Cell)> + '_ {
+        self.cells.iter().enumerate().map(|(index, &cell)| {
+            let (x, y) = Board32::coordinates(index);
+            (x, y, cell)
+        })
+    }
+}
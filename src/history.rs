@@ -0,0 +1,163 @@
+use crate::notation::parse_record;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use std::path::Path;
+
+// Finished games kept in a SQLite database, one row per game with the headers worth searching
+// by and the full record, so a game can be shown or replayed later.
+
+/// One finished game as the database keeps it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredGame {
+    pub id: i64,
+    pub date: String, // As in the record's Date header, e.g. 2026.10.15
+    pub red: String,
+    pub black: String,
+    pub result: String, // Result token: 1-0, 0-1 or 1/2-1/2
+    pub termination: Option<String>,
+    pub seed: Option<String>,
+    pub plies: u32,
+    pub record: String,
+}
+
+/// Which games to list. Every field left unset matches all games.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GameFilter {
+    pub player: Option<String>, // Plays either color
+    pub opponent: Option<String>, // Also plays either color, so with `player` it picks their games together
+    pub result: Option<String>, // Result token
+    pub since: Option<String>, // Earliest date, inclusive, written like the Date header
+    pub until: Option<String>, // Latest date, inclusive
+}
+
+/// Wins, draws and losses over a set of games, from one player's side or, without a player,
+/// counting wins for Red and losses for Black.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Score {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl Score {
+    pub fn tally(games: &[StoredGame], player: Option<&str>) -> Self {
+        let mut score = Score::default();
+        for game in games {
+            let red_won = match game.result.as_str() {
+                "1-0" => true,
+                "0-1" => false,
+                _ => {
+                    score.draws += 1;
+                    continue;
+                },
+            };
+            // Someone playing themselves wins whichever way it goes
+            let won = match player {
+                Some(name) if game.red == name && game.black == name => true,
+                Some(name) if game.black == name => !red_won,
+                _ => red_won,
+            };
+            if won {
+                score.wins += 1;
+            } else {
+                score.losses += 1;
+            }
+        }
+        score
+    }
+}
+
+pub struct GameDatabase {
+    connection: Connection,
+}
+
+impl GameDatabase {
+    /// Opens the database at `path`, creating it and its directory if needed.
+    pub fn open(path: &Path) -> Result<Self, String> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(|e| format!("could not create {}: {}", dir.display(), e))?;
+        }
+        let connection = Connection::open(path).map_err(|e| format!("could not open {}: {}", path.display(), e))?;
+        connection
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS games (
+                    id INTEGER PRIMARY KEY,
+                    date TEXT NOT NULL,
+                    red TEXT NOT NULL,
+                    black TEXT NOT NULL,
+                    result TEXT NOT NULL,
+                    termination TEXT,
+                    seed TEXT,
+                    plies INTEGER NOT NULL,
+                    record TEXT NOT NULL
+                );
+                CREATE INDEX IF NOT EXISTS games_by_date ON games (date);",
+            )
+            .map_err(|e| format!("invalid game database {}: {}", path.display(), e))?;
+        Ok(GameDatabase { connection })
+    }
+
+    /// Stores a game from its record in the notation of `notation::export_record`, and returns
+    /// the id it was given.
+    pub fn add_game(&self, text: &str) -> Result<i64, String> {
+        let record = parse_record(text)?;
+        let header = |name: &str| record.header(name).map(str::to_string);
+        self.connection
+            .execute(
+                "INSERT INTO games (date, red, black, result, termination, seed, plies, record) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    header("Date").unwrap_or_default(),
+                    header("Red").unwrap_or_default(),
+                    header("Black").unwrap_or_default(),
+                    record.result,
+                    header("Termination"),
+                    header("Seed"),
+                    record.actions.len() as u32,
+                    text,
+                ],
+            )
+            .map_err(|e| format!("could not store the game: {}", e))?;
+        Ok(self.connection.last_insert_rowid())
+    }
+
+    /// The games matching `filter`, oldest first.
+    pub fn games(&self, filter: &GameFilter) -> Result<Vec<StoredGame>, String> {
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT id, date, red, black, result, termination, seed, plies, record FROM games
+                WHERE (?1 IS NULL OR red = ?1 OR black = ?1)
+                AND (?2 IS NULL OR red = ?2 OR black = ?2)
+                AND (?3 IS NULL OR result = ?3)
+                AND (?4 IS NULL OR date >= ?4)
+                AND (?5 IS NULL OR date <= ?5)
+                ORDER BY id",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = statement
+            .query_map(params![filter.player, filter.opponent, filter.result, filter.since, filter.until], stored_game)
+            .map_err(|e| format!("could not read the games: {}", e))?;
+        rows.collect::<Result<_, _>>().map_err(|e| format!("could not read the games: {}", e))
+    }
+
+    /// The game stored under `id`, if there is one.
+    pub fn game(&self, id: i64) -> Result<Option<StoredGame>, String> {
+        self.connection
+            .query_row("SELECT id, date, red, black, result, termination, seed, plies, record FROM games WHERE id = ?1", [id], stored_game)
+            .optional()
+            .map_err(|e| format!("could not read game {}: {}", id, e))
+    }
+}
+
+fn stored_game(row: &Row) -> rusqlite::Result<StoredGame> {
+    Ok(StoredGame {
+        id: row.get(0)?,
+        date: row.get(1)?,
+        red: row.get(2)?,
+        black: row.get(3)?,
+        result: row.get(4)?,
+        termination: row.get(5)?,
+        seed: row.get(6)?,
+        plies: row.get(7)?,
+        record: row.get(8)?,
+    })
+}
@@ -0,0 +1,1433 @@
+use rand::seq::SliceRandom;
+use rand::{thread_rng, Rng};
+use std::collections::HashMap;
+use std::fmt;
+
+pub mod ai;
+pub mod arena;
+pub mod belief;
+pub mod clock;
+pub mod display;
+pub mod error;
+#[cfg(feature = "cli")]
+pub mod engine;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod grid;
+#[cfg(feature = "cli")]
+pub mod history;
+pub mod lobby;
+pub mod locale;
+pub mod net;
+pub mod notation;
+pub mod profiles;
+pub mod review;
+pub mod variant;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod web;
+
+pub use error::GameError;
+pub use grid::{Board32, Grid};
+pub use variant::{RuleSet, Variant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PieceType {
+    General,
+    Advisor,
+    Elephant,
+    Chariot,
+    Horse,
+    Cannon,
+    Soldier,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Player {
+    Red,
+    Black,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Piece {
+    pub piece_type: PieceType,
+    pub player: Player,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Cell {
+    Hidden(Option<Piece>),
+    Revealed(Piece),
+    Empty,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayerColor {
+    Unassigned, // Nobody owns a color until the first flip
+    Assigned(Player), // Color taken by the player who moved first
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActionType {
+    Flip { x: usize, y: usize },
+    Move { from_x: usize, from_y: usize, to_x: usize, to_y: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GameMove {
+    pub action_type: ActionType,
+    pub piece: Option<Piece>, // Piece that was moved or flipped
+    pub captured_piece: Option<Piece>, // Piece that was captured, if any
+    pub captured_hidden: bool, // Whether the captured piece was still face down when taken
+    pub revealed_own: Option<Piece>, // Mover's own piece turned up by a cannon jumping onto it face down
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameResult {
+    Winner(Player),
+    Draw(DrawReason),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrawReason {
+    Elimination, // Both sides lost their last pieces on the same action
+    NoProgress, // No capture or flip within the configured number of plies
+    Repetition, // The same position came up for the third time
+    Agreement, // One player offered a draw and the other accepted
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EliminationOutcome {
+    #[default]
+    Draw, // Both sides wiped out at once is scored as a draw
+    LastMoverWins, // The player whose action emptied the board takes the game
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BoardFill {
+    #[default]
+    Exact, // The piece set must cover every square
+    PadWithEmpty, // Squares left over after placing the pieces start empty
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RuleConfig {
+    pub variant: Variant, // Regional rules deciding how pieces move and capture
+    pub cannon_can_capture_hidden: bool, // Cannons may jump onto a hidden piece, revealing and capturing it in one action
+    pub cannon_single_step: bool, // Cannons move only one square when not capturing
+    pub simultaneous_elimination: EliminationOutcome, // Result when both players run out of pieces on the same action
+    pub board_fill: BoardFill, // What to do when the piece set doesn't exactly fill the board
+    pub no_progress_limit: Option<usize>, // Plies without a capture or flip before the game is drawn, None to disable
+    pub composition: Composition, // Pieces each player starts with
+}
+
+impl Default for RuleConfig {
+    fn default() -> Self {
+        RuleConfig {
+            variant: Variant::default(),
+            cannon_can_capture_hidden: false,
+            cannon_single_step: false,
+            simultaneous_elimination: EliminationOutcome::default(),
+            board_fill: BoardFill::default(),
+            no_progress_limit: None,
+            composition: STANDARD_COMPOSITION,
+        }
+    }
+}
+
+pub type Board = Vec<Vec<Cell>>;
+
+pub const BOARD_WIDTH: usize = 8;
+pub const BOARD_HEIGHT: usize = 4;
+
+// Occurrences of one position that end the game as a draw
+pub const REPETITION_LIMIT: usize = 3;
+
+// How many of each piece type a player starts with, in rank order
+pub type Composition = [(PieceType, usize); 7];
+
+// Pieces each player starts with in a standard game
+pub const STANDARD_COMPOSITION: Composition = [
+    (PieceType::General, 1),
+    (PieceType::Advisor, 2),
+    (PieceType::Elephant, 2),
+    (PieceType::Chariot, 2),
+    (PieceType::Horse, 2),
+    (PieceType::Cannon, 2),
+    (PieceType::Soldier, 5),
+];
+
+fn captor_index(player: Player) -> usize {
+    match player {
+        Player::Red => 0,
+        Player::Black => 1,
+    }
+}
+
+/// A game in progress: the board, whose turn it is, and every action taken so far.
+#[derive(Debug, Clone)]
+pub struct GameState {
+    pub board: Board,
+    pub current_player: Player,
+    pub history: Vec<GameMove>,
+    pub rules: RuleConfig,
+    pub first_player: PlayerColor,
+    pub position_hashes: Vec<u64>, // Hash of every position reached, starting with the initial one
+    pub redo_stack: Vec<ActionType>, // Undone actions, most recent last, cleared by any new action
+    pub adjudicated: Option<GameResult>, // Result decided off the board, such as a loss on time
+    pub captured: [Vec<Piece>; 2], // Pieces taken by Red and by Black, in the order they fell
+    colors_from_flip: bool, // Whether first_player was settled by a flip in this history
+}
+
+impl GameState {
+    /// Starts a new game on a shuffled standard board. Colors are unassigned until the first flip.
+    pub fn new(rules: RuleConfig) -> Result<Self, GameError> {
+        GameState::with_rng(rules, &mut thread_rng())
+    }
+
+    /// Like `new`, but shuffles with the given RNG so a seeded RNG reproduces the same layout.
+    pub fn with_rng<R: Rng + ?Sized>(rules: RuleConfig, rng: &mut R) -> Result<Self, GameError> {
+        let mut game = GameState::from_board(init_board(&rules, rng)?, Player::Red, rules);
+        game.first_player = PlayerColor::Unassigned;
+        Ok(game)
+    }
+
+    /// Starts a game from an existing position with an empty history and `current_player` to move.
+    pub fn from_board(board: Board, current_player: Player, rules: RuleConfig) -> Self {
+        let position_hashes = vec![position_hash(&board, current_player)];
+        GameState {
+            board,
+            current_player,
+            history: Vec::new(),
+            rules,
+            first_player: PlayerColor::Assigned(current_player),
+            position_hashes,
+            redo_stack: Vec::new(),
+            adjudicated: None,
+            captured: [Vec::new(), Vec::new()],
+            colors_from_flip: false,
+        }
+    }
+
+    /// Loads a position in the notation of `notation::parse_fen`, dealing unrecorded hidden squares with `rng`.
+    pub fn from_fen<R: Rng + ?Sized>(fen: &str, rules: RuleConfig, rng: &mut R) -> Result<Self, GameError> {
+        let (mut board, to_move) = notation::parse_fen(fen).map_err(GameError::InvalidPosition)?;
+        notation::deal_unknown_hidden(&mut board, &rules.composition, rng).map_err(GameError::InvalidPosition)?;
+        validate_board(&board)?;
+        check_piece_limits(&board, &rules.composition).map_err(|e| GameError::InvalidPosition(e.message()))?;
+
+        let mut game = GameState::from_board(board, to_move.unwrap_or(Player::Red), rules);
+        if to_move.is_none() {
+            game.first_player = PlayerColor::Unassigned;
+        }
+        Ok(game)
+    }
+
+    /// The position in the notation of `notation::export_fen`, with hidden contents only if `include_hidden`.
+    pub fn to_fen(&self, include_hidden: bool) -> String {
+        let to_move = if self.colors_unassigned() { None } else { Some(self.current_player) };
+        notation::export_fen(&self.board, to_move, include_hidden)
+    }
+
+    /// Whether the opening flip that decides the colors is still to be made.
+    pub fn colors_unassigned(&self) -> bool {
+        self.first_player == PlayerColor::Unassigned
+    }
+
+    /// Whether the player who made the opening action is the one to move.
+    pub fn first_player_to_move(&self) -> bool {
+        match self.first_player {
+            PlayerColor::Unassigned => true,
+            PlayerColor::Assigned(color) => color == self.current_player,
+        }
+    }
+
+    /// Plays a flip or move for the current player, records it and passes the turn.
+    pub fn apply_action(&mut self, action: ActionType) -> Result<GameMove, GameError> {
+        if self.colors_unassigned() && matches!(action, ActionType::Move { .. }) {
+            return Err(GameError::FirstActionMustFlip);
+        }
+
+        let game_move = match action {
+            ActionType::Flip { x, y } => flip_piece(&mut self.board, x, y)?,
+            ActionType::Move { from_x, from_y, to_x, to_y } => move_piece(&mut self.board, self.current_player, from_x, from_y, to_x, to_y, &self.rules)?,
+        }
+        .ok_or(GameError::EmptyHiddenSquare)?;
+
+        let mover_before = self.current_player;
+        if let Some(captured) = game_move.captured_piece {
+            self.captured[captor_index(mover_before)].push(captured);
+        }
+
+        // The first piece revealed decides the flipping player's color
+        if let (PlayerColor::Unassigned, Some(piece)) = (self.first_player, game_move.piece) {
+            self.first_player = PlayerColor::Assigned(piece.player);
+            self.colors_from_flip = true;
+            self.current_player = piece.player;
+        }
+
+        // Update the hash incrementally; the side to move may also have been reassigned by the opening flip
+        let previous = self.position_hashes.last().copied().unwrap_or_else(|| position_hash(&self.board, self.current_player));
+        let moved = update_hash(previous, &game_move, self.board[0].len()) ^ zobrist_side_key(mover_before);
+        self.history.push(game_move);
+        self.current_player = opponent(self.current_player);
+        self.position_hashes.push(moved ^ zobrist_side_key(self.current_player));
+        self.redo_stack.clear();
+        Ok(game_move)
+    }
+
+    /// Checks whether the current player may take `action` without playing it, and if not why,
+    /// so a front-end can grey out options or explain a refusal before anything changes.
+    pub fn validate_action(&self, action: ActionType) -> Result<(), GameError> {
+        if self.is_game_over() {
+            return Err(GameError::GameOver);
+        }
+        match action {
+            ActionType::Flip { x, y } => check_flip(&self.board, x, y)?,
+            ActionType::Move { .. } if self.colors_unassigned() => return Err(GameError::FirstActionMustFlip),
+            ActionType::Move { from_x, from_y, to_x, to_y } => check_player_move(&self.board, self.current_player, from_x, from_y, to_x, to_y, &self.rules)?,
+        };
+        Ok(())
+    }
+
+    /// Reverts the last action and hands the turn back to the player who made it.
+    pub fn undo(&mut self) -> Result<GameMove, GameError> {
+        let undone = undo_last_move(&mut self.board, &mut self.history)?;
+        self.current_player = opponent(self.current_player);
+        // A resignation, agreed draw or loss on time came after this ply, so it goes with it
+        self.adjudicated = None;
+        self.position_hashes.pop();
+        if undone.captured_piece.is_some() {
+            self.captured[captor_index(self.current_player)].pop();
+        }
+
+        // Taking back the opening flip reopens the color choice, with Red back as the placeholder side to move
+        if self.colors_from_flip && self.history.is_empty() {
+            self.first_player = PlayerColor::Unassigned;
+            self.colors_from_flip = false;
+            self.current_player = Player::Red;
+        }
+        self.redo_stack.push(undone.action_type);
+        Ok(undone)
+    }
+
+    /// Replays the most recently undone action.
+    pub fn redo(&mut self) -> Result<GameMove, GameError> {
+        let action = self.redo_stack.pop().ok_or(GameError::NothingToRedo)?;
+        // Applying clears the stack, so keep the rest of the undone line around
+        let remaining = std::mem::take(&mut self.redo_stack);
+        let redone = self.apply_action(action);
+        self.redo_stack = remaining;
+        if redone.is_err() {
+            self.redo_stack.push(action);
+        }
+        redone
+    }
+
+    /// Pieces `player` has captured so far, in the order they fell.
+    pub fn captured_by(&self, player: Player) -> &[Piece] {
+        &self.captured[captor_index(player)]
+    }
+
+    /// Every flip and move the current player could make right now.
+    pub fn legal_actions(&self) -> Vec<ActionType> {
+        let actions = generate_legal_actions(&self.board, self.current_player, &self.rules);
+        if self.colors_unassigned() {
+            // Nobody owns a piece yet, so the opening action is always a flip
+            return actions.into_iter().filter(|action| matches!(action, ActionType::Flip { .. })).collect();
+        }
+        actions
+    }
+
+    /// The outcome of the game, if it has ended.
+    pub fn result(&self) -> Option<GameResult> {
+        if self.adjudicated.is_some() {
+            return self.adjudicated;
+        }
+        if self.colors_unassigned() {
+            return None;
+        }
+        // The last action was made by the player who is no longer to move
+        check_game_over(&self.board, opponent(self.current_player), &self.rules).or_else(|| self.draw_reason().map(GameResult::Draw))
+    }
+
+    /// Why the game is drawn by the repetition or no-progress rules, if it is.
+    pub fn draw_reason(&self) -> Option<DrawReason> {
+        if let Some(&current) = self.position_hashes.last() {
+            if self.position_hashes.iter().filter(|&&hash| hash == current).count() >= REPETITION_LIMIT {
+                return Some(DrawReason::Repetition);
+            }
+        }
+        match self.rules.no_progress_limit {
+            Some(limit) if plies_without_progress(&self.history) >= limit => Some(DrawReason::NoProgress),
+            _ => None,
+        }
+    }
+
+    pub fn is_game_over(&self) -> bool {
+        self.result().is_some()
+    }
+
+    /// Points scored by the player who made the opening flip and by the other, once the game
+    /// is over: 1 for a win, half each for a draw.
+    pub fn seat_points(&self) -> Option<[f64; 2]> {
+        match (self.result()?, self.first_player) {
+            (GameResult::Winner(winner), PlayerColor::Assigned(first)) => Some(if winner == first { [1.0, 0.0] } else { [0.0, 1.0] }),
+            (GameResult::Draw(_), _) => Some([0.5, 0.5]),
+            (GameResult::Winner(_), PlayerColor::Unassigned) => None,
+        }
+    }
+
+    /// Ends the game as a draw both players agreed to.
+    pub fn agree_draw(&mut self) -> Result<GameResult, GameError> {
+        if self.is_game_over() {
+            return Err(GameError::GameOver);
+        }
+        let result = GameResult::Draw(DrawReason::Agreement);
+        self.adjudicated = Some(result);
+        Ok(result)
+    }
+
+    /// Ends the game with a win for `player`'s opponent.
+    pub fn resign(&mut self, player: Player) -> Result<GameResult, GameError> {
+        if self.is_game_over() {
+            return Err(GameError::GameOver);
+        }
+        if self.colors_unassigned() {
+            return Err(GameError::ColorsUndecided);
+        }
+        let result = GameResult::Winner(opponent(player));
+        self.adjudicated = Some(result);
+        Ok(result)
+    }
+
+    /// Scores an action for the current player on a copy of the board without playing it. An
+    /// action the player couldn't make gets the same error `validate_action` gives.
+    pub fn evaluate_action(&self, action: ActionType) -> Result<f64, GameError> {
+        self.validate_action(action)?;
+        let player = self.current_player;
+        let outcome = |board: &Board| -> Result<i32, GameError> {
+            let mut hypothetical = board.clone();
+            match action {
+                ActionType::Flip { x, y } => flip_piece(&mut hypothetical, x, y)?,
+                ActionType::Move { from_x, from_y, to_x, to_y } => move_piece(&mut hypothetical, player, from_x, from_y, to_x, to_y, &self.rules)?,
+            };
+            Ok(evaluate(&hypothetical, player))
+        };
+
+        // What a flip turns up, or a cannon finds on a face-down square, is unknown to the player,
+        // so average over every piece still face down
+        let (x, y) = match action {
+            ActionType::Flip { x, y } => (x, y),
+            ActionType::Move { to_x, to_y, .. } => (to_x, to_y),
+        };
+        if !matches!(self.board[y][x], Cell::Hidden(_)) {
+            return Ok(outcome(&self.board)? as f64);
+        }
+        let pool = hidden_pieces(&self.board);
+        let mut total = 0;
+        for &piece in &pool {
+            let mut dealt = self.board.clone();
+            dealt[y][x] = Cell::Hidden(Some(piece));
+            total += outcome(&dealt)?;
+        }
+        Ok(total as f64 / pool.len() as f64)
+    }
+}
+
+/// The position on one line, as `to_fen` writes it without hidden contents, for logs and debugging.
+impl fmt::Display for GameState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_fen(false))
+    }
+}
+
+pub fn init_board<R: Rng + ?Sized>(rules: &RuleConfig, rng: &mut R) -> Result<Board, GameError> {
+    let mut pieces = Vec::new();
+
+    // Populate the vector with two sets of pieces, one for each player
+    for &player in &[Player::Red, Player::Black] {
+        for &(piece_type, count) in &rules.composition {
+            pieces.extend((0..count).map(|_| Piece { piece_type, player }));
+        }
+    }
+
+    pieces.shuffle(rng);
+
+    let mut board = build_board(&pieces, BOARD_WIDTH, BOARD_HEIGHT, rules.board_fill)?;
+    if pieces.len() < BOARD_WIDTH * BOARD_HEIGHT {
+        // A smaller set is spread over the whole board rather than packed into the top rows
+        let mut cells = board.concat();
+        cells.shuffle(rng);
+        board = cells.chunks(BOARD_WIDTH).map(|row| row.to_vec()).collect();
+    }
+    debug_assert!(verify_composition(&board, &rules.composition), "Initial board must hold the configured piece set.");
+    Ok(board)
+}
+
+pub fn build_board(pieces: &[Piece], width: usize, height: usize, fill: BoardFill) -> Result<Board, GameError> {
+    let cells = width * height;
+    if pieces.len() > cells {
+        return Err(GameError::TooManyPieces);
+    }
+    if pieces.len() < cells && fill == BoardFill::Exact {
+        return Err(GameError::BoardNotFilled);
+    }
+
+    // Lay the pieces out face down row by row, padding any leftover squares with empty cells
+    let mut cells: Vec<Cell> = pieces.iter().map(|&piece| Cell::Hidden(Some(piece))).collect();
+    cells.resize(width * height, Cell::Empty);
+    Ok(cells.chunks(width).map(|row| row.to_vec()).collect())
+}
+
+pub fn reveal_random_pieces<R: Rng + ?Sized>(board: &mut Board, count: usize, rng: &mut R) {
+    let mut hidden: Vec<(usize, usize)> = board
+        .iter()
+        .enumerate()
+        .flat_map(|(y, row)| {
+            row.iter().enumerate().filter_map(move |(x, cell)| match cell {
+                Cell::Hidden(Some(_)) => Some((x, y)),
+                _ => None,
+            })
+        })
+        .collect();
+
+    hidden.shuffle(rng);
+    for &(x, y) in hidden.iter().take(count) {
+        if let Cell::Hidden(Some(piece)) = board[y][x] {
+            board[y][x] = Cell::Revealed(piece);
+        }
+    }
+}
+
+pub fn validate_board(board: &Board) -> Result<(), GameError> {
+    let width = board.first().map_or(0, |row| row.len());
+    if width == 0 || board.iter().any(|row| row.len() != width) {
+        return Err(GameError::BoardNotRectangle);
+    }
+
+    // A hidden cell always conceals a piece; an empty hidden cell is never produced by play
+    if board.iter().flatten().any(|cell| matches!(cell, Cell::Hidden(None))) {
+        return Err(GameError::EmptyHiddenSquare);
+    }
+    Ok(())
+}
+
+pub fn verify_composition(board: &Board, composition: &Composition) -> bool {
+    let mut counts: HashMap<(Player, PieceType), usize> = HashMap::new();
+    for cell in board.iter().flatten() {
+        if let Cell::Hidden(Some(piece)) | Cell::Revealed(piece) = cell {
+            *counts.entry((piece.player, piece.piece_type)).or_insert(0) += 1;
+        }
+    }
+
+    // Every player must hold exactly the given count of every piece type, and nothing else
+    let total: usize = counts.values().sum();
+    let expected_total: usize = 2 * composition.iter().map(|&(_, count)| count).sum::<usize>();
+    total == expected_total
+        && [Player::Red, Player::Black].iter().all(|&player| {
+            composition
+                .iter()
+                .all(|&(piece_type, count)| counts.get(&(player, piece_type)).copied().unwrap_or(0) == count)
+        })
+}
+
+pub fn check_piece_limits(board: &Board, composition: &Composition) -> Result<(), GameError> {
+    // Edited positions may hold fewer pieces than the set, but never more of any type
+    for &player in &[Player::Red, Player::Black] {
+        for &(piece_type, limit) in composition {
+            let count = board
+                .iter()
+                .flatten()
+                .filter(|cell| matches!(cell, Cell::Hidden(Some(p)) | Cell::Revealed(p) if p.player == player && p.piece_type == piece_type))
+                .count();
+            if count > limit {
+                return Err(if piece_type == PieceType::General { GameError::DuplicateGeneral } else { GameError::PieceLimit });
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn flip_piece(board: &mut Board, x: usize, y: usize) -> Result<Option<GameMove>, GameError> {
+    let game_move = check_flip(board, x, y)?;
+    board[y][x] = Cell::Revealed(game_move.piece.expect("A flipped piece must exist."));
+    Ok(Some(game_move))
+}
+
+/// The flip of the square at (x, y), checked without turning the piece over.
+pub fn check_flip<B: Grid>(board: &B, x: usize, y: usize) -> Result<GameMove, GameError> {
+    match board.cell_at(x, y) {
+        None => Err(GameError::OutOfBounds),
+        Some(Cell::Hidden(Some(piece))) => Ok(GameMove {
+            action_type: ActionType::Flip { x, y },
+            piece: Some(piece),
+            captured_piece: None, // No piece is captured during a flip
+            captured_hidden: false,
+            revealed_own: None,
+        }),
+        // Hidden(None) never occurs on a validated board
+        Some(Cell::Hidden(None)) => Err(GameError::EmptyHiddenSquare),
+        Some(_) => Err(GameError::SquareNotHidden),
+    }
+}
+
+pub fn can_capture(attacker: Piece, defender: Piece) -> bool {
+    use PieceType::*;
+
+    match (attacker.piece_type, defender.piece_type) {
+        // Handle the special case where Soldiers can capture Generals but not the other way around
+        (Soldier, General) => true,
+        (General, Soldier) => false,
+
+        // Each piece captures pieces of the same type or lower rank, except for the special Soldier-General interaction
+        // General is the highest rank and Soldier the lowest, with the order being General > Advisor > Elephant > Chariot > Horse > Cannon > Soldier
+        // All pieces can capture lower-ranked pieces, except for the Soldier-General interaction
+        // Cannon can capture every piece in the cannon capture jump but otherwise it can't attack anything
+        // General rule: A piece can capture another piece of the same type or any type below it in the following order
+        // For other cases, use a predefined order of power to determine capture ability
+        _ => {
+            // A piece can capture another piece of the same type or any type below it in the hierarchy
+            piece_rank(attacker.piece_type) >= piece_rank(defender.piece_type)
+        }
+    }
+}
+
+pub fn piece_rank(piece_type: PieceType) -> i32 {
+    use PieceType::*;
+
+    match piece_type {
+        General => 7,
+        Advisor => 6,
+        Elephant => 5,
+        Chariot => 4, // Note: Chariot moves any number of spaces in a straight line, handled separately
+        Horse => 3,
+        Cannon => 2, // Note: Cannon's capturing rule needs board state, handled separately
+        Soldier => 1,
+    }
+}
+
+/// How far apart two squares on the same row or column are, and how many occupied squares
+/// lie between them, or `None` if they don't share a line.
+pub fn line_between<B: Grid>(board: &B, from_x: usize, from_y: usize, to_x: usize, to_y: usize) -> Option<(usize, usize)> {
+    let between = if (from_x, from_y) == (to_x, to_y) {
+        return None;
+    } else if from_x == to_x { // Vertical movement
+        (from_y.min(to_y) + 1..from_y.max(to_y)).filter(|&y| !matches!(board.cell(from_x, y), Cell::Empty)).count()
+    } else if from_y == to_y { // Horizontal movement
+        (from_x.min(to_x) + 1..from_x.max(to_x)).filter(|&x| !matches!(board.cell(x, from_y), Cell::Empty)).count()
+    } else {
+        return None;
+    };
+    Some((from_x.abs_diff(to_x) + from_y.abs_diff(to_y), between))
+}
+
+pub fn check_move<B: Grid>(board: &B, from_x: usize, from_y: usize, to_x: usize, to_y: usize, rules: &RuleConfig) -> Result<GameMove, GameError> {
+    let (Some(source), Some(target)) = (board.cell_at(from_x, from_y), board.cell_at(to_x, to_y)) else {
+        return Err(GameError::OutOfBounds);
+    };
+    let rule_set = rules.variant.rule_set();
+    let line = line_between(board, from_x, from_y, to_x, to_y);
+    let reach = |piece_type: PieceType, capture: bool| match line {
+        Some((distance, between)) => rule_set.check_reach(piece_type, distance, between, capture),
+        None => Err(GameError::NotInLine),
+    };
+
+    match source {
+        Cell::Revealed(attacker) => {
+            match target {
+                // Only a cannon capturing by jumping a screen may land on a face-down piece
+                Cell::Hidden(Some(defender)) if attacker.piece_type == PieceType::Cannon
+                    && rules.cannon_can_capture_hidden
+                    && line.is_some_and(|(_, between)| between == 1)
+                    && reach(PieceType::Cannon, true).is_ok() => {
+                    // The jump is allowed whatever is face down, so its legality gives nothing away.
+                    // Turning up an own piece voids the capture: the piece stays revealed where it
+                    // is, the cannon stays put, and the turn is lost
+                    if attacker.player == defender.player {
+                        return Ok(GameMove {
+                            action_type: ActionType::Move { from_x, from_y, to_x, to_y },
+                            piece: Some(attacker),
+                            captured_piece: None,
+                            captured_hidden: false,
+                            revealed_own: Some(defender),
+                        });
+                    }
+
+                    Ok(GameMove {
+                        action_type: ActionType::Move { from_x, from_y, to_x, to_y },
+                        piece: Some(attacker),
+                        captured_piece: Some(defender),
+                        captured_hidden: true,
+                        revealed_own: None,
+                    })
+                },
+                Cell::Hidden(_) => Err(GameError::OntoHiddenPiece),
+                Cell::Empty => {
+                    // Handle non-capturing moves
+                    reach(attacker.piece_type, false)?;
+                    let stepped_too_far = attacker.piece_type == PieceType::Cannon
+                        && rules.cannon_single_step
+                        && line.is_some_and(|(distance, _)| distance > 1);
+                    if stepped_too_far {
+                        return Err(GameError::TooFar);
+                    }
+
+                    Ok(GameMove {
+                        action_type: ActionType::Move { from_x, from_y, to_x, to_y },
+                        piece: Some(attacker),
+                        captured_piece: None,
+                        captured_hidden: false,
+                        revealed_own: None,
+                    })
+                },
+                Cell::Revealed(defender) => {
+                    // Handle capturing moves
+                    if attacker.player == defender.player {
+                        return Err(GameError::CaptureOwnPiece);
+                    }
+
+                    // The attacker must be able to reach the square and be allowed to take what is there
+                    reach(attacker.piece_type, true)?;
+                    if !rule_set.can_capture(attacker, defender) {
+                        return Err(if piece_rank(attacker.piece_type) < piece_rank(defender.piece_type) {
+                            GameError::CannotCaptureHigherRank
+                        } else {
+                            GameError::CaptureNotAllowed
+                        });
+                    }
+
+                    Ok(GameMove {
+                        action_type: ActionType::Move { from_x, from_y, to_x, to_y },
+                        piece: Some(attacker),
+                        captured_piece: Some(defender),
+                        captured_hidden: false,
+                        revealed_own: None,
+                    })
+                },
+            }
+        },
+        _ => Err(GameError::NoPieceToMove),
+    }
+}
+
+pub fn move_piece<B: Grid>(board: &mut B, player: Player, from_x: usize, from_y: usize, to_x: usize, to_y: usize, rules: &RuleConfig) -> Result<Option<GameMove>, GameError> {
+    let game_move = check_player_move(board, player, from_x, from_y, to_x, to_y, rules)?;
+    let attacker = game_move.piece.expect("A moved piece must exist.");
+    if let Some(own) = game_move.revealed_own {
+        board.set_cell(to_x, to_y, Cell::Revealed(own));
+        return Ok(Some(game_move));
+    }
+
+    board.set_cell(to_x, to_y, Cell::Revealed(attacker));
+    board.set_cell(from_x, from_y, Cell::Empty);
+    Ok(Some(game_move))
+}
+
+/// Like `check_move`, but also refuses to move a piece that isn't `player`'s.
+pub fn check_player_move<B: Grid>(board: &B, player: Player, from_x: usize, from_y: usize, to_x: usize, to_y: usize, rules: &RuleConfig) -> Result<GameMove, GameError> {
+    // Players may only move pieces of their own color
+    if let Some(Cell::Revealed(piece)) = board.cell_at(from_x, from_y) {
+        if piece.player != player {
+            return Err(GameError::NotYourPiece);
+        }
+    }
+    check_move(board, from_x, from_y, to_x, to_y, rules)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Threat {
+    pub target: Piece,
+    pub target_pos: (usize, usize),
+    pub attacker: Piece,
+    pub attacker_pos: (usize, usize),
+}
+
+pub fn threatened_pieces(board: &Board, player: Player, rules: &RuleConfig) -> Vec<Threat> {
+    let revealed: Vec<(usize, usize, Piece)> = board
+        .iter()
+        .enumerate()
+        .flat_map(|(y, row)| {
+            row.iter().enumerate().filter_map(move |(x, cell)| match cell {
+                Cell::Revealed(piece) => Some((x, y, *piece)),
+                _ => None,
+            })
+        })
+        .collect();
+
+    // Every opposing revealed piece that could legally capture one of ours next turn, cannon jumps included
+    let mut threats = Vec::new();
+    for &(tx, ty, target) in revealed.iter().filter(|(_, _, piece)| piece.player == player) {
+        for &(ax, ay, attacker) in revealed.iter().filter(|(_, _, piece)| piece.player != player) {
+            if check_move(board, ax, ay, tx, ty, rules).is_ok() {
+                threats.push(Threat { target, target_pos: (tx, ty), attacker, attacker_pos: (ax, ay) });
+            }
+        }
+    }
+    threats
+}
+
+pub fn undo_last_move(board: &mut Board, moves_history: &mut Vec<GameMove>) -> Result<GameMove, GameError> {
+    if let Some(last_move) = moves_history.pop() {
+        match last_move.action_type {
+            ActionType::Flip { x, y } => {
+                // If the last action was a flip, simply hide the piece again.
+                board[y][x] = Cell::Hidden(last_move.piece);
+            },
+            ActionType::Move { from_x, from_y, to_x, to_y } => {
+                // If the last action was a move, move the piece back to its original position.
+                let piece = last_move.piece.expect("A moved piece must exist.");
+                board[from_y][from_x] = Cell::Revealed(piece);
+
+                // If a piece was captured during the move, restore it to its position.
+                // Otherwise, set the cell to empty.
+                // A piece taken while still hidden goes back face down, as does an own piece a cannon turned up.
+                if let Some(own) = last_move.revealed_own {
+                    board[to_y][to_x] = Cell::Hidden(Some(own));
+                    return Ok(last_move);
+                }
+                match last_move.captured_piece {
+                    Some(captured_piece) if last_move.captured_hidden => board[to_y][to_x] = Cell::Hidden(Some(captured_piece)),
+                    Some(captured_piece) => board[to_y][to_x] = Cell::Revealed(captured_piece),
+                    None => board[to_y][to_x] = Cell::Empty,
+                }
+            }
+        }
+        Ok(last_move)
+    } else {
+        Err(GameError::NothingToUndo)
+    }
+}
+
+pub fn generate_legal_actions<B: Grid>(board: &B, player: Player, rules: &RuleConfig) -> Vec<ActionType> {
+    // Any face-down piece may be flipped; only the player's own revealed pieces may move
+    let mut actions = Vec::new();
+    for (x, y, cell) in board.squares() {
+        match cell {
+            Cell::Hidden(Some(_)) => actions.push(ActionType::Flip { x, y }),
+            Cell::Revealed(piece) if piece.player == player => {
+                // Every piece moves orthogonally, so only the piece's own row and column can be targets
+                let targets = (0..board.width()).map(|to_x| (to_x, y)).chain((0..board.height()).map(|to_y| (x, to_y)));
+                for (to_x, to_y) in targets {
+                    if (to_x, to_y) != (x, y) && check_move(board, x, y, to_x, to_y, rules).is_ok() {
+                        actions.push(ActionType::Move { from_x: x, from_y: y, to_x, to_y });
+                    }
+                }
+            },
+            _ => {},
+        }
+    }
+    actions
+}
+
+pub fn moves_from(board: &Board, x: usize, y: usize, rules: &RuleConfig) -> Result<Vec<GameMove>, GameError> {
+    // Moves and captures of the revealed piece on (x, y), whoever's turn it is
+    let piece = match board.get(y).and_then(|row| row.get(x)) {
+        Some(Cell::Revealed(piece)) => *piece,
+        Some(_) => return Err(GameError::NoRevealedPiece),
+        None => return Err(GameError::OutOfBounds),
+    };
+    Ok(generate_legal_actions(board, piece.player, rules)
+        .into_iter()
+        .filter_map(|action| match action {
+            ActionType::Move { from_x, from_y, to_x, to_y } if (from_x, from_y) == (x, y) => check_move(board, from_x, from_y, to_x, to_y, rules).ok(),
+            _ => None,
+        })
+        .collect())
+}
+
+pub fn check_game_over<B: Grid>(board: &B, last_mover: Player, rules: &RuleConfig) -> Option<GameResult> {
+    let mut red_pieces = 0;
+    let mut black_pieces = 0;
+    let mut hidden_pieces = 0;
+
+    for (_, _, cell) in board.squares() {
+        match cell {
+            Cell::Hidden(Some(_)) => hidden_pieces += 1,
+            Cell::Hidden(None) => {},
+            Cell::Revealed(piece) => match piece.player {
+                Player::Red => red_pieces += 1,
+                Player::Black => black_pieces += 1,
+            },
+            Cell::Empty => {},
+        }
+    }
+
+    // Do not end the game if there are still hidden pieces
+    if hidden_pieces > 0 {
+        return None;
+    }
+
+    // The rule set decides once everything is face up
+    let has_action = || !generate_legal_actions(board, opponent(last_mover), rules).is_empty();
+    rules.variant.rule_set().outcome([red_pieces, black_pieces], &has_action, last_mover, rules)
+}
+
+pub fn zobrist_key(square: usize, piece: Piece, revealed: bool) -> u64 {
+    // Keys come from a fixed mix of (square, piece, face up) so every board size gets stable keys without a table
+    let index = (square * 14 + piece.player as usize * 7 + piece.piece_type as usize) * 2 + usize::from(revealed);
+    splitmix64(index as u64 ^ 0x5851_F42D_4C95_7F2D)
+}
+
+pub fn zobrist_side_key(player_to_move: Player) -> u64 {
+    match player_to_move {
+        Player::Red => 0,
+        Player::Black => splitmix64(u64::MAX),
+    }
+}
+
+fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+pub fn position_hash<B: Grid>(board: &B, player_to_move: Player) -> u64 {
+    // Zobrist hash: same board with the same side to move hashes the same, regardless of how it was reached
+    let width = board.width();
+    let mut hash = zobrist_side_key(player_to_move);
+    for (x, y, cell) in board.squares() {
+        match cell {
+            Cell::Revealed(piece) => hash ^= zobrist_key(y * width + x, piece, true),
+            Cell::Hidden(Some(piece)) => hash ^= zobrist_key(y * width + x, piece, false),
+            _ => {},
+        }
+    }
+    hash
+}
+
+pub fn update_hash(hash: u64, game_move: &GameMove, width: usize) -> u64 {
+    // XORs in the squares an action changed; applying it twice undoes it. The side to move is handled separately
+    let Some(piece) = game_move.piece else { return hash };
+    match game_move.action_type {
+        ActionType::Flip { x, y } => hash ^ zobrist_key(y * width + x, piece, false) ^ zobrist_key(y * width + x, piece, true),
+        ActionType::Move { from_x, from_y, to_x, to_y } => {
+            let to = to_y * width + to_x;
+            // A voided cannon capture only turns the mover's own piece face up
+            if let Some(own) = game_move.revealed_own {
+                return hash ^ zobrist_key(to, own, false) ^ zobrist_key(to, own, true);
+            }
+            let captured = game_move.captured_piece.map_or(0, |victim| zobrist_key(to, victim, !game_move.captured_hidden));
+            hash ^ zobrist_key(from_y * width + from_x, piece, true) ^ zobrist_key(to, piece, true) ^ captured
+        },
+    }
+}
+
+pub fn plies_without_progress(moves_history: &[GameMove]) -> usize {
+    // Captures and flips both change the material on the board for good, and so does a voided cannon capture's reveal
+    moves_history
+        .iter()
+        .rev()
+        .take_while(|game_move| matches!(game_move.action_type, ActionType::Move { .. }) && game_move.captured_piece.is_none() && game_move.revealed_own.is_none())
+        .count()
+}
+
+pub fn material_count<B: Grid>(board: &B, player: Player) -> i32 {
+    // Sum of ranks of the player's revealed pieces still on the board
+    board
+        .squares()
+        .map(|(_, _, cell)| match cell {
+            Cell::Revealed(piece) if piece.player == player => piece_rank(piece.piece_type),
+            _ => 0,
+        })
+        .sum()
+}
+
+pub fn opponent(player: Player) -> Player {
+    match player {
+        Player::Red => Player::Black,
+        Player::Black => Player::Red,
+    }
+}
+
+pub fn evaluate<B: Grid>(board: &B, player: Player) -> i32 {
+    // Static evaluation: revealed material balance from the player's point of view
+    material_count(board, player) - material_count(board, opponent(player))
+}
+
+pub fn hidden_pieces<B: Grid>(board: &B) -> Vec<Piece> {
+    board
+        .squares()
+        .filter_map(|(_, _, cell)| match cell {
+            Cell::Hidden(Some(piece)) => Some(piece),
+            _ => None,
+        })
+        .collect()
+}
+
+pub fn hidden_count(board: &Board) -> usize {
+    // Squares whose contents aren't known, as on a board received over the network, still count
+    board.iter().flatten().filter(|cell| matches!(cell, Cell::Hidden(_))).count()
+}
+
+pub fn capture_count(moves_history: &[GameMove], player: Player) -> usize {
+    // Captures made by the player, i.e. moves of their pieces that took something
+    moves_history
+        .iter()
+        .filter(|game_move| game_move.captured_piece.is_some() && game_move.piece.map(|p| p.player) == Some(player))
+        .count()
+}
+
+pub fn flip_count(moves_history: &[GameMove], first_mover: Player, player: Player) -> usize {
+    // Turns alternate, so who made each flip follows from who made the first action
+    moves_history
+        .iter()
+        .enumerate()
+        .filter(|(ply, game_move)| {
+            let mover = if ply % 2 == 0 { first_mover } else { opponent(first_mover) };
+            mover == player && matches!(game_move.action_type, ActionType::Flip { .. })
+        })
+        .count()
+}
+
+pub fn capture_tally(moves_history: &[GameMove], player: Player) -> Vec<(PieceType, usize)> {
+    // Count the player's captures per victim type, listed in rank order
+    STANDARD_COMPOSITION
+        .iter()
+        .map(|&(piece_type, _)| {
+            let count = moves_history
+                .iter()
+                .filter(|game_move| game_move.piece.map(|p| p.player) == Some(player))
+                .filter(|game_move| game_move.captured_piece.map(|p| p.piece_type) == Some(piece_type))
+                .count();
+            (piece_type, count)
+        })
+        .filter(|&(_, count)| count > 0)
+        .collect()
+}
+
+pub fn swap_hidden_piece(board: &mut Board, x: usize, y: usize, piece: Piece) -> Result<(), GameError> {
+    // Trade hidden contents with another square so (x, y) conceals `piece`, leaving the set unchanged
+    match board.get(y).and_then(|row| row.get(x)) {
+        Some(Cell::Hidden(Some(current))) if *current == piece => return Ok(()),
+        Some(Cell::Hidden(Some(_))) => {},
+        _ => return Err(GameError::SquareNotHidden),
+    }
+    let (other_x, other_y) = board
+        .iter()
+        .enumerate()
+        .flat_map(|(oy, row)| row.iter().enumerate().map(move |(ox, cell)| (ox, oy, cell)))
+        .find(|&(_, _, cell)| *cell == Cell::Hidden(Some(piece)))
+        .map(|(ox, oy, _)| (ox, oy))
+        .ok_or(GameError::PieceNotHidden)?;
+    board[other_y][other_x] = board[y][x];
+    board[y][x] = Cell::Hidden(Some(piece));
+    Ok(())
+}
+
+pub fn perft(game: &GameState, depth: u32) -> u64 {
+    // Leaf count of the game tree, with each distinct piece a flip could reveal as its own branch
+    if depth == 0 {
+        return 1;
+    }
+    if game.is_game_over() {
+        return 0;
+    }
+
+    let mut outcomes: Vec<Piece> = hidden_pieces(&game.board);
+    outcomes.sort_by_key(|piece| (piece.player as u8, piece.piece_type as u8));
+    outcomes.dedup();
+
+    let mut count = 0;
+    for action in game.legal_actions() {
+        let mut branches = Vec::new();
+        match action {
+            ActionType::Flip { x, y } => {
+                for &piece in &outcomes {
+                    let mut child = game.clone();
+                    if swap_hidden_piece(&mut child.board, x, y, piece).is_ok() {
+                        branches.push(child);
+                    }
+                }
+            },
+            ActionType::Move { .. } => branches.push(game.clone()),
+        }
+        for mut child in branches {
+            if child.apply_action(action).is_ok() {
+                count += perft(&child, depth - 1);
+            }
+        }
+    }
+    count
+}
+
+pub fn flip_all_pieces(board: &mut Board) {
+    // For testing
+    for row in board.iter_mut() {
+        for cell in row.iter_mut() {
+            if let Cell::Hidden(Some(piece)) = cell {
+                *cell = Cell::Revealed(*piece);
+            }
+        }
+    }
+}
+
+pub fn parse_player(name: &str) -> Option<Player> {
+    match name {
+        "red" | "r" => Some(Player::Red),
+        "black" | "b" => Some(Player::Black),
+        _ => None,
+    }
+}
+
+pub fn parse_piece_type(name: &str) -> Option<PieceType> {
+    use PieceType::*;
+
+    match name {
+        "general" => Some(General),
+        "advisor" => Some(Advisor),
+        "elephant" => Some(Elephant),
+        "chariot" => Some(Chariot),
+        "horse" => Some(Horse),
+        "cannon" => Some(Cannon),
+        "soldier" => Some(Soldier),
+        _ => None,
+    }
+}
+
+pub fn place_cell(board: &mut Board, x: usize, y: usize, cell: Cell, composition: &Composition) -> Result<(), GameError> {
+    if y >= board.len() || x >= board[0].len() {
+        return Err(GameError::OutOfBounds);
+    }
+
+    if let Cell::Hidden(Some(piece)) | Cell::Revealed(piece) = cell {
+        // The piece being replaced doesn't count against the limit
+        let limit = composition.iter().find(|&&(t, _)| t == piece.piece_type).map_or(0, |&(_, count)| count);
+        let existing = board
+            .iter()
+            .enumerate()
+            .flat_map(|(cy, row)| row.iter().enumerate().map(move |(cx, cell)| (cx, cy, cell)))
+            .filter(|&(cx, cy, cell)| {
+                (cx, cy) != (x, y)
+                    && matches!(cell, Cell::Hidden(Some(p)) | Cell::Revealed(p) if p.player == piece.player && p.piece_type == piece.piece_type)
+            })
+            .count();
+        if existing >= limit {
+            return Err(GameError::PieceLimit);
+        }
+    }
+
+    board[y][x] = cell;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::SeedableRng;
+
+    fn piece(player: Player, piece_type: PieceType) -> Piece {
+        Piece { player, piece_type }
+    }
+
+    // A red cannon on 0,0 with a screen on 1,0 and `target` face down on 2,0
+    fn cannon_jump_game(target: Piece) -> GameState {
+        let mut board = vec![vec![Cell::Empty; BOARD_WIDTH]; BOARD_HEIGHT];
+        board[0][0] = Cell::Revealed(piece(Player::Red, PieceType::Cannon));
+        board[0][1] = Cell::Revealed(piece(Player::Black, PieceType::Soldier));
+        board[0][2] = Cell::Hidden(Some(target));
+        board[3][7] = Cell::Revealed(piece(Player::Black, PieceType::General));
+        let rules = RuleConfig { cannon_can_capture_hidden: true, ..RuleConfig::default() };
+        GameState::from_board(board, Player::Red, rules)
+    }
+
+    const JUMP: ActionType = ActionType::Move { from_x: 0, from_y: 0, to_x: 2, to_y: 0 };
+
+    #[test]
+    fn hidden_cannon_capture_records_the_captured_identity() {
+        let horse = piece(Player::Black, PieceType::Horse);
+        let mut game = cannon_jump_game(horse);
+
+        let played = game.apply_action(JUMP).unwrap();
+        assert_eq!(played.captured_piece, Some(horse));
+        assert!(played.captured_hidden);
+        assert_eq!(game.captured_by(Player::Red), &[horse]);
+        assert_eq!(game.board[0][2], Cell::Revealed(piece(Player::Red, PieceType::Cannon)));
+
+        game.undo().unwrap();
+        assert_eq!(game.board[0][2], Cell::Hidden(Some(horse)));
+        assert!(game.captured_by(Player::Red).is_empty());
+    }
+
+    #[test]
+    fn hidden_cannon_jump_is_legal_whatever_is_face_down() {
+        let enemy = cannon_jump_game(piece(Player::Black, PieceType::Horse));
+        let own = cannon_jump_game(piece(Player::Red, PieceType::Horse));
+        assert_eq!(enemy.validate_action(JUMP), Ok(()));
+        assert_eq!(own.validate_action(JUMP), Ok(()));
+        assert_eq!(enemy.legal_actions(), own.legal_actions());
+    }
+
+    #[test]
+    fn hidden_cannon_jump_onto_own_piece_voids_the_capture() {
+        let horse = piece(Player::Red, PieceType::Horse);
+        let mut game = cannon_jump_game(horse);
+        let start_hash = game.position_hashes[0];
+
+        let played = game.apply_action(JUMP).unwrap();
+        assert_eq!(played.captured_piece, None);
+        assert_eq!(played.revealed_own, Some(horse));
+        assert_eq!(game.board[0][0], Cell::Revealed(piece(Player::Red, PieceType::Cannon)));
+        assert_eq!(game.board[0][2], Cell::Revealed(horse));
+        assert_eq!(game.current_player, Player::Black);
+        assert!(game.captured_by(Player::Red).is_empty());
+        assert_eq!(game.position_hashes.last(), Some(&position_hash(&game.board, Player::Black)));
+
+        game.undo().unwrap();
+        assert_eq!(game.board[0][2], Cell::Hidden(Some(horse)));
+        assert_eq!(game.current_player, Player::Red);
+        assert_eq!(position_hash(&game.board, Player::Red), start_hash);
+    }
+
+    // Everything an action may change, with the hidden pool spelled out on its own
+    type Snapshot = (Board, Vec<Piece>, Player, PlayerColor, bool, Vec<GameMove>, Vec<u64>, [Vec<Piece>; 2]);
+
+    fn snapshot(game: &GameState) -> Snapshot {
+        (
+            game.board.clone(),
+            hidden_pieces(&game.board),
+            game.current_player,
+            game.first_player,
+            game.colors_from_flip,
+            game.history.clone(),
+            game.position_hashes.clone(),
+            game.captured.clone(),
+        )
+    }
+
+    #[test]
+    fn apply_then_undo_restores_every_action_in_random_games() {
+        let mut rng = StdRng::seed_from_u64(724);
+        // Flips, quiet moves, direct captures and cannon jumps, by how often each was checked
+        let mut seen = [0usize; 4];
+        for game_index in 0..200 {
+            let rules = RuleConfig { cannon_can_capture_hidden: game_index % 2 == 0, ..RuleConfig::default() };
+            let mut game = GameState::with_rng(rules, &mut rng).unwrap();
+            while game.result().is_none() && game.history.len() < 400 {
+                let action = *game.legal_actions().choose(&mut rng).unwrap();
+                let before = snapshot(&game);
+                let played = game.apply_action(action).unwrap();
+                let after = snapshot(&game);
+
+                game.undo().unwrap();
+                assert_eq!(snapshot(&game), before, "undoing {:?} in game {}", played, game_index);
+                game.redo().unwrap();
+                assert_eq!(snapshot(&game), after, "redoing {:?} in game {}", played, game_index);
+                assert_eq!(game.position_hashes.last(), Some(&position_hash(&game.board, game.current_player)));
+
+                let took = played.captured_piece.is_some() || played.revealed_own.is_some();
+                let kind = match played.action_type {
+                    ActionType::Flip { .. } => 0,
+                    ActionType::Move { from_x, from_y, to_x, to_y } if took && from_x.abs_diff(to_x) + from_y.abs_diff(to_y) > 1 => 3,
+                    ActionType::Move { .. } if took => 2,
+                    ActionType::Move { .. } => 1,
+                };
+                seen[kind] += 1;
+            }
+        }
+        assert!(seen.iter().all(|&count| count > 0), "action kinds checked: {:?}", seen);
+    }
+
+    #[test]
+    fn simultaneous_elimination_follows_the_configured_outcome() {
+        let board = vec![vec![Cell::Empty; BOARD_WIDTH]; BOARD_HEIGHT];
+        let draw = RuleConfig::default();
+        assert_eq!(check_game_over(&board, Player::Black, &draw), Some(GameResult::Draw(DrawReason::Elimination)));
+
+        let last_mover_wins = RuleConfig { simultaneous_elimination: EliminationOutcome::LastMoverWins, ..RuleConfig::default() };
+        assert_eq!(check_game_over(&board, Player::Black, &last_mover_wins), Some(GameResult::Winner(Player::Black)));
+        assert_eq!(check_game_over(&board, Player::Red, &last_mover_wins), Some(GameResult::Winner(Player::Red)));
+    }
+
+    #[test]
+    fn threatened_pieces_lists_a_hanging_piece_with_its_cannon_attacker() {
+        // The red horse on 2,1 hangs to the black cannon jumping the hidden square between them;
+        // the black soldier next to it is too weak to take it
+        let mut board = vec![vec![Cell::Empty; BOARD_WIDTH]; BOARD_HEIGHT];
+        board[1][0] = Cell::Revealed(piece(Player::Black, PieceType::Cannon));
+        board[1][1] = Cell::Hidden(Some(piece(Player::Black, PieceType::Horse)));
+        board[1][2] = Cell::Revealed(piece(Player::Red, PieceType::Horse));
+        board[1][3] = Cell::Revealed(piece(Player::Black, PieceType::Soldier));
+        board[3][7] = Cell::Revealed(piece(Player::Red, PieceType::Advisor));
+
+        let threats = threatened_pieces(&board, Player::Red, &RuleConfig::default());
+        assert_eq!(threats.len(), 1);
+        assert_eq!(threats[0].target, piece(Player::Red, PieceType::Horse));
+        assert_eq!(threats[0].target_pos, (2, 1));
+        assert_eq!(threats[0].attacker, piece(Player::Black, PieceType::Cannon));
+        assert_eq!(threats[0].attacker_pos, (0, 1));
+    }
+
+    #[test]
+    fn cannon_cannot_capture_an_adjacent_piece_without_a_screen() {
+        let mut board = vec![vec![Cell::Empty; BOARD_WIDTH]; BOARD_HEIGHT];
+        board[0][0] = Cell::Revealed(piece(Player::Red, PieceType::Cannon));
+        board[0][1] = Cell::Revealed(piece(Player::Black, PieceType::Soldier));
+        let rules = RuleConfig::default();
+
+        assert!(check_move(&board, 0, 0, 1, 0, &rules).is_err());
+        assert!(check_move(&board, 0, 0, 0, 1, &rules).is_ok());
+        assert!(!generate_legal_actions(&board, Player::Red, &rules).contains(&ActionType::Move { from_x: 0, from_y: 0, to_x: 1, to_y: 0 }));
+    }
+
+    #[test]
+    fn verify_composition_accepts_the_standard_board_and_rejects_a_tampered_one() {
+        let mut board = init_board(&RuleConfig::default(), &mut StdRng::seed_from_u64(700)).unwrap();
+        assert!(verify_composition(&board, &STANDARD_COMPOSITION));
+
+        // A second red general in place of whatever was on the first square
+        board[0][0] = Cell::Hidden(Some(piece(Player::Red, PieceType::General)));
+        assert!(!verify_composition(&board, &STANDARD_COMPOSITION));
+
+        board[0][0] = Cell::Empty;
+        assert!(!verify_composition(&board, &STANDARD_COMPOSITION));
+    }
+
+    fn capture(attacker: Piece, victim: Piece) -> GameMove {
+        GameMove {
+            action_type: ActionType::Move { from_x: 0, from_y: 0, to_x: 1, to_y: 0 },
+            piece: Some(attacker),
+            captured_piece: Some(victim),
+            captured_hidden: false,
+            revealed_own: None,
+        }
+    }
+
+    #[test]
+    fn capture_tally_counts_each_player_s_captures_by_type() {
+        let (red_chariot, black_horse) = (piece(Player::Red, PieceType::Chariot), piece(Player::Black, PieceType::Horse));
+        let history = [
+            capture(red_chariot, piece(Player::Black, PieceType::Soldier)),
+            capture(black_horse, piece(Player::Red, PieceType::Cannon)),
+            capture(red_chariot, black_horse),
+            capture(red_chariot, piece(Player::Black, PieceType::Soldier)),
+        ];
+        assert_eq!(capture_tally(&history, Player::Red), vec![(PieceType::Horse, 1), (PieceType::Soldier, 2)]);
+        assert_eq!(capture_tally(&history, Player::Black), vec![(PieceType::Cannon, 1)]);
+        assert_eq!(capture_count(&history, Player::Red), 3);
+    }
+
+    #[test]
+    fn evaluate_action_matches_evaluate_on_the_hypothetical_board() {
+        let game = cannon_jump_game(piece(Player::Black, PieceType::Horse));
+        let step = ActionType::Move { from_x: 0, from_y: 0, to_x: 0, to_y: 1 };
+        let mut hypothetical = game.board.clone();
+        move_piece(&mut hypothetical, Player::Red, 0, 0, 0, 1, &game.rules).unwrap();
+        assert_eq!(game.evaluate_action(step), Ok(evaluate(&hypothetical, Player::Red) as f64));
+
+        // Only one piece is face down, so the jump onto it is scored as taking it
+        let mut hypothetical = game.board.clone();
+        move_piece(&mut hypothetical, Player::Red, 0, 0, 2, 0, &game.rules).unwrap();
+        assert_eq!(game.evaluate_action(JUMP), Ok(evaluate(&hypothetical, Player::Red) as f64));
+        assert_eq!(game.board, cannon_jump_game(piece(Player::Black, PieceType::Horse)).board);
+
+        let mut hypothetical = game.board.clone();
+        hypothetical[0][2] = Cell::Revealed(piece(Player::Black, PieceType::Horse));
+        assert_eq!(game.evaluate_action(ActionType::Flip { x: 2, y: 0 }), Ok(evaluate(&hypothetical, Player::Red) as f64));
+    }
+
+    #[test]
+    fn evaluate_action_rejects_what_validate_action_rejects() {
+        let game = cannon_jump_game(piece(Player::Black, PieceType::Horse));
+        let onto_screen = ActionType::Move { from_x: 0, from_y: 0, to_x: 1, to_y: 0 };
+        assert_eq!(game.evaluate_action(onto_screen), Err(game.validate_action(onto_screen).unwrap_err()));
+
+        let mut opening = GameState::with_rng(RuleConfig::default(), &mut StdRng::seed_from_u64(707)).unwrap();
+        opening.board[0][0] = Cell::Revealed(piece(Player::Red, PieceType::Chariot));
+        opening.board[0][1] = Cell::Empty;
+        let before_the_first_flip = ActionType::Move { from_x: 0, from_y: 0, to_x: 1, to_y: 0 };
+        assert_eq!(opening.evaluate_action(before_the_first_flip), Err(GameError::FirstActionMustFlip));
+    }
+
+    #[test]
+    fn three_initial_reveals_turn_exactly_three_pieces_face_up() {
+        let mut rng = StdRng::seed_from_u64(708);
+        let mut game = GameState::with_rng(RuleConfig::default(), &mut rng).unwrap();
+        reveal_random_pieces(&mut game.board, 3, &mut rng);
+
+        let revealed = game.board.iter().flatten().filter(|cell| matches!(cell, Cell::Revealed(_))).count();
+        assert_eq!(revealed, 3);
+        assert_eq!(hidden_pieces(&game.board).len(), BOARD_WIDTH * BOARD_HEIGHT - 3);
+        assert!(verify_composition(&game.board, &STANDARD_COMPOSITION));
+        // The face-up pieces show in the position shared with players
+        assert_eq!(game.to_fen(false).chars().filter(char::is_ascii_alphabetic).count(), 3);
+    }
+
+    #[test]
+    fn undo_last_move_returns_the_move_it_reverted() {
+        let mut game = cannon_jump_game(piece(Player::Black, PieceType::Horse));
+        let flipped = game.apply_action(ActionType::Flip { x: 2, y: 0 }).unwrap();
+        let stepped = game.apply_action(ActionType::Move { from_x: 7, from_y: 3, to_x: 7, to_y: 2 }).unwrap();
+
+        let mut board = game.board.clone();
+        let mut history = game.history.clone();
+        assert_eq!(undo_last_move(&mut board, &mut history), Ok(stepped));
+        assert_eq!(undo_last_move(&mut board, &mut history), Ok(flipped));
+        assert_eq!(undo_last_move(&mut board, &mut history), Err(GameError::NothingToUndo));
+        assert_eq!(board, cannon_jump_game(piece(Player::Black, PieceType::Horse)).board);
+    }
+
+    #[test]
+    fn undo_reopens_a_game_decided_off_the_board() {
+        let mut game = cannon_jump_game(piece(Player::Black, PieceType::Horse));
+        game.apply_action(ActionType::Flip { x: 2, y: 0 }).unwrap();
+        game.apply_action(ActionType::Move { from_x: 7, from_y: 3, to_x: 7, to_y: 2 }).unwrap();
+
+        game.resign(game.current_player).unwrap();
+        game.undo().unwrap();
+        assert_eq!(game.result(), None);
+        game.apply_action(ActionType::Move { from_x: 7, from_y: 3, to_x: 7, to_y: 2 }).unwrap();
+
+        game.agree_draw().unwrap();
+        game.undo().unwrap();
+        assert_eq!(game.result(), None);
+        assert!(!game.legal_actions().is_empty());
+    }
+
+    #[test]
+    fn build_board_errors_or_pads_when_the_pieces_fall_short() {
+        let pieces = vec![piece(Player::Red, PieceType::Soldier); 30];
+        assert_eq!(build_board(&pieces, BOARD_WIDTH, BOARD_HEIGHT, BoardFill::Exact), Err(GameError::BoardNotFilled));
+
+        let board = build_board(&pieces, BOARD_WIDTH, BOARD_HEIGHT, BoardFill::PadWithEmpty).unwrap();
+        assert_eq!(board.len(), BOARD_HEIGHT);
+        assert!(board.iter().all(|row| row.len() == BOARD_WIDTH));
+        assert_eq!(hidden_pieces(&board).len(), 30);
+        assert_eq!(board[3][6..], [Cell::Empty, Cell::Empty]);
+
+        let too_many = vec![piece(Player::Red, PieceType::Soldier); 33];
+        assert_eq!(build_board(&too_many, BOARD_WIDTH, BOARD_HEIGHT, BoardFill::PadWithEmpty), Err(GameError::TooManyPieces));
+    }
+
+    #[test]
+    fn boards_with_an_empty_hidden_square_are_rejected() {
+        let mut board = vec![vec![Cell::Empty; BOARD_WIDTH]; BOARD_HEIGHT];
+        board[1][1] = Cell::Hidden(None);
+        assert_eq!(validate_board(&board), Err(GameError::EmptyHiddenSquare));
+        assert_eq!(flip_piece(&mut board, 1, 1), Err(GameError::EmptyHiddenSquare));
+
+        board[1][1] = Cell::Hidden(Some(piece(Player::Red, PieceType::Horse)));
+        assert_eq!(validate_board(&board), Ok(()));
+    }
+
+    #[test]
+    fn positions_with_more_pieces_than_the_set_are_rejected() {
+        let mut rng = StdRng::seed_from_u64(759);
+        for fen in ["KKK5/8/8/8 r", "PPPPPPPP/PPPPPPPP/PPPP4/8 r", "*c*c*c5/8/8/8"] {
+            assert!(matches!(GameState::from_fen(fen, RuleConfig::default(), &mut rng), Err(GameError::InvalidPosition(_))), "{} loaded", fen);
+        }
+        assert!(GameState::from_fen("K*k*p5/8/8/8 r", RuleConfig::default(), &mut rng).is_ok());
+    }
+
+    #[test]
+    fn display_writes_the_position_without_hidden_contents() {
+        let mut game = cannon_jump_game(piece(Player::Black, PieceType::Horse));
+        assert_eq!(format!("{}", game), game.to_fen(false));
+        assert_ne!(format!("{}", game), game.to_fen(true));
+        game.apply_action(ActionType::Flip { x: 2, y: 0 }).unwrap();
+        assert_eq!(format!("{}", game), game.to_fen(false));
+    }
+}
@@ -0,0 +1,436 @@
+use crate::display::{describe_ending, describe_move, CoordinateStyle};
+use crate::profiles::rate_game;
+use crate::web::{color_name, state_message, ClientMessage, GameSummary, ServerMessage};
+use crate::*;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+// Sessions and games behind the WebSocket server. The lobby knows nothing about sockets: the
+// server hands it each client's messages and sends whatever it leaves in the outbox.
+
+pub type ClientId = u64;
+pub type GameId = u64;
+
+/// Name of a client who didn't give one. Guests' games aren't rated.
+pub const GUEST_NAME: &str = "Guest";
+
+/// Where a connected client is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Lobby,
+    Player(GameId, usize),
+    Spectator(GameId),
+}
+
+struct Session {
+    name: String,
+    role: Role,
+}
+
+/// One game: its two seats, and what its spectators have been shown so far.
+struct Table {
+    title: String,
+    game: GameState,
+    seats: [Option<(ClientId, String)>; 2],
+    delayed: VecDeque<(Instant, ServerMessage)>, // Spectator messages waiting out the delay
+    spectator_view: Option<ServerMessage>, // Latest state released to spectators, for late arrivals
+    draw_offer: Option<usize>, // Seat whose draw offer is waiting for an answer
+    termination: Option<&'static str>, // How the game ended off the board, as game records write it
+    finished: bool,
+}
+
+impl Table {
+    fn started(&self) -> bool {
+        self.seats.iter().all(Option::is_some)
+    }
+
+    fn open(&self) -> bool {
+        !self.finished && !self.started()
+    }
+
+    fn names(&self) -> [String; 2] {
+        self.seats.clone().map(|seat| seat.map_or_else(String::new, |(_, name)| name))
+    }
+}
+
+pub struct Lobby {
+    sessions: HashMap<ClientId, Session>,
+    tables: BTreeMap<GameId, Table>,
+    next_game: GameId,
+    new_game: Box<dyn FnMut() -> Result<GameState, GameError>>,
+    spectator_delay: Duration,
+    profiles: Option<PathBuf>, // Where named players' ratings are kept, if games are rated
+    outbox: Vec<(ClientId, ServerMessage)>,
+}
+
+fn display_name(name: Option<String>) -> Option<String> {
+    name.map(|name| name.trim().to_string()).filter(|name| !name.is_empty())
+}
+
+impl Lobby {
+    pub fn new(new_game: Box<dyn FnMut() -> Result<GameState, GameError>>, spectator_delay: Duration, profiles: Option<PathBuf>) -> Self {
+        Lobby { sessions: HashMap::new(), tables: BTreeMap::new(), next_game: 1, new_game, spectator_delay, profiles, outbox: Vec::new() }
+    }
+
+    pub fn connect(&mut self, client: ClientId) {
+        self.sessions.insert(client, Session { name: String::from(GUEST_NAME), role: Role::Lobby });
+    }
+
+    /// Removes a client whose connection closed, leaving any game they were in.
+    pub fn disconnect(&mut self, client: ClientId) {
+        self.leave(client);
+        self.sessions.remove(&client);
+    }
+
+    pub fn role(&self, client: ClientId) -> Option<Role> {
+        self.sessions.get(&client).map(|session| session.role)
+    }
+
+    /// How a finished game ended off the board, e.g. "resignation"; `None` for an ending on the board.
+    pub fn termination(&self, game_id: GameId) -> Option<&'static str> {
+        self.tables.get(&game_id).and_then(|table| table.termination)
+    }
+
+    /// Messages to send, in order, since the last call.
+    pub fn take_outbox(&mut self) -> Vec<(ClientId, ServerMessage)> {
+        std::mem::take(&mut self.outbox)
+    }
+
+    pub fn games(&self) -> Vec<GameSummary> {
+        self.tables
+            .iter()
+            .map(|(&game_id, table)| GameSummary {
+                game_id,
+                title: table.title.clone(),
+                players: table.seats.iter().flatten().map(|(_, name)| name.clone()).collect(),
+                open: table.open(),
+                spectators: self.sessions.values().filter(|session| session.role == Role::Spectator(game_id)).count(),
+            })
+            .collect()
+    }
+
+    /// Acts on one message from `client`. An error is meant for that client alone.
+    pub fn handle(&mut self, client: ClientId, message: ClientMessage) -> Result<(), String> {
+        let role = self.role(client).ok_or("Unknown client.")?;
+        match (message, role) {
+            (ClientMessage::List, _) => {
+                let games = self.games();
+                self.outbox.push((client, ServerMessage::Games { games }));
+                Ok(())
+            },
+            (ClientMessage::Create { name, title }, Role::Lobby) => {
+                self.rename(client, name);
+                let game_id = self.create_table(client, display_name(title))?;
+                self.take_seat(client, game_id)
+            },
+            (ClientMessage::Join { game_id, name }, Role::Lobby) => {
+                self.rename(client, name);
+                // Without an id, pair up with whoever has been waiting longest
+                let game_id = match game_id.or_else(|| self.tables.iter().find(|(_, table)| table.open()).map(|(&id, _)| id)) {
+                    Some(game_id) => game_id,
+                    None => self.create_table(client, None)?,
+                };
+                self.take_seat(client, game_id)
+            },
+            (ClientMessage::Spectate { game_id, name }, Role::Lobby) => {
+                self.rename(client, name);
+                let game_id = game_id
+                    .or_else(|| self.tables.iter().find(|(_, table)| table.started() && !table.finished).map(|(&id, _)| id))
+                    .ok_or("No game is under way to watch.")?;
+                let table = self.tables.get(&game_id).ok_or("No game with that id.")?;
+                let joined = ServerMessage::Joined { game_id, title: table.title.clone(), seat: None };
+                let view = table.spectator_view.clone();
+                self.set_role(client, Role::Spectator(game_id));
+                println!("{} is watching game {}.", self.name(client), game_id);
+                self.outbox.push((client, joined));
+                self.outbox.extend(view.map(|view| (client, view)));
+                Ok(())
+            },
+            (ClientMessage::Create { .. } | ClientMessage::Join { .. } | ClientMessage::Spectate { .. }, _) => {
+                Err(String::from("Leave your current game first."))
+            },
+            (ClientMessage::Action { .. }, Role::Lobby) => Err(String::from("Join a game first.")),
+            (ClientMessage::Action { .. }, Role::Spectator(_)) => Err(String::from("Spectators cannot act.")),
+            (ClientMessage::Action { action }, Role::Player(game_id, seat)) => self.play(client, game_id, seat, action.into()),
+            (ClientMessage::Resign, Role::Player(game_id, seat)) => self.resign(game_id, seat),
+            (ClientMessage::Resign, _) => Err(String::from("Only a player can resign.")),
+            (ClientMessage::OfferDraw, Role::Player(game_id, seat)) => self.offer_draw(game_id, seat),
+            (ClientMessage::AnswerDraw { accept }, Role::Player(game_id, seat)) => self.answer_draw(game_id, seat, accept),
+            (ClientMessage::OfferDraw | ClientMessage::AnswerDraw { .. }, _) => Err(String::from("Only a player can offer or accept a draw.")),
+            (ClientMessage::Leave, _) => {
+                self.leave(client);
+                Ok(())
+            },
+        }
+    }
+
+    /// Shows spectators whatever has waited out the delay, and closes games that are over.
+    pub fn tick(&mut self, now: Instant) {
+        let mut closed = Vec::new();
+        for (&game_id, table) in self.tables.iter_mut() {
+            while table.delayed.front().is_some_and(|(due, _)| *due <= now) {
+                let Some((_, message)) = table.delayed.pop_front() else { break };
+                for (&client, session) in self.sessions.iter() {
+                    if session.role == Role::Spectator(game_id) {
+                        self.outbox.push((client, message.clone()));
+                    }
+                }
+                table.spectator_view = Some(message);
+            }
+            if table.finished && table.delayed.is_empty() {
+                closed.push(game_id);
+            }
+        }
+        for game_id in closed {
+            self.tables.remove(&game_id);
+            for session in self.sessions.values_mut() {
+                if matches!(session.role, Role::Player(id, _) | Role::Spectator(id) if id == game_id) {
+                    session.role = Role::Lobby;
+                }
+            }
+        }
+    }
+
+    fn name(&self, client: ClientId) -> String {
+        self.sessions.get(&client).map_or_else(String::new, |session| session.name.clone())
+    }
+
+    fn rename(&mut self, client: ClientId, name: Option<String>) {
+        if let (Some(session), Some(name)) = (self.sessions.get_mut(&client), display_name(name)) {
+            session.name = name;
+        }
+    }
+
+    fn set_role(&mut self, client: ClientId, role: Role) {
+        if let Some(session) = self.sessions.get_mut(&client) {
+            session.role = role;
+        }
+    }
+
+    fn create_table(&mut self, client: ClientId, title: Option<String>) -> Result<GameId, String> {
+        let game = (self.new_game)().map_err(|e| e.to_string())?;
+        let game_id = self.next_game;
+        self.next_game += 1;
+        let title = title.unwrap_or_else(|| format!("{}'s game", self.name(client)));
+        println!("Game {} ({}) created.", game_id, title);
+        self.tables.insert(game_id, Table { title, game, seats: [None, None], delayed: VecDeque::new(), spectator_view: None, draw_offer: None, termination: None, finished: false });
+        Ok(game_id)
+    }
+
+    fn take_seat(&mut self, client: ClientId, game_id: GameId) -> Result<(), String> {
+        let name = self.name(client);
+        let table = self.tables.get_mut(&game_id).ok_or("No game with that id.")?;
+        if !table.open() {
+            return Err(String::from("That game has no free seat; spectate it instead."));
+        }
+        // The creator sits first and makes the opening flip
+        let seat = if table.seats[0].is_none() { 0 } else { 1 };
+        table.seats[seat] = Some((client, name.clone()));
+        let joined = ServerMessage::Joined { game_id, title: table.title.clone(), seat: Some(seat) };
+        let started = table.started();
+        self.set_role(client, Role::Player(game_id, seat));
+        println!("{} took seat {} in game {}.", name, seat + 1, game_id);
+        self.outbox.push((client, joined));
+        if started {
+            self.broadcast(game_id, None);
+        }
+        Ok(())
+    }
+
+    fn play(&mut self, client: ClientId, game_id: GameId, seat: usize, action: ActionType) -> Result<(), String> {
+        let table = self.tables.get_mut(&game_id).ok_or("No game with that id.")?;
+        if table.finished {
+            return Err(String::from("The game is over."));
+        }
+        if !table.started() {
+            return Err(String::from("Waiting for a second player."));
+        }
+        if (seat == 0) != table.game.first_player_to_move() {
+            return Err(String::from("It is not your turn."));
+        }
+        if table.draw_offer.is_some() {
+            return Err(String::from("Your draw offer is waiting for an answer."));
+        }
+        let game_move = table.game.apply_action(action).map_err(|e| e.to_string())?;
+        let description = describe_move(&game_move, CoordinateStyle::Numeric);
+        let result = table.game.result();
+        println!("Game {}: {} plays {}", game_id, self.name(client), description);
+        self.broadcast(game_id, Some(&description));
+        if let Some(result) = result {
+            self.conclude(game_id, result, None);
+        }
+        Ok(())
+    }
+
+    /// Puts a draw to the other seat; play stops until they answer.
+    fn offer_draw(&mut self, game_id: GameId, seat: usize) -> Result<(), String> {
+        let table = self.tables.get_mut(&game_id).ok_or("No game with that id.")?;
+        if table.finished {
+            return Err(String::from("The game is over."));
+        }
+        if !table.started() {
+            return Err(String::from("Waiting for a second player."));
+        }
+        if (seat == 0) != table.game.first_player_to_move() {
+            return Err(String::from("Draws are offered on your own turn."));
+        }
+        if table.draw_offer.is_some() {
+            return Err(String::from("A draw offer is already waiting for an answer."));
+        }
+        table.draw_offer = Some(seat);
+        let (by, opponent) = (table.names()[seat].clone(), table.seats[1 - seat].as_ref().map(|(client, _)| *client));
+        println!("Game {}: {} offers a draw.", game_id, by);
+        self.outbox.extend(opponent.map(|client| (client, ServerMessage::DrawOffered { by })));
+        Ok(())
+    }
+
+    fn answer_draw(&mut self, game_id: GameId, seat: usize, accept: bool) -> Result<(), String> {
+        let table = self.tables.get_mut(&game_id).ok_or("No game with that id.")?;
+        if table.finished || table.draw_offer != Some(1 - seat) {
+            return Err(String::from("There is no draw offer to answer."));
+        }
+        table.draw_offer = None;
+        if accept {
+            let result = table.game.agree_draw().map_err(|e| e.to_string())?;
+            self.conclude(game_id, result, Some("draw agreed"));
+        } else {
+            let (by, offerer) = (table.names()[seat].clone(), table.seats[1 - seat].as_ref().map(|(client, _)| *client));
+            println!("Game {}: {} declines the draw.", game_id, by);
+            self.outbox.extend(offerer.map(|client| (client, ServerMessage::DrawDeclined { by })));
+        }
+        Ok(())
+    }
+
+    /// Ends the game at any point in it with a win for the other seat.
+    fn resign(&mut self, game_id: GameId, seat: usize) -> Result<(), String> {
+        let table = self.tables.get_mut(&game_id).ok_or("No game with that id.")?;
+        if table.finished {
+            return Err(String::from("The game is over."));
+        }
+        let PlayerColor::Assigned(first) = table.game.first_player else {
+            return Err(String::from("Nobody has a color to resign yet."));
+        };
+        let result = table.game.resign(if seat == 0 { first } else { opponent(first) }).map_err(|e| e.to_string())?;
+        self.conclude(game_id, result, Some("resignation"));
+        Ok(())
+    }
+
+    /// Rates a finished game and tells everyone at the table how it ended.
+    fn conclude(&mut self, game_id: GameId, result: GameResult, termination: Option<&'static str>) {
+        let winner = match result {
+            GameResult::Winner(player) => Some(color_name(player)),
+            GameResult::Draw(_) => None,
+        };
+        let mut message = describe_ending(result, termination);
+        println!("Game {}: {}", game_id, message);
+        if let Some(ratings) = self.rate(game_id) {
+            println!("Game {}: ratings {}", game_id, ratings);
+            message = format!("{} Ratings: {}", message, ratings);
+        }
+        if let Some(table) = self.tables.get_mut(&game_id) {
+            table.termination = termination;
+        }
+        self.end(game_id, ServerMessage::GameOver { winner, message });
+    }
+
+    /// Rates a finished game if the server keeps ratings and both players gave distinct names.
+    fn rate(&self, game_id: GameId) -> Option<String> {
+        let table = self.tables.get(&game_id)?;
+        let (path, PlayerColor::Assigned(first), names) = (self.profiles.as_ref()?, table.game.first_player, table.names()) else { return None };
+        if names.iter().any(|name| name == GUEST_NAME) || names[0] == names[1] {
+            return None;
+        }
+        let (red, black) = if first == Player::Red { (&names[0], &names[1]) } else { (&names[1], &names[0]) };
+        match rate_game(path, red, black, &table.game) {
+            Ok(ratings) => Some(ratings),
+            Err(e) => {
+                println!("Error: could not update ratings: {}", e);
+                None
+            },
+        }
+    }
+
+    /// Sends each player their view of the position, and queues the spectators' view.
+    fn broadcast(&mut self, game_id: GameId, last_move: Option<&str>) {
+        let Some(table) = self.tables.get_mut(&game_id) else { return };
+        let names = table.names();
+        for (seat, (client, _)) in table.seats.iter().enumerate().filter_map(|(seat, entry)| entry.as_ref().map(|entry| (seat, entry))) {
+            self.outbox.push((*client, state_message(game_id, &table.game, Some(seat), &names, last_move)));
+        }
+        let view = state_message(game_id, &table.game, None, &names, last_move);
+        table.delayed.push_back((Instant::now() + self.spectator_delay, view));
+    }
+
+    fn end(&mut self, game_id: GameId, message: ServerMessage) {
+        let Some(table) = self.tables.get_mut(&game_id) else { return };
+        for (client, _) in table.seats.iter().flatten() {
+            self.outbox.push((*client, message.clone()));
+        }
+        table.delayed.push_back((Instant::now() + self.spectator_delay, message));
+        table.finished = true;
+    }
+
+    fn leave(&mut self, client: ClientId) {
+        let name = self.name(client);
+        match self.role(client) {
+            Some(Role::Player(game_id, seat)) => {
+                let Some(table) = self.tables.get_mut(&game_id) else { return };
+                println!("{} left game {}.", name, game_id);
+                if table.started() && !table.finished {
+                    self.end(game_id, ServerMessage::GameOver { winner: None, message: format!("{} left the game.", name) });
+                } else if !table.started() {
+                    // Nobody else is waiting at a game that never began
+                    table.seats[seat] = None;
+                    if table.seats.iter().all(Option::is_none) {
+                        self.tables.remove(&game_id);
+                    }
+                }
+            },
+            Some(Role::Spectator(game_id)) => println!("{} stopped watching game {}.", name, game_id),
+            Some(Role::Lobby) | None => {},
+        }
+        self.set_role(client, Role::Lobby);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::web::WireAction;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    // Red's rook takes Black's last piece with its first move
+    fn lobby_with_game() -> Lobby {
+        let new_game = || GameState::from_fen("Rp6/8/8/8 r", RuleConfig::default(), &mut StdRng::seed_from_u64(790));
+        let mut lobby = Lobby::new(Box::new(new_game), Duration::ZERO, None);
+        for client in [1, 2] {
+            lobby.connect(client);
+        }
+        lobby.handle(1, ClientMessage::Create { name: None, title: None }).unwrap();
+        lobby.handle(2, ClientMessage::Join { game_id: Some(1), name: None }).unwrap();
+        lobby
+    }
+
+    #[test]
+    fn games_ended_on_the_board_have_no_termination() {
+        let mut lobby = lobby_with_game();
+        let capture = WireAction::Move { from_x: 0, from_y: 0, to_x: 1, to_y: 0 };
+        lobby.handle(1, ClientMessage::Action { action: capture }).unwrap();
+
+        assert!(lobby.tables[&1].finished);
+        assert_eq!(lobby.tables[&1].game.result(), Some(GameResult::Winner(Player::Red)));
+        assert_eq!(lobby.termination(1), None);
+    }
+
+    #[test]
+    fn accepted_draws_are_terminated_by_agreement() {
+        let mut lobby = lobby_with_game();
+        lobby.handle(1, ClientMessage::OfferDraw).unwrap();
+        lobby.handle(2, ClientMessage::AnswerDraw { accept: true }).unwrap();
+
+        assert_eq!(lobby.tables[&1].game.result(), Some(GameResult::Draw(DrawReason::Agreement)));
+        assert_eq!(lobby.termination(1), Some("draw agreed"));
+    }
+}
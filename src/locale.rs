@@ -0,0 +1,339 @@
+use crate::display::*;
+use crate::*;
+use std::fmt::Display;
+
+// Text is written in English at the point of use and looked up here by that English text, so
+// anything without a translation still shows up, in English. Templates mark each value with {}
+// and take the values in order; a translation may not reorder them.
+
+/// A language the game can talk in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Language {
+    #[default]
+    English,
+    TraditionalChinese,
+}
+
+impl Language {
+    /// Reads a language tag such as "en" or "zh-TW", in any case.
+    pub fn parse(tag: &str) -> Option<Self> {
+        match tag.to_lowercase().replace('_', "-").as_str() {
+            "en" | "english" => Some(Language::English),
+            "zh-tw" | "zh-hant" | "zh-hant-tw" => Some(Language::TraditionalChinese),
+            _ => None,
+        }
+    }
+
+    pub fn tag(self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::TraditionalChinese => "zh-TW",
+        }
+    }
+
+    /// The text in this language, or the English text itself if it has no translation.
+    pub fn tr(self, english: &'static str) -> &'static str {
+        match self {
+            Language::English => english,
+            Language::TraditionalChinese => traditional_chinese(english).unwrap_or(english),
+        }
+    }
+
+    /// Translates `template` and fills its {} markers with `args` in order.
+    pub fn format(self, template: &'static str, args: &[&dyn Display]) -> String {
+        let mut parts = self.tr(template).split("{}");
+        let mut out = parts.next().unwrap_or_default().to_string();
+        for (part, arg) in parts.zip(args.iter().map(ToString::to_string).chain(std::iter::repeat(String::new()))) {
+            out.push_str(&arg);
+            out.push_str(part);
+        }
+        out
+    }
+
+    pub fn error(self, error: GameError) -> &'static str {
+        self.tr(error.message())
+    }
+
+    pub fn player_name(self, player: Player) -> &'static str {
+        self.tr(player_name(player))
+    }
+
+    /// The name of one piece of a type, e.g. "Cannon".
+    pub fn piece_name(self, piece_type: PieceType) -> &'static str {
+        self.tr(piece_type_name(piece_type, 1))
+    }
+
+    pub fn describe_result(self, result: GameResult) -> String {
+        match (self, result) {
+            (Language::English, _) => describe_result(result),
+            (_, GameResult::Winner(player)) => self.format("{} wins!", &[&self.player_name(player)]),
+            (_, GameResult::Draw(DrawReason::Elimination)) => self.tr("The game is a draw: both sides were eliminated at once.").to_string(),
+            (_, GameResult::Draw(DrawReason::NoProgress)) => self.tr("The game is a draw: no capture or flip within the move limit.").to_string(),
+            (_, GameResult::Draw(DrawReason::Repetition)) => self.format("The game is a draw: the same position occurred {} times.", &[&REPETITION_LIMIT]),
+            (_, GameResult::Draw(DrawReason::Agreement)) => self.tr("The game is a draw by agreement.").to_string(),
+        }
+    }
+
+    /// The result along with how an off-board ending came about, as `display::describe_ending`.
+    pub fn describe_ending(self, result: GameResult, termination: Option<&str>) -> String {
+        match (self, result, termination) {
+            (Language::English, _, _) => describe_ending(result, termination),
+            (_, GameResult::Winner(winner), Some("resignation")) => {
+                self.format("{} resigns. {}", &[&self.player_name(opponent(winner)), &self.describe_result(result)])
+            },
+            (_, GameResult::Winner(winner), Some("time forfeit")) => {
+                self.format("{} ran out of time. {}", &[&self.player_name(opponent(winner)), &self.describe_result(result)])
+            },
+            _ => self.describe_result(result),
+        }
+    }
+
+    pub fn describe_move(self, game_move: &GameMove, style: CoordinateStyle) -> String {
+        if self == Language::English {
+            return describe_move(game_move, style);
+        }
+        let piece_description = |piece: Option<Piece>| match piece {
+            Some(piece) => self.format("{} {}", &[&self.player_name(piece.player), &self.piece_name(piece.piece_type)]),
+            None => self.tr("Unknown piece").to_string(),
+        };
+
+        match (game_move.action_type, game_move.captured_piece) {
+            (ActionType::Flip { x, y }, _) => self.format("{} flip at {}", &[&piece_description(game_move.piece), &format_square(x, y, style)]),
+            (ActionType::Move { to_x, to_y, .. }, None) if game_move.revealed_own.is_some() => self.format(
+                "{} jump onto {} turns up own {}",
+                &[&piece_description(game_move.piece), &format_square(to_x, to_y, style), &piece_description(game_move.revealed_own)],
+            ),
+            (ActionType::Move { to_x, to_y, .. }, Some(victim)) => self.format(
+                "{} capture of {} at {}",
+                &[&piece_description(game_move.piece), &piece_description(Some(victim)), &format_square(to_x, to_y, style)],
+            ),
+            (ActionType::Move { from_x, from_y, to_x, to_y }, None) => self.format(
+                "{} move from {} to {}",
+                &[&piece_description(game_move.piece), &format_square(from_x, from_y, style), &format_square(to_x, to_y, style)],
+            ),
+        }
+    }
+
+    /// A suggested action, as `display::describe_action`.
+    pub fn describe_action(self, board: &Board, action: ActionType, rules: &RuleConfig, style: CoordinateStyle) -> String {
+        match (self, action) {
+            (Language::English, _) => describe_action(board, action, rules, style),
+            (_, ActionType::Flip { x, y }) => self.format("flip at {}", &[&format_square(x, y, style)]),
+            (_, ActionType::Move { to_x, to_y, .. }) if matches!(board.cell_at(to_x, to_y), Some(Cell::Hidden(_))) => {
+                self.format("cannon jump onto the hidden piece at {}", &[&format_square(to_x, to_y, style)])
+            },
+            (_, ActionType::Move { from_x, from_y, to_x, to_y }) => match check_move(board, from_x, from_y, to_x, to_y, rules) {
+                Ok(game_move) => self.describe_move(&game_move, style),
+                Err(_) => self.format("move from {} to {}", &[&format_square(from_x, from_y, style), &format_square(to_x, to_y, style)]),
+            },
+        }
+    }
+
+    /// The line shown after a capture, as `display::describe_capture`.
+    pub fn describe_capture(self, game_move: &GameMove, symbols: &Symbols, colors: &ColorConfig, style: CoordinateStyle) -> Option<String> {
+        if self == Language::English {
+            return describe_capture(game_move, symbols, colors, style);
+        }
+        let (attacker, victim) = (game_move.piece?, game_move.captured_piece?);
+        let ActionType::Move { from_x, from_y, to_x, to_y } = game_move.action_type else {
+            return None;
+        };
+        let adjacent = from_x.abs_diff(to_x) + from_y.abs_diff(to_y) == 1;
+        let template = if attacker.piece_type == PieceType::Cannon && !adjacent { "{} {} jumped and captured {} {} at {}" } else { "{} {} captured {} {} at {}" };
+        Some(self.format(
+            template,
+            &[
+                &self.player_name(attacker.player),
+                &colored_symbol(attacker, symbols, colors),
+                &self.player_name(victim.player),
+                &colored_symbol(victim, symbols, colors),
+                &format_square(to_x, to_y, style),
+            ],
+        ))
+    }
+
+    /// What one side captured over the game, as `display::capture_summary`.
+    pub fn capture_summary(self, moves_history: &[GameMove], player: Player) -> String {
+        if self == Language::English {
+            return capture_summary(moves_history, player);
+        }
+        let tally = capture_tally(moves_history, player);
+        if tally.is_empty() {
+            return self.format("{} captured: nothing", &[&self.player_name(player)]);
+        }
+        let parts: Vec<String> = tally.iter().map(|&(piece_type, count)| format!("{} {}", count, self.piece_name(piece_type))).collect();
+        self.format("{} captured: {}", &[&self.player_name(player), &parts.join(self.tr(", "))])
+    }
+}
+
+fn traditional_chinese(english: &str) -> Option<&'static str> {
+    Some(match english {
+        // Sides, pieces and rule sets
+        "Red" => "紅方",
+        "Black" => "黑方",
+        "General" => "將",
+        "Advisor" => "士",
+        "Elephant" => "象",
+        "Chariot" => "車",
+        "Horse" => "馬",
+        "Cannon" => "炮",
+        "Soldier" => "兵",
+        "Taiwanese" => "台灣",
+        "Hong Kong" => "香港",
+        ", " => "、",
+
+        // Results and moves
+        "{} wins!" => "{}獲勝！",
+        "The game is a draw: both sides were eliminated at once." => "和棋：雙方同時被吃光。",
+        "The game is a draw: no capture or flip within the move limit." => "和棋：在步數限制內沒有吃子或翻棋。",
+        "The game is a draw: the same position occurred {} times." => "和棋：同一局面已出現 {} 次。",
+        "The game is a draw by agreement." => "雙方同意和棋。",
+        "{} resigns. {}" => "{}認輸。{}",
+        "{} ran out of time. {}" => "{}超時。{}",
+        "{} ran out of time." => "{}超時。",
+        "Unknown piece" => "未知棋子",
+        "{} {}" => "{}{}",
+        "{} flip at {}" => "{}，翻開於 {}",
+        "{} capture of {} at {}" => "{}吃{}，於 {}",
+        "{} jump onto {} turns up own {}" => "{}打 {} 翻出己方{}",
+        "{} move from {} to {}" => "{}從 {} 走到 {}",
+        "cannon jump onto the hidden piece at {}" => "炮打 {} 的暗子",
+        "flip at {}" => "翻開 {}",
+        "move from {} to {}" => "從 {} 走到 {}",
+        "{} {} captured {} {} at {}" => "{} {} 吃掉 {} {}，於 {}",
+        "{} {} jumped and captured {} {} at {}" => "{} {} 隔子吃掉 {} {}，於 {}",
+        "{} captured: nothing" => "{}吃子：無",
+        "{} captured: {}" => "{}吃子：{}",
+
+        // Refused actions and edits
+        "Coordinates out of bounds." => "座標超出棋盤範圍。",
+        "The game is already over." => "對局已經結束。",
+        "The first action must be a flip, which decides the colors." => "第一步必須翻棋，由翻出的棋子決定顏色。",
+        "No moves to undo." => "沒有可以悔的棋。",
+        "No moves to redo." => "沒有可以重走的棋。",
+        "Nobody has a color to resign yet." => "尚未決定顏色，還不能認輸。",
+        "There is no hidden piece to flip there." => "那裡沒有可以翻的暗棋。",
+        "A hidden square has no piece under it." => "有一格暗棋底下沒有棋子。",
+        "No piece to move." => "沒有可以移動的棋子。",
+        "There is no revealed piece on that square." => "那一格沒有已翻開的棋子。",
+        "You can only move your own pieces." => "只能移動自己的棋子。",
+        "Cannot capture your own piece." => "不能吃自己的棋子。",
+        "Cannot move onto a hidden piece." => "不能走到暗棋上。",
+        "Pieces move along a row or column." => "棋子只能沿直線或橫線移動。",
+        "That piece moves only one square at a time." => "這枚棋子一次只能走一格。",
+        "The path is blocked." => "路徑被擋住了。",
+        "A cannon captures by jumping exactly one piece." => "炮吃子時必須正好隔一枚棋子。",
+        "Cannot capture a piece that outranks yours." => "不能吃階級比自己高的棋子。",
+        "Cannot capture this piece." => "不能吃這枚棋子。",
+        "That piece is not among the hidden pieces." => "暗棋中沒有這枚棋子。",
+        "Too many pieces for the board." => "棋子太多，棋盤放不下。",
+        "The piece set does not fill the board." => "這組棋子填不滿棋盤。",
+        "The board must be a non-empty rectangle." => "棋盤必須是非空的矩形。",
+        "That player already has the maximum number of this piece." => "該方的這種棋子已達上限。",
+        "A player has more than one general." => "有一方的將不只一枚。",
+        "No legal actions." => "沒有合法的動作。",
+        "Missing command" => "缺少指令",
+        "Invalid coordinates" => "座標無效",
+        "Expected <row> <col> coordinates." => "需要 <row> <col> 座標。",
+        "Unknown color, use red or black." => "未知的顏色，請用 red 或 black。",
+        "Unknown piece type." => "未知的棋子種類。",
+        "There is no hidden piece on that square." => "那一格沒有暗棋。",
+        "Unknown setup command." => "未知的擺設指令。",
+        "Usage: eval <from_row> <from_col> <to_row> <to_col> or eval flip <row> <col>" => "用法：eval <from_row> <from_col> <to_row> <to_col> 或 eval flip <row> <col>",
+
+        // The game loop
+        "Error: {}" => "錯誤：{}",
+        "Error parsing input: {}" => "無法解讀輸入：{}",
+        "Seed: {} (start with --seed {} to replay this layout)" => "種子：{}（以 --seed {} 開始可重現這個佈局）",
+        "Playing by {} rules." => "採用{}規則。",
+        "Setup cancelled, playing the deal as it was." => "已取消擺設，照原本的發牌開始。",
+        "{} offers a draw." => "{}提議和棋。",
+        "{} left the game." => "{}離開了對局。",
+        "{} plays: {}" => "{}走了：{}",
+        "Error: {} could not play: {}" => "錯誤：{}無法走棋：{}",
+        "the first player" => "先手玩家",
+        "Pass the keyboard to {} - press Enter when ready." => "請把鍵盤交給{}，準備好後按 Enter。",
+        "No colors yet: the first player flips a piece and plays its color." => "尚未決定顏色：先手玩家翻開一枚棋子，並執該棋子的顏色。",
+        "First player" => "先手玩家",
+        "Player {}" => "{}",
+        "{}, enter your action (e.g., 'flip row col', 'move from_row from_col to_row to_col', 'flip c2', 'undo', 'draw', 'resign', or 'exit'):" => {
+            "{}，請輸入動作（例如 'flip row col'、'move from_row from_col to_row to_col'、'flip c2'、'undo'、'draw'、'resign' 或 'exit'）："
+        },
+        "Exiting game." => "離開對局。",
+        "Narration is on." => "已開啟旁白。",
+        "Narration is off." => "已關閉旁白。",
+        "That command is not available in a network game." => "網路對局中不能使用這個指令。",
+        "All pieces flipped for testing (not recorded in history)." => "已翻開所有棋子以供測試（不列入棋譜）。",
+        "Setup complete." => "擺設完成。",
+        "Setup cancelled." => "已取消擺設。",
+        "Hint: any flip will do, the colors aren't decided yet." => "提示：尚未決定顏色，翻哪一枚都可以。",
+        "Hint: {} (evaluation {} for {}, {} positions searched)" => "提示：{}（評估值 {}，以{}計，搜尋了 {} 個局面）",
+        "No legal actions to suggest." => "沒有可以建議的合法動作。",
+        "Usage: perft <depth>" => "用法：perft <depth>",
+        "Estimated from {} playouts:" => "由 {} 局模擬估計：",
+        "{}: wins {}%, draws {}%, loses {}%" => "{}：勝 {}%，和 {}%，負 {}%",
+        "{} of the drawn playouts stopped at the {}-ply limit." => "和局中有 {} 局因達到 {} 步上限而中止。",
+        "Usage: winrate [playouts] [random|captures]" => "用法：winrate [playouts] [random|captures]",
+        "Undid: {}" => "已悔棋：{}",
+        "Redid: {}" => "已重走：{}",
+        "Position loaded." => "已載入局面。",
+        "Game saved to {}." => "對局已儲存至 {}。",
+        "Error: could not write {}: {}" => "錯誤：無法寫入 {}：{}",
+        "Evaluation for {}: {}" => "評估（{}）：{}",
+        "Piece flipped." => "已翻開棋子。",
+        "Piece moved." => "已走子。",
+        "Invalid command or number of coordinates." => "指令或座標數量無效。",
+        "Game length: {} {}. Flips: Red {}, Black {}." => "對局長度：{} {}。翻棋次數：紅方 {}，黑方 {}。",
+        "ply" | "plies" => "步",
+        "Review at depth {}, flagging actions that lose more than {}:" => "以深度 {} 覆盤，標出損失超過 {} 的動作：",
+        "Ply {}, {}: {} loses {}; better was {}." => "第 {} 步，{}：{}損失 {}；較佳的是{}。",
+        "No action lost more than {}." => "沒有動作損失超過 {}。",
+        "{}: accuracy {}% over {} {}, average loss {}, {} {}." => "{}：準確率 {}%（共 {} {}），平均損失 {}，{} {}。",
+        "action" | "actions" => "步",
+        "mistake" | "mistakes" => "次失誤",
+        "Error: could not review the game: {}" => "錯誤：無法覆盤：{}",
+        "Ratings: {}" => "等級分：{}",
+        "Error: could not update ratings: {}" => "錯誤：無法更新等級分：{}",
+        "Error: could not autosave to {}: {}" => "錯誤：無法自動儲存至 {}：{}",
+        "Error: could not add the game to the log in {}: {}" => "錯誤：無法把對局加入 {} 的紀錄：{}",
+        "Stored as game {} in the history." => "已存為歷史紀錄中的第 {} 局。",
+        "Error: could not store the game: {}" => "錯誤：無法儲存對局：{}",
+        "Game over. Thanks for playing!" => "對局結束，感謝遊玩！",
+        "{} accepts the draw." => "{}接受和棋。",
+        "{} declines the draw." => "{}拒絕和棋。",
+        "{}, do you accept the draw? (yes/no)" => "{}，是否接受和棋？(yes/no)",
+        "Draw declined." => "已拒絕和棋。",
+        "The first player" => "先手玩家",
+        "The second player" => "後手玩家",
+        "The first player takes {}, the second player takes {}." => "先手玩家執{}，後手玩家執{}。",
+        "Clock: {} | {}" => "棋鐘：{} | {}",
+        "Please answer yes or no." => "請回答 yes 或 no。",
+        "Setup ({} to move)> " => "擺設（輪到{}）> ",
+        "Help topics: {}\nUse 'help <topic>' for details or 'help all' for everything." => "說明主題：{}\n輸入 'help <topic>' 查看詳情，或 'help all' 查看全部。",
+        "Unknown help topic '{}'. Type 'help' to list topics." => "沒有「{}」這個說明主題。輸入 'help' 列出所有主題。",
+
+        // Matches
+        "Game {} of {}: {} makes the opening flip." => "第 {} 局（共 {} 局）：由{}先翻棋。",
+        "Match abandoned after {} of {} games: {}." => "比賽在第 {} 局（共 {} 局）後中止：{}。",
+        "Match score after game {} of {}: {}." => "第 {} 局（共 {} 局）後的比分：{}。",
+        "{} wins the match, {}." => "{}贏得比賽，{}。",
+        "The match is drawn, {}." => "比賽平手，{}。",
+
+        // Network games
+        "Error: could not join {}: {}" => "錯誤：無法加入 {}：{}",
+        "Joined the game at {}. Waiting for the host to move..." => "已加入 {} 的對局，等待主持方走棋……",
+        "Error: the host sent an invalid position: {}" => "錯誤：主持方傳來無效的局面：{}",
+        "Error: connection lost: {}" => "錯誤：連線中斷：{}",
+        "You left the game." => "你離開了對局。",
+        "Draw offered. Waiting for the host to answer..." => "已提議和棋，等待主持方回覆……",
+        "Waiting for the host..." => "等待主持方……",
+        "The host offers a draw. Do you accept? (yes/no)" => "主持方提議和棋，是否接受？(yes/no)",
+        "The host left the game." => "主持方離開了對局。",
+        "Your turn as {}, enter 'flip row col', 'move from_row from_col to_row to_col', 'draw', 'resign' or 'exit':" => {
+            "輪到你（{}），請輸入 'flip row col'、'move from_row from_col to_row to_col'、'draw'、'resign' 或 'exit'："
+        },
+        "Your turn: flip a piece to take its color with 'flip row col', or 'exit':" => "輪到你：用 'flip row col' 翻開一枚棋子並執其顏色，或輸入 'exit'：",
+        "Only flip and move can be played in a network game." => "網路對局中只能翻棋或走子。",
+        _ => return None,
+    })
+}
@@ -1,620 +1,1631 @@
-use rand::seq::SliceRandom;
-use rand::thread_rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rust_dark_chess::ai::playout::{estimate_winrate, parse_playout_policy, PlayoutPolicy, PLAYOUT_PLY_LIMIT};
+use rust_dark_chess::ai::*;
+use rust_dark_chess::arena::{format_crosstable, parse_engine_spec, ArenaGame, TournamentGame};
+use rust_dark_chess::display::*;
+use rust_dark_chess::history::{GameDatabase, GameFilter, Score};
+use rust_dark_chess::locale::Language;
+use rust_dark_chess::notation::*;
+use rust_dark_chess::profiles::*;
+use rust_dark_chess::review::{review_game, Accuracy, MISTAKE_THRESHOLD};
+use rust_dark_chess::*;
+use rust_dark_chess::clock::*;
 use std::io;
-use std::collections::HashMap;
+use std::time::Duration;
+
+mod cli;
+mod config;
+mod remote;
+mod tui;
+mod ui;
+use clap::Parser;
+use cli::*;
+use config::*;
+use remote::RemotePlayer;
+use tui::TuiRenderer;
+use ui::{confirm, say, tell, Evaluator, LineRenderer, Renderer};
 
-const RED: &str = "\x1b[31m";
-const RESET: &str = "\x1b[0m"; // Resets the color to default
+fn parse_input(input: &str) -> Result<(String, Vec<usize>), &'static str> {
+    let parts: Vec<&str> = input.split_whitespace().collect();
+    let command = parts.first().ok_or("Missing command")?.to_string();
+    let coordinates = parse_coordinates(&parts[1..])?;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-enum PieceType {
-    General,
-    Advisor,
-    Elephant,
-    Chariot,
-    Horse,
-    Cannon,
-    Soldier,
+    Ok((command, coordinates))
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-enum Player {
-    Red,
-    Black,
+fn parse_coordinates(tokens: &[&str]) -> Result<Vec<usize>, &'static str> {
+    // A square is either two numbers "x y" or one algebraic name like "c2"
+    let mut coordinates = Vec::new();
+    for token in tokens {
+        match parse_square_name(token) {
+            Some((x, y)) => coordinates.extend([x, y]),
+            None => coordinates.push(token.parse::<usize>().map_err(|_| "Invalid coordinates")?),
+        }
+    }
+    Ok(coordinates)
 }
 
-#[derive(Debug, Clone, Copy)]
-struct Piece {
-    piece_type: PieceType,
-    player: Player,
+fn parse_action(command: &str, coordinates: &[usize]) -> Option<ActionType> {
+    match (command, coordinates) {
+        ("flip", &[x, y]) => Some(ActionType::Flip { x, y }),
+        ("move", &[from_x, from_y, to_x, to_y]) => Some(ActionType::Move { from_x, from_y, to_x, to_y }),
+        _ => None,
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
-enum Cell {
-    Hidden(Option<Piece>),
-    Revealed(Piece),
-    Empty,
+fn parse_eval_action(input: &str) -> Result<ActionType, &'static str> {
+    // Either "flip <row> <col>" or bare move coordinates
+    let (command, rest) = match input.split_whitespace().next() {
+        Some("flip") => ("flip", &input.trim_start()["flip".len()..]),
+        _ => ("move", input),
+    };
+    let coordinates = parse_coordinates(&rest.split_whitespace().collect::<Vec<_>>())?;
+
+    parse_action(command, &coordinates).ok_or("Usage: eval <from_row> <from_col> <to_row> <to_col> or eval flip <row> <col>")
 }
 
-#[derive(Debug, Clone, Copy)]
-enum ActionType {
-    Flip { x: usize, y: usize },
-    Move { from_x: usize, from_y: usize, to_x: usize, to_y: usize },
+fn apply_setup_command(board: &mut Board, input: &str, composition: &Composition) -> Result<(), &'static str> {
+    let parts: Vec<&str> = input.split_whitespace().collect();
+    let (command, args) = parts.split_first().ok_or("Missing command")?;
+
+    if *command == "clear" && args == ["all"] {
+        for cell in board.iter_mut().flatten() {
+            *cell = Cell::Empty;
+        }
+        return Ok(());
+    }
+
+    // The square takes one argument in algebraic form and two as numbers
+    let square_args = if args.first().is_some_and(|arg| parse_square_name(arg).is_some()) { 1 } else { 2 };
+    let coordinates = parse_coordinates(&args[..square_args.min(args.len())])?;
+    if coordinates.len() != 2 {
+        return Err("Expected <row> <col> coordinates.");
+    }
+    let (x, y) = (coordinates[0], coordinates[1]);
+    let current = *board.get(y).and_then(|row| row.get(x)).ok_or("Coordinates out of bounds.")?;
+
+    match (*command, &args[square_args..]) {
+        ("clear" | "remove", []) => place_cell(board, x, y, Cell::Empty, composition).map_err(GameError::message),
+        ("place" | "hide", [color, piece_name]) => {
+            let player = parse_player(color).ok_or("Unknown color, use red or black.")?;
+            let piece_type = parse_piece_type(piece_name).ok_or("Unknown piece type.")?;
+            let piece = Piece { piece_type, player };
+            place_cell(board, x, y, if *command == "place" { Cell::Revealed(piece) } else { Cell::Hidden(Some(piece)) }, composition).map_err(GameError::message)
+        },
+        ("hide", []) => match current {
+            Cell::Revealed(piece) => place_cell(board, x, y, Cell::Hidden(Some(piece)), composition).map_err(GameError::message),
+            _ => Err("There is no revealed piece on that square."),
+        },
+        ("reveal", []) => match current {
+            Cell::Hidden(Some(piece)) => place_cell(board, x, y, Cell::Revealed(piece), composition).map_err(GameError::message),
+            _ => Err("There is no hidden piece on that square."),
+        },
+        _ => Err("Unknown setup command."),
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
-struct GameMove {
-    action_type: ActionType,
-    piece: Option<Piece>, // Piece that was moved or flipped
-    captured_piece: Option<Piece>, // Piece that was captured, if any
+fn setup_help(language: Language) -> String {
+    match language {
+        Language::English => SETUP_HELP.join("\n"),
+        Language::TraditionalChinese => SETUP_HELP_ZH_TW.join("\n"),
+    }
 }
 
-type Board = Vec<Vec<Cell>>;
+const SETUP_HELP: &[&str] = &[
+    "Setup commands:",
+    "  place <row> <col> <red|black> <piece> - Puts a revealed piece on the square.",
+    "  hide <row> <col> <red|black> <piece>  - Puts a hidden piece on the square.",
+    "  hide <row> <col>                      - Turns the piece on the square face down.",
+    "  reveal <row> <col>                    - Turns the hidden piece on the square face up.",
+    "  remove <row> <col>                    - Empties the square (also 'clear <row> <col>').",
+    "  clear all                             - Empties the whole board.",
+    "  turn <red|black>                      - Sets the side to move once colors are decided.",
+    "  fen                                   - Prints the position, hidden contents included.",
+    "  done                                  - Starts from the edited position.",
+    "  cancel                                - Discards the edits.",
+    "Pieces: general, advisor, elephant, chariot, horse, cannon, soldier.",
+];
+
+const SETUP_HELP_ZH_TW: &[&str] = &[
+    "擺設指令：",
+    "  place <row> <col> <red|black> <piece> - 在該格放一枚翻開的棋子。",
+    "  hide <row> <col> <red|black> <piece>  - 在該格放一枚暗棋。",
+    "  hide <row> <col>                      - 把該格的棋子蓋回去。",
+    "  reveal <row> <col>                    - 翻開該格的暗棋。",
+    "  remove <row> <col>                    - 清空該格（也可用 'clear <row> <col>'）。",
+    "  clear all                             - 清空整個棋盤。",
+    "  turn <red|black>                      - 顏色決定後，設定輪到哪一方。",
+    "  fen                                   - 印出局面，包含暗棋的內容。",
+    "  done                                  - 從編輯後的局面開始。",
+    "  cancel                                - 放棄這次編輯。",
+    "棋子：general（將）、advisor（士）、elephant（象）、chariot（車）、horse（馬）、cannon（炮）、soldier（兵）。",
+];
+
+/// The position editor. Returns the edited game, with an empty history, or `None` if cancelled.
+/// Colors stay undecided while nothing on the board is face up.
+fn run_setup(ui: &mut dyn Renderer, game: &GameState, language: Language) -> Option<GameState> {
+    // Edit a copy so cancelling leaves the game untouched
+    let mut edited = game.board.clone();
+    let mut to_move = game.current_player;
+    ui.message(&setup_help(language));
+
+    loop {
+        ui.show_board(&edited, &[]);
+        let input = ui.read_command(&language.format("Setup ({} to move)> ", &[&language.player_name(to_move)]))?.to_lowercase();
+        let undecided = !edited.iter().flatten().any(|cell| matches!(cell, Cell::Revealed(_)));
+
+        match input.as_str() {
+            "done" => match validate_board(&edited).and_then(|()| check_piece_limits(&edited, &game.rules.composition)) {
+                Ok(()) => {
+                    let mut result = GameState::from_board(edited, to_move, game.rules);
+                    if undecided {
+                        result.first_player = PlayerColor::Unassigned;
+                    }
+                    return Some(result);
+                },
+                Err(e) => tell!(ui, language, "Error: {}", language.error(e)),
+            },
+            "cancel" => return None,
+            "help" => ui.message(&setup_help(language)),
+            "fen" => say!(ui, "{}", export_fen(&edited, (!undecided).then_some(to_move), true)),
+            _ if input.starts_with("turn ") => match parse_player(input["turn ".len()..].trim()) {
+                Some(player) => to_move = player,
+                None => tell!(ui, language, "Error: {}", language.tr("Unknown color, use red or black.")),
+            },
+            _ => {
+                if let Err(e) = apply_setup_command(&mut edited, &input, &game.rules.composition) {
+                    tell!(ui, language, "Error: {}", language.tr(e));
+                }
+            },
+        }
+    }
+}
 
-fn init_board() -> Board {
-    let mut pieces = Vec::new();
+fn run_replay(path: &str, rules: RuleConfig, settings: &Settings) {
+    let (symbols, colors, style) = (settings.symbols(), settings.colors.clone(), settings.style);
+    let record = match std::fs::read_to_string(path).map_err(|e| e.to_string()).and_then(|text| parse_record(&text).map_err(str::to_string)) {
+        Ok(record) => record,
+        Err(e) => {
+            println!("Error: could not load {}: {}", path, e);
+            return;
+        },
+    };
+    let (mut game, clock) = match record.start_position(rules).and_then(|game| Ok((game, record.clock()?))) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            println!("Error: {}", e);
+            return;
+        },
+    };
 
-    // Populate the vector with two sets of pieces, one for each player
-    for &player in &[Player::Red, Player::Black] {
-        pieces.push(Piece { piece_type: PieceType::General, player });
-        pieces.extend((0..2).map(|_| Piece { piece_type: PieceType::Advisor, player }));
-        pieces.extend((0..2).map(|_| Piece { piece_type: PieceType::Elephant, player }));
-        pieces.extend((0..2).map(|_| Piece { piece_type: PieceType::Chariot, player }));
-        pieces.extend((0..2).map(|_| Piece { piece_type: PieceType::Horse, player }));
-        pieces.extend((0..2).map(|_| Piece { piece_type: PieceType::Cannon, player }));
-        pieces.extend((0..5).map(|_| Piece { piece_type: PieceType::Soldier, player }));
+    // The setup lists every hidden piece, so leave it out to keep the flips a surprise
+    for (name, value) in record.headers.iter().filter(|(name, _)| name != "Setup") {
+        println!("{}: {}", name, value);
     }
-    
-    let mut rng = thread_rng();
-    pieces.shuffle(&mut rng);
+    println!("Replay commands: n (next), p (previous), goto <ply>, q (quit).");
+
+    let total = record.actions.len();
+    loop {
+        print!("{}", render_board(&game.board, &symbols, &colors, style, settings.charset));
+        match game.history.last() {
+            Some(last) => println!("Ply {}/{}: {}", game.history.len(), total, describe_move(last, style)),
+            None => println!("Ply 0/{}: starting position", total),
+        }
+        if game.history.len() == total {
+            println!("End of game, result {}.", record.result);
+            if let Some(clock) = &clock {
+                println!("{}", clock_line(clock));
+            }
+        }
 
-    // Initialize the board with hidden cells containing the pieces
-    pieces
-        .chunks(8)
-        .map(|row| {
-            row.iter()
-                .map(|&piece| Cell::Hidden(Some(piece)))
-                .collect::<Vec<Cell>>()
-        })
-        .collect::<Vec<_>>()
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input).expect("Failed to read line") == 0 {
+            break;
+        }
+        let input = input.trim().to_lowercase();
+
+        // Stepping forward replays the recorded action, stepping back undoes it
+        let target = match input.as_str() {
+            "q" | "quit" | "exit" => break,
+            "n" | "next" | "" => game.history.len() + 1,
+            "p" | "prev" | "previous" => game.history.len().saturating_sub(1),
+            other => match other.strip_prefix("goto ").map(|ply| ply.trim().parse::<usize>()) {
+                Some(Ok(ply)) => ply,
+                _ => {
+                    println!("Unknown replay command.");
+                    continue;
+                },
+            },
+        };
+        if target > total {
+            println!("The record has only {} plies.", total);
+            continue;
+        }
+        while game.history.len() > target && game.undo().is_ok() {}
+        while game.history.len() < target {
+            if let Err(e) = game.apply_action(record.actions[game.history.len()]) {
+                println!("Error: ply {} of the record cannot be played: {}", game.history.len() + 1, e);
+                break;
+            }
+        }
+    }
 }
 
-fn init_board_testing() -> Board {
-    // Create a 4x8 board initialized with Empty cells
-    let mut board = vec![vec![Cell::Empty; 8]; 4];
+// Playouts the winrate command runs without a count
+const WINRATE_PLAYOUTS: usize = 1000;
+
+// Help text keyed by topic: (topic, heading, lines)
+const HELP_TOPICS: &[(&str, &str, &[&str])] = &[
+    ("commands", "Available commands:", &[
+        "  flip <row> <col>        - Flips a hidden piece at the specified coordinates.",
+        "  move <from_row> <from_col> <to_row> <to_col> - Moves a piece from the starting coordinates to the destination coordinates.",
+        "  undo                    - Undo the last move; against the bot, its reply and your move together.",
+        "  redo                    - Replays the last undone move (and the bot's reply). Making a new move discards undone moves.",
+        "  state                   - Prints the current game state in a simple text format.",
+        "  narrate on|off          - Describes the board and every move in words, for screen readers.",
+        "  history                 - Prints the move history.",
+        "  summary                 - Prints a one-line summary of ply, side to move, material, captures and hidden pieces.",
+        "  remaining               - Counts each side's pieces still in the game, and how many are still hidden.",
+        "  odds                    - Shows the chance of each piece type turning up on the next flip.",
+        "  draw                    - Offers a draw, which your opponent accepts or declines.",
+        "  resign                  - Gives up the game; your opponent wins.",
+        "  threats                 - Lists your revealed pieces that the opponent could capture next turn.",
+        "  eval <from_row> <from_col> <to_row> <to_col> - Scores a candidate move without playing it.",
+        "  eval flip <row> <col>   - Scores a candidate flip, averaged over the pieces it could reveal.",
+        "  perft <depth>           - Counts the action sequences up to the given depth, each distinct flip outcome counted separately.",
+        "  moves <row> <col>       - Lists the legal destinations of the piece on that square and marks them on the next board.",
+        "  hint                    - Suggests an action for you, with the engine's evaluation, without playing it.",
+        "  winrate [n] [random|captures] - Plays the position out n times (1000) over fresh deals of the hidden pieces and shows each side's chances.",
+        "  inspect <row> <col>     - Describes a square: its piece, rank, and legal moves and captures.",
+        "  setup                   - Opens the position editor to place, hide, reveal and remove pieces.",
+        "  fen                     - Prints the position in FEN-like notation, with hidden squares shown as '?'.",
+        "  fen full                - Prints the position including the contents of hidden squares, for trusted saves.",
+        "  position <fen>          - Loads a position; hidden squares written as '?' are dealt from the unused pieces.",
+        "  export <file>           - Saves the game so far as a PGN-style record.",
+        "  help [topic]            - Lists help topics, or prints the given topic ('help all' prints everything).",
+        "  exit                    - Exits the game.",
+        "  flip all                - (For Testing) Flips all hidden pieces on the board.",
+        "  Any <row> <col> pair may also be typed as one algebraic square, e.g. 'flip c2' or 'move a1 a3'.",
+    ]),
+    ("rules", "Gameplay Instructions:", &[
+        "  1. The game starts with all pieces hidden. Players take turns to either flip or move pieces.",
+        "     The first flip decides the colors: the first player plays the color of the piece they reveal.",
+        "  2. Players may only move pieces of their own color; any hidden piece may be flipped.",
+        "  3. Pieces can only move to adjacent squares unless specified by their type (e.g., Chariots and Cannons have special movement rules).",
+        "  4. To capture an opponent's piece, move your piece to the same square. Capturing rules vary by piece type.",
+        "  5. The winning condition is when the opponent has no legal moves left.",
+        "  6. The game is drawn when the same position occurs three times, or, with --no-progress-limit, after that many plies without a capture or flip.",
+        "  Note: The game supports two players: Red and Black. Players must alternate turns, and the opening action must be a flip.",
+    ]),
+    ("capture", "Piece Capture Order:", &[
+        "  - Pieces capture pieces of the same or lower rank, except the Soldier-General interaction and the Cannon's jump.",
+        "  - The capture order from highest to lowest is General (將/帥) > Advisor (士/仕) > Elephant (象/相) > Chariot (車/俥) > Horse (馬/傌) > Cannon (砲/炮) > Soldier (卒/兵).",
+        "  - With --variant hong-kong, rank alone decides: the General takes Soldiers, Soldiers can't take the General,",
+        "    and the Cannon moves one square and captures adjacent pieces of the same or lower rank.",
+    ]),
+    ("general", "General (將/帥):", &[
+        "  - Moves one square vertically or horizontally.",
+        "  - Can be captured by Soldiers (卒/兵) but cannot capture Soldiers directly.",
+    ]),
+    ("advisor", "Advisor (士/仕):", &[
+        "  - Moves one square vertically or horizontally.",
+        "  - Captures pieces of the same or lower rank.",
+    ]),
+    ("elephant", "Elephant (象/相):", &[
+        "  - Moves one square vertically or horizontally.",
+        "  - Captures pieces of the same or lower rank.",
+    ]),
+    ("chariot", "Chariot (車/俥):", &[
+        "  - Moves and captures in any number of unblocked squares vertically or horizontally.",
+        "  - Captures pieces of the same or lower rank.",
+    ]),
+    ("horse", "Horse (馬/傌):", &[
+        "  - Moves one square vertically or horizontally.",
+        "  - Captures pieces of the same or lower rank.",
+    ]),
+    ("cannon", "Cannon (砲/炮):", &[
+        "  - Moves like the Chariot through any number of empty squares, or only one square with --cannon-single-step.",
+        "  - Can capture any piece by jumping over exactly one piece (of any type) along its movement path.",
+    ]),
+    ("soldier", "Soldier (卒/兵):", &[
+        "  - Moves one square vertically or horizontally.",
+        "  - Can capture the General and pieces of the same or lower rank.",
+    ]),
+];
+
+// The same topics in Traditional Chinese; topic names stay as typed
+const HELP_TOPICS_ZH_TW: &[(&str, &str, &[&str])] = &[
+    ("commands", "可用指令：", &[
+        "  flip <row> <col>        - 翻開指定座標的暗棋。",
+        "  move <from_row> <from_col> <to_row> <to_col> - 把棋子從起點座標走到終點座標。",
+        "  undo                    - 悔一步棋；對電腦時連同電腦的應著一起悔回。",
+        "  redo                    - 重走剛才悔掉的棋（以及電腦的應著）。走新的一步會捨棄悔掉的棋。",
+        "  state                   - 以簡單的文字格式印出目前局面。",
+        "  narrate on|off          - 以文字描述棋盤與每一步，供螢幕閱讀器使用。",
+        "  history                 - 印出棋譜。",
+        "  summary                 - 以一行列出步數、輪到哪方、子力、吃子與暗棋數量。",
+        "  remaining               - 計算雙方還在場上的棋子，以及其中幾枚仍是暗棋。",
+        "  odds                    - 顯示下一次翻棋翻出各種棋子的機率。",
+        "  draw                    - 提議和棋，由對手決定是否接受。",
+        "  resign                  - 認輸，對手獲勝。",
+        "  threats                 - 列出下一手可能被對手吃掉的己方明棋。",
+        "  eval <from_row> <from_col> <to_row> <to_col> - 評估一步候選走法，但不實際走。",
+        "  eval flip <row> <col>   - 評估一次候選翻棋，取可能翻出的棋子的平均。",
+        "  perft <depth>           - 計算到指定深度的動作序列數，每種翻棋結果分開計算。",
+        "  moves <row> <col>       - 列出該格棋子的合法落點，並標示在下一次顯示的棋盤上。",
+        "  hint                    - 由引擎建議一個動作並附上評估，但不實際走。",
+        "  winrate [n] [random|captures] - 以隨機重發的暗棋把局面下完 n 次（預設 1000），顯示雙方勝率。",
+        "  inspect <row> <col>     - 描述一格：上面的棋子、階級，以及合法的走法與吃法。",
+        "  setup                   - 開啟局面編輯器，可放置、蓋回、翻開與移除棋子。",
+        "  fen                     - 以類 FEN 記法印出局面，暗棋以 '?' 表示。",
+        "  fen full                - 印出包含暗棋內容的局面，供可信任的存檔使用。",
+        "  position <fen>          - 載入局面；寫成 '?' 的暗棋會從未使用的棋子中發出。",
+        "  export <file>           - 把目前的對局存成 PGN 風格的棋譜。",
+        "  help [topic]            - 列出說明主題，或印出指定主題（'help all' 印出全部）。",
+        "  exit                    - 離開對局。",
+        "  flip all                - （測試用）翻開棋盤上所有暗棋。",
+        "  任何 <row> <col> 座標也可以寫成一個代數格名，例如 'flip c2' 或 'move a1 a3'。",
+    ]),
+    ("rules", "遊戲規則：", &[
+        "  1. 開局時所有棋子都蓋著。雙方輪流翻棋或走子。",
+        "     第一次翻棋決定顏色：先手玩家執所翻出棋子的顏色。",
+        "  2. 只能移動自己顏色的棋子；任何暗棋都可以翻。",
+        "  3. 棋子只能走到相鄰的格子，除非該兵種另有規定（例如車和炮有特殊走法）。",
+        "  4. 走到對方棋子所在的格子即可吃掉它。各兵種的吃子規則不同。",
+        "  5. 對手沒有合法動作時即獲勝。",
+        "  6. 同一局面出現三次即為和棋；使用 --no-progress-limit 時，超過該步數沒有吃子或翻棋也算和棋。",
+        "  注意：本遊戲由紅黑兩方對弈，雙方必須輪流行動，且第一步必須翻棋。",
+    ]),
+    ("capture", "吃子順序：", &[
+        "  - 棋子可以吃同階或較低階的棋子，兵與將之間的關係和炮的隔子吃除外。",
+        "  - 階級由高到低為 將/帥 > 士/仕 > 象/相 > 車/俥 > 馬/傌 > 砲/炮 > 卒/兵。",
+        "  - 使用 --variant hong-kong 時只看階級：將可以吃兵，兵不能吃將，",
+        "    而炮只走一格，並吃相鄰的同階或較低階棋子。",
+    ]),
+    ("general", "將/帥：", &[
+        "  - 直向或橫向走一格。",
+        "  - 可以被卒/兵吃掉，但不能直接吃卒/兵。",
+    ]),
+    ("advisor", "士/仕：", &[
+        "  - 直向或橫向走一格。",
+        "  - 吃同階或較低階的棋子。",
+    ]),
+    ("elephant", "象/相：", &[
+        "  - 直向或橫向走一格。",
+        "  - 吃同階或較低階的棋子。",
+    ]),
+    ("chariot", "車/俥：", &[
+        "  - 直向或橫向走任意格數，途中不能有阻擋，吃子亦同。",
+        "  - 吃同階或較低階的棋子。",
+    ]),
+    ("horse", "馬/傌：", &[
+        "  - 直向或橫向走一格。",
+        "  - 吃同階或較低階的棋子。",
+    ]),
+    ("cannon", "砲/炮：", &[
+        "  - 像車一樣穿過任意數量的空格移動；使用 --cannon-single-step 時只走一格。",
+        "  - 沿移動路線正好隔一枚棋子（任何兵種皆可）即可吃任何棋子。",
+    ]),
+    ("soldier", "卒/兵：", &[
+        "  - 直向或橫向走一格。",
+        "  - 可以吃將，以及同階或較低階的棋子。",
+    ]),
+];
+
+fn help_text(topic: Option<&str>, language: Language) -> Result<String, String> {
+    let topics = match language {
+        Language::English => HELP_TOPICS,
+        Language::TraditionalChinese => HELP_TOPICS_ZH_TW,
+    };
+    let format_topic = |&(_, heading, lines): &(&str, &str, &[&str])| format!("{}\n{}", heading, lines.join("\n"));
 
-    // Setup for testing cannon and chariot movements and captures
-    // - Cannons positioned to test jumping and capturing
-    // - Chariots positioned to test movement and capturing
-    // - Various pieces to act as targets or obstacles
+    match topic {
+        None => {
+            let names: Vec<&str> = topics.iter().map(|&(name, _, _)| name).collect();
+            Ok(language.format("Help topics: {}\nUse 'help <topic>' for details or 'help all' for everything.", &[&names.join(", ")]))
+        },
+        Some("all") => Ok(topics.iter().map(format_topic).collect::<Vec<_>>().join("\n\n")),
+        Some(name) => topics
+            .iter()
+            .find(|&&(topic_name, _, _)| topic_name == name)
+            .map(format_topic)
+            .ok_or_else(|| language.format("Unknown help topic '{}'. Type 'help' to list topics.", &[&name])),
+    }
+}
 
-    // Placing cannons for Red and Black
-    board[3][1] = Cell::Revealed(Piece { piece_type: PieceType::Cannon, player: Player::Red }); // Bottom row, 2nd col
-    board[0][1] = Cell::Revealed(Piece { piece_type: PieceType::Cannon, player: Player::Black }); // Top row, 2nd col
+fn main() {
+    let cli = Cli::parse();
+    let rules = cli.rules.rules();
+
+    // Flags override the config file, which overrides the built-in defaults
+    let settings = match load_config(cli.config.as_ref()).and_then(|(config, file)| Settings::resolve(&cli.display, &config, file)) {
+        Ok(settings) => settings,
+        Err(e) => {
+            println!("Error: {}", e);
+            return;
+        },
+    };
 
-    // Placing chariots for Red and Black
-    board[3][0] = Cell::Revealed(Piece { piece_type: PieceType::Chariot, player: Player::Red }); // Bottom row, 1st col
-    board[0][0] = Cell::Revealed(Piece { piece_type: PieceType::Chariot, player: Player::Black }); // Top row, 1st col
+    match &cli.command {
+        None => play_at_keyboard(&cli.play, cli.play.vs_ai, rules, &settings),
+        Some(Command::Play(args)) => play_at_keyboard(args, args.vs_ai, rules, &settings),
+        Some(Command::Ai(args)) => play_at_keyboard(args, true, rules, &settings),
+        Some(Command::Serve { play, .. }) if play.match_length.is_some() => println!("Error: --match is only available for games at the keyboard"),
+        Some(Command::Serve { play, .. }) if play.edit => println!("Error: --edit is only available for games at the keyboard"),
+        Some(Command::Serve { port, play }) => match remote::accept(*port, settings.style) {
+            Ok(player) => {
+                run_game(play, Opponent::Remote(player), rules, &settings);
+            },
+            Err(e) => println!("Error: could not host on port {}: {}", port, e),
+        },
+        Some(Command::Connect { address, tui }) => remote::run_client(address, make_renderer(*tui, rules, &settings).as_mut(), settings.language),
+        Some(Command::ServeWs { port, spectator_delay, setup }) => run_serve_ws(*port, Duration::from_secs(*spectator_delay), setup, rules, &settings),
+        Some(Command::Replay { file }) => run_replay(file, rules, &settings),
+        Some(Command::Analyze(args)) => run_analyze(args, rules, &settings),
+        Some(Command::Perft(args)) => run_perft(args, rules),
+        Some(Command::Selfplay(args)) => run_selfplay(args, rules),
+        Some(Command::Tournament(args)) => run_tournament(args, rules),
+        Some(Command::Engine) => rust_dark_chess::engine::run_engine(rules, rand::thread_rng().gen()),
+        Some(Command::Config) => println!("{}", settings.describe()),
+        Some(Command::Ratings) => match settings.profiles.as_deref().map_or(Ok(Profiles::default()), Profiles::load) {
+            Ok(profiles) => println!("{}", format_ratings(&profiles)),
+            Err(e) => println!("Error: {}", e),
+        },
+        Some(Command::Stats { name }) => match settings.profiles.as_deref().map_or(Ok(Profiles::default()), Profiles::load) {
+            Ok(profiles) => println!("{}", stats_report(&profiles, name.as_deref())),
+            Err(e) => println!("Error: {}", e),
+        },
+        Some(Command::History { command }) => match settings.database.as_deref().ok_or_else(|| String::from("no game database: set database in the config file")).and_then(GameDatabase::open) {
+            Ok(database) => run_history(command, &database),
+            Err(e) => println!("Error: {}", e),
+        },
+    }
+}
 
-    // Placing obstacles for cannons to jump over and targets for chariots
-    board[2][1] = Cell::Revealed(Piece { piece_type: PieceType::Soldier, player: Player::Black }); // Cannon jump target
-    board[1][0] = Cell::Revealed(Piece { piece_type: PieceType::Soldier, player: Player::Red }); // Chariot capture target
+fn play_at_keyboard(args: &PlayArgs, bot: bool, rules: RuleConfig, settings: &Settings) {
+    match args.match_length {
+        Some(games) => run_match(args, bot, games, rules, settings),
+        None => {
+            run_game(args, Opponent::at_keyboard(bot), rules, settings);
+        },
+    }
+}
 
-    board
+fn run_serve_ws(port: u16, spectator_delay: Duration, setup: &SetupArgs, rules: RuleConfig, settings: &Settings) {
+    // Every game is dealt from one seeded sequence, so a seed replays the whole session
+    let seed = setup.seed.unwrap_or_else(|| rand::thread_rng().gen());
+    println!("Seed: {}", seed);
+    if let Err(e) = start_position(setup, rules, &mut StdRng::seed_from_u64(seed)) {
+        println!("Error: {}", e);
+        return;
+    }
+    let setup = setup.clone();
+    let mut rng = StdRng::seed_from_u64(seed);
+    if let Err(e) = rust_dark_chess::web::run_ws_server(port, move || start_position(&setup, rules, &mut rng), spectator_delay, settings.profiles.clone()) {
+        println!("Error: {}", e);
+    }
 }
 
-fn flip_piece(board: &mut Board, x: usize, y: usize) -> Result<Option<GameMove>, &'static str> {
-    if y >= board.len() || x >= board[0].len() {
-        return Err("Coordinates out of bounds.");
-    }
-    
-    match board[y][x] {
-        Cell::Hidden(piece_option) => {
-            if let Some(piece) = piece_option {
-                board[y][x] = Cell::Revealed(piece);
-                let game_move = GameMove {
-                    action_type: ActionType::Flip { x, y },
-                    piece: Some(piece),
-                    captured_piece: None, // No piece is captured during a flip
-                };
-                Ok(Some(game_move))
-            } else {
-                Err("No piece to flip here.")
+fn run_history(command: &HistoryCommand, database: &GameDatabase) {
+    match command {
+        HistoryCommand::List(args) => {
+            let filter = GameFilter {
+                player: args.player.clone(),
+                opponent: args.opponent.clone(),
+                result: args.result.map(|result| result.token().to_string()),
+                since: args.since.clone(),
+                until: args.until.clone(),
+            };
+            match database.games(&filter) {
+                Ok(games) if games.is_empty() => println!("No games found."),
+                Ok(games) => {
+                    for game in &games {
+                        let termination = game.termination.as_ref().map_or(String::new(), |termination| format!(", {}", termination));
+                        let plies = if game.plies == 1 { "ply" } else { "plies" };
+                        println!("{:>4}  {}  {} - {}  {}  ({} {}{})", game.id, game.date, game.red, game.black, game.result, game.plies, plies, termination);
+                    }
+                    let score = Score::tally(&games, args.player.as_deref());
+                    let count = format!("{} {}", games.len(), if games.len() == 1 { "game" } else { "games" });
+                    match &args.player {
+                        Some(name) => println!("{}: {} won, {} drawn, {} lost in {}", name, score.wins, score.draws, score.losses, count),
+                        None => println!("{}: Red won {}, Black won {}, {} drawn", count, score.wins, score.losses, score.draws),
+                    }
+                },
+                Err(e) => println!("Error: {}", e),
             }
         },
-        _ => Err("Invalid flip action."),
-    }
-}
-
-fn can_capture(attacker: Piece, defender: Piece) -> bool {
-    use PieceType::*;
-
-    match (attacker.piece_type, defender.piece_type) {
-        // Handle the special case where Soldiers can capture Generals but not the other way around
-        (Soldier, General) => true,
-        (General, Soldier) => false,
-
-        // Each piece captures pieces of the same type or lower rank, except for the special Soldier-General interaction
-        // General is the highest rank and Soldier the lowest, with the order being General > Advisor > Elephant > Chariot > Horse > Cannon > Soldier
-        // All pieces can capture lower-ranked pieces, except for the Soldier-General interaction
-        // Cannon can capture every piece in the cannon capture jump but otherwise it can't attack anything
-        // General rule: A piece can capture another piece of the same type or any type below it in the following order
-        // For other cases, use a predefined order of power to determine capture ability
-        _ => {
-            let order = |piece_type: PieceType| -> i32 {
-                match piece_type {
-                    General => 7,
-                    Advisor => 6,
-                    Elephant => 5,
-                    Chariot => 4, // Note: Chariot moves any number of spaces in a straight line, handled separately
-                    Horse => 3,
-                    Cannon => 2, // Note: Cannon's capturing rule needs board state, handled separately
-                    Soldier => 1,
-                }
-            };
-
-            // A piece can capture another piece of the same type or any type below it in the hierarchy
-            order(attacker.piece_type) >= order(defender.piece_type)
-        }
+        HistoryCommand::Show { id } => match database.game(*id) {
+            Ok(Some(game)) => print!("{}", game.record),
+            Ok(None) => println!("Error: there is no game {} in the history", id),
+            Err(e) => println!("Error: {}", e),
+        },
     }
 }
 
-fn is_valid_cannon_capture(board: &Board, from_x: usize, from_y: usize, to_x: usize, to_y: usize) -> bool {
-    // Ensure movement is in a straight line and the target is a revealed piece
-    if from_x != to_x && from_y != to_y || matches!(board[to_y][to_x], Cell::Empty | Cell::Hidden(_)) {
-        return false;
+fn stats_report(profiles: &Profiles, name: Option<&str>) -> String {
+    match name {
+        Some(name) => match profiles.players.get(name) {
+            Some(profile) => format_stats(name, profile),
+            None => format!("No statistics for {}.", name),
+        },
+        None if profiles.players.is_empty() => String::from("No rated games yet. Name the players with --player to track their statistics."),
+        None => profiles.ranked().into_iter().map(|(name, profile)| format_stats(name, profile)).collect::<Vec<_>>().join("\n\n"),
     }
+}
 
-    let mut obstacles_encountered = 0;
+fn start_position(setup: &SetupArgs, rules: RuleConfig, rng: &mut StdRng) -> Result<GameState, GameError> {
+    // Colors are decided by the first flip unless the position says whose turn it is
+    let mut game = match setup.fen.as_ref().or(setup.position_file.as_ref()) {
+        Some(fen) => GameState::from_fen(fen, rules, rng)?,
+        None => GameState::with_rng(rules, rng)?,
+    };
+    debug_assert!(validate_board(&game.board).is_ok(), "Initial board must be valid.");
+    if setup.reveal > 0 {
+        reveal_random_pieces(&mut game.board, setup.reveal, rng);
+        game.position_hashes = vec![position_hash(&game.board, game.current_player)];
+    }
+    Ok(game)
+}
 
-    // Count obstacles in the path
-    if from_x == to_x { // Vertical movement
-        for y in (std::cmp::min(from_y, to_y) + 1)..std::cmp::max(from_y, to_y) {
-            if !matches!(board[y][from_x], Cell::Empty) {
-                obstacles_encountered += 1;
-            }
+fn run_analyze(args: &AnalyzeArgs, rules: RuleConfig, settings: &Settings) {
+    let mut rng = StdRng::seed_from_u64(args.seed.unwrap_or_else(|| rand::thread_rng().gen()));
+    // A saved game brings its clock along, as it stood when the game was saved
+    let loaded = match (&args.position, &args.record) {
+        (_, Some(path)) => std::fs::read_to_string(path)
+            .map_err(|e| format!("could not read {}: {}", path, e))
+            .and_then(|text| parse_record(&text).and_then(|record| Ok((record.to_game(rules)?, record.clock()?))).map_err(str::to_string)),
+        (Some(fen), None) => GameState::from_fen(fen, rules, &mut rng).map(|game| (game, None)).map_err(|e| e.to_string()),
+        (None, None) if args.edit => Ok((GameState::from_board(vec![vec![Cell::Empty; BOARD_WIDTH]; BOARD_HEIGHT], Player::Red, rules), None)),
+        (None, None) => Err(String::from("give a position or --record <file> to analyze")),
+    };
+    let (mut game, clock) = match loaded {
+        Ok(game) => game,
+        Err(e) => {
+            println!("Error: {}", e);
+            return;
+        },
+    };
+    if args.edit {
+        match run_setup(make_renderer(false, rules, settings).as_mut(), &game, settings.language) {
+            Some(edited) => game = edited,
+            None => {
+                println!("Setup cancelled.");
+                return;
+            },
         }
-    } else { // Horizontal movement
-        for x in (std::cmp::min(from_x, to_x) + 1)..std::cmp::max(from_x, to_x) {
-            if !matches!(board[from_y][x], Cell::Empty) {
-                obstacles_encountered += 1;
-            }
+    }
+
+    let (symbols, colors, style) = (settings.symbols(), settings.colors.clone(), settings.style);
+    print!("{}", render_board(&game.board, &symbols, &colors, style, settings.charset));
+    println!("{}", summary_line(&game.board, &game.history, game.current_player));
+    if let Some(clock) = &clock {
+        println!("{}", clock_line(clock));
+    }
+    if args.review {
+        match review_report(&game, args.depth, args.threshold, settings.language, style) {
+            Ok(lines) => lines.iter().for_each(|line| println!("{}", line)),
+            Err(e) => println!("Error: {}", e),
         }
+        return;
+    }
+    if let Some(result) = game.result() {
+        println!("{}", describe_result(result));
+        return;
     }
+    let settings = AiSettings::for_difficulty(Difficulty::Hard);
+    match best_action(&game, args.depth, settings.flip_policy, &mut rng) {
+        Some(_) if game.colors_unassigned() => println!("Best action: any flip, the colors aren't decided yet."),
+        Some(suggestion) => println!(
+            "Best action: {} (evaluation {:+.1} for {}, depth {}, {} positions searched)",
+            describe_action(&game.board, suggestion.action, &game.rules, style),
+            suggestion.value,
+            player_name(game.current_player),
+            args.depth,
+            suggestion.nodes
+        ),
+        None => println!("No legal actions."),
+    }
+}
 
-    // Valid if exactly one obstacle is jumped over, regardless of its allegiance
-    obstacles_encountered == 1 && matches!(board[to_y][to_x], Cell::Revealed(_))
+fn run_perft(args: &PerftArgs, rules: RuleConfig) {
+    let mut rng = StdRng::seed_from_u64(args.seed.unwrap_or_else(|| rand::thread_rng().gen()));
+    let setup = SetupArgs { seed: args.seed, reveal: 0, fen: args.fen.clone(), position_file: args.position_file.clone() };
+    let game = match start_position(&setup, rules, &mut rng) {
+        Ok(game) => game,
+        Err(e) => {
+            println!("Error: {}", e);
+            return;
+        },
+    };
+    for ply in 1..=args.depth {
+        let started = std::time::Instant::now();
+        println!("perft {}: {} ({:.2?})", ply, perft(&game, ply), started.elapsed());
+    }
 }
 
-fn is_valid_chariot_move_or_capture(board: &Board, from_x: usize, from_y: usize, to_x: usize, to_y: usize) -> bool {
-    if from_x != to_x && from_y != to_y {
-        return false; // Chariots must move straight.
+fn run_selfplay(args: &SelfplayArgs, rules: RuleConfig) {
+    let engines = match (parse_engine_spec(&args.engines[0]), parse_engine_spec(&args.engines[1])) {
+        (Ok(first), Ok(second)) => [first, second],
+        (Err(e), _) | (_, Err(e)) => {
+            println!("Error: {}", e);
+            return;
+        },
+    };
+    let names = [args.engines[0].as_str(), args.engines[1].as_str()];
+    let seed = args.seed.unwrap_or_else(|| rand::thread_rng().gen());
+    println!("{} vs {}, {} games from seed {}", names[0], names[1], args.games, seed);
+
+    let report = |number: usize, played: &ArenaGame, swapped: bool| {
+        let opener = usize::from(swapped);
+        let outcome = match played.points {
+            Some([first, _]) if first > 0.5 => format!("{} wins", names[opener]),
+            Some([first, _]) if first < 0.5 => format!("{} wins", names[1 - opener]),
+            Some(_) => String::from("draw"),
+            None => String::from("stopped at the ply limit"),
+        };
+        println!("Game {}: {} opens, {} in {} plies", number, names[opener], outcome, played.plies);
+    };
+    match rust_dark_chess::arena::run_selfplay(engines, args.games, seed, rules, args.max_plies, report) {
+        Ok(tally) => {
+            println!(
+                "{}: {} won, {} drawn, {} lost against {} (score {}/{})",
+                names[0], tally.wins, tally.draws, tally.losses, names[1], tally.score(), tally.games
+            );
+            println!("Average game length: {:.1} plies", tally.average_length());
+            if tally.unfinished > 0 {
+                println!("{} of the draws stopped at the {}-ply limit.", tally.unfinished, args.max_plies);
+            }
+        },
+        Err(e) => println!("Error: {}", e),
     }
+}
 
-    let path_clear = if from_x == to_x {
-        // Check vertical path
-        (std::cmp::min(from_y, to_y) + 1..std::cmp::max(from_y, to_y)).all(|y| matches!(board[y][from_x], Cell::Empty))
-    } else {
-        // Check horizontal path
-        (std::cmp::min(from_x, to_x) + 1..std::cmp::max(from_x, to_x)).all(|x| matches!(board[from_y][x], Cell::Empty))
+fn run_tournament(args: &TournamentArgs, rules: RuleConfig) {
+    let engines: Vec<AiSettings> = match args.engines.iter().map(|spec| parse_engine_spec(spec)).collect() {
+        Ok(engines) => engines,
+        Err(e) => {
+            println!("Error: {}", e);
+            return;
+        },
+    };
+    // The same engine may be entered twice, so each name carries its place in the list
+    let names: Vec<String> = args.engines.iter().enumerate().map(|(index, spec)| format!("{}.{}", index + 1, spec)).collect();
+    let seed = args.seed.unwrap_or_else(|| rand::thread_rng().gen());
+    let threads = args.threads.unwrap_or_else(|| std::thread::available_parallelism().map_or(1, usize::from));
+    let pairings = engines.len() * (engines.len() - 1) / 2;
+    println!("{} engines, {} games each pairing, {} games from seed {}, up to {} at a time", engines.len(), args.games, pairings * args.games, seed, threads);
+
+    let mut log = Vec::new();
+    let report = |game: &TournamentGame| {
+        let [first, second] = game.engines;
+        let result = match game.played.points {
+            Some([first_points, _]) if first_points > 0.5 => "1-0",
+            Some([first_points, _]) if first_points < 0.5 => "0-1",
+            Some(_) => "1/2-1/2",
+            None => "1/2-1/2 (ply limit)",
+        };
+        let line = format!("{} vs {}: {} in {} plies, seed {}", names[first], names[second], result, game.played.plies, game.seed);
+        println!("{}", line);
+        log.push(line);
     };
+    match rust_dark_chess::arena::run_tournament(&engines, args.games, seed, rules, args.max_plies, threads, report) {
+        Ok(table) => {
+            let crosstable = format_crosstable(&names, &table);
+            println!("{}", crosstable);
+            if let Some(path) = &args.output {
+                let text = format!("Seed {}\n{}\n\n{}\n", seed, log.join("\n"), crosstable);
+                match std::fs::write(path, text) {
+                    Ok(()) => println!("Results written to {}.", path.display()),
+                    Err(e) => println!("Error: could not write {}: {}", path.display(), e),
+                }
+            }
+        },
+        Err(e) => println!("Error: {}", e),
+    }
+}
+
+/// Who sits opposite the player at the keyboard.
+enum Opponent {
+    Human,
+    Bot,
+    Remote(RemotePlayer),
+}
 
-    path_clear && matches!(board[to_y][to_x], Cell::Revealed(_) | Cell::Empty) // Ensure path is clear and target is either empty or a revealed piece for capturing
+impl Opponent {
+    fn at_keyboard(bot: bool) -> Opponent {
+        if bot { Opponent::Bot } else { Opponent::Human }
+    }
 }
 
-fn valid_move_for_piece(piece: Piece, from_x: usize, from_y: usize, to_x: usize, to_y: usize, board: &Board) -> bool {
-    match piece.piece_type {
-        // Use the same logic for cannons and chariots for non-capturing moves.
-        PieceType::Cannon | PieceType::Chariot => is_valid_chariot_move_or_capture(board, from_x, from_y, to_x, to_y),
-        // Direct adjacent move for all other pieces.
-        _ => (from_x as i32 - to_x as i32).abs() + (from_y as i32 - to_y as i32).abs() == 1,
+/// A player in a game: an agent, or `None` for a human at the keyboard.
+type Seat = Option<Box<dyn Agent>>;
+
+fn make_renderer(tui: bool, rules: RuleConfig, settings: &Settings) -> Box<dyn Renderer> {
+    let (symbols, colors, style) = (settings.symbols(), settings.colors.clone(), settings.style);
+    if tui {
+        Box::new(TuiRenderer::new(symbols, colors, style, settings.charset, rules, settings.piece_values.clone(), settings.eval_bar))
+    } else {
+        let piece_values = settings.piece_values.clone();
+        // The bar is printed from the search thread, usually before the prompt
+        let evaluator = settings.eval_bar.then(|| {
+            let colors = colors.clone();
+            Evaluator::new(move |_, value| println!("{}", evaluation_bar(value, &colors)))
+        });
+        Box::new(LineRenderer { symbols, colors, style, charset: settings.charset, material_bar: settings.material_bar, narrate: settings.narrate, piece_values, captured: None, evaluator })
     }
 }
 
-fn move_piece(board: &mut Board, from_x: usize, from_y: usize, to_x: usize, to_y: usize) -> Result<Option<GameMove>, &'static str> {
-    if from_y >= board.len() || from_x >= board[0].len() || to_y >= board.len() || to_x >= board[0].len() {
-        return Err("Coordinates out of bounds.");
+/// Plays one game and returns the points each seat scored, or `None` if it was left unfinished.
+fn run_game(args: &PlayArgs, opposition: Opponent, rules: RuleConfig, settings: &Settings) -> Option<[f64; 2]> {
+    let (symbols, colors, style, language) = (settings.symbols(), settings.colors.clone(), settings.style, settings.language);
+    let mut ui = make_renderer(args.tui, rules, settings);
+    let mut narrate = settings.narrate;
+
+    // Seed for the shuffle and initial reveals, printed so a layout can be replayed
+    let seed = args.setup.seed.unwrap_or_else(|| rand::thread_rng().gen());
+    let mut rng = StdRng::seed_from_u64(seed);
+    let started = unix_time();
+    tell!(ui, language, "Seed: {} (start with --seed {} to replay this layout)", seed, seed);
+    if rules.variant != Variant::default() {
+        tell!(ui, language, "Playing by {} rules.", language.tr(rules.variant.rule_set().name()));
     }
 
-    match board[from_y][from_x] {
-        Cell::Revealed(attacker) => {
-            match board[to_y][to_x] {
-                Cell::Hidden(_) | Cell::Empty => {
-                    // Handle non-capturing moves
-                    if valid_move_for_piece(attacker, from_x, from_y, to_x, to_y, board) {
-                        let game_move = GameMove {
-                            action_type: ActionType::Move { from_x, from_y, to_x, to_y },
-                            piece: Some(attacker),
-                            captured_piece: None,
-                        };
-                        board[to_y][to_x] = Cell::Revealed(attacker);
-                        board[from_y][from_x] = Cell::Empty;
-                        Ok(Some(game_move))
-                    } else {
-                        Err("Invalid move.")
-                    }
+    let mut game = match start_position(&args.setup, rules, &mut rng) {
+        Ok(game) => game,
+        Err(e) => {
+            tell!(ui, language, "Error: {}", language.error(e));
+            return None;
+        },
+    };
+    if args.edit {
+        match run_setup(ui.as_mut(), &game, language) {
+            Some(edited) => game = edited,
+            None => tell!(ui, language, "Setup cancelled, playing the deal as it was."),
+        }
+    }
+
+    // Seats in turn order: the player making the opening flip, then the other. None is a human at the keyboard
+    let mut seats: [Seat; 2] = [None, None];
+    // In a network game the host is the only one who could take moves back or rearrange the board
+    let networked = matches!(opposition, Opponent::Remote(_));
+    match opposition {
+        Opponent::Human => {},
+        Opponent::Bot => {
+            let bot_seat = if args.ai.ai_first { 0 } else { 1 };
+            let bot_rng = StdRng::from_rng(&mut rng).expect("Seeded RNG cannot fail");
+            seats[bot_seat] = Some(args.ai.settings(settings.difficulty).build_agent(bot_rng));
+        },
+        // The host makes the opening flip
+        Opponent::Remote(player) => seats[1] = Some(Box::new(player)),
+    }
+    // Profile names for ratings: --player names the people in turn order, and bots play under their own names
+    let mut given_names = args.players.iter().cloned();
+    let names: [Option<String>; 2] = [0, 1].map(|seat| match &seats[seat] {
+        Some(agent) if !networked => Some(agent.name().to_string()),
+        _ => given_names.next(),
+    });
+    let seat_names = [0, 1].map(|seat| match (&names[seat], &seats[seat]) {
+        (Some(name), _) => name.clone(),
+        (None, Some(agent)) => agent.name().to_string(),
+        (None, None) => String::from("Human"),
+    });
+
+    // Both clocks are stopped until the opening flip has decided who plays which color
+    let mut clock = match args.time.as_ref().or(settings.time_control.as_ref()).map(|text| TimeControl::parse(text)) {
+        Some(Ok(control)) => Some(Clock::new(control)),
+        Some(Err(e)) => {
+            tell!(ui, language, "Error: {}", language.tr(e));
+            return None;
+        },
+        None => None,
+    };
+    let mut clock_log = ClockLog::new(clock.as_ref());
+    let mut termination = None;
+
+    // Seat that was last asked for a command, for hot-seat hand-overs
+    let mut last_seat = None;
+
+    // Squares highlighted on the next board render by the moves command
+    let mut marks: Vec<(usize, usize)> = Vec::new();
+
+    // Main game loop, one command per iteration
+    loop {
+        if let Some(clock) = clock.as_mut() {
+            clock_log.sync(clock, &game);
+            if !game.colors_unassigned() && clock.running() != Some(game.current_player) {
+                clock.start(game.current_player);
+            }
+            ui.set_status(&clock_status(clock, language));
+        }
+
+        // Display the board to the current player
+        ui.set_captured(&game.captured);
+        ui.show_board(&game.board, &marks);
+        ui.evaluate(&game);
+        marks.clear();
+
+        // Bots choose their action directly instead of reading a command
+        let seat = if game.first_player_to_move() { 0 } else { 1 };
+        let (own, other) = seat_and_opponent(&mut seats, seat);
+        if let Some(agent) = own.as_mut() {
+            let choosing_colors = game.colors_unassigned();
+            let mover = (!choosing_colors).then_some(game.current_player);
+            let decision = agent.decide(&game);
+            if out_of_time(&mut game, clock.as_ref(), ui.as_mut(), language) {
+                termination = Some("time forfeit");
+                break;
+            }
+            let choice = match decision {
+                Decision::Play(action) => Ok(action),
+                Decision::Resign => match game.resign(game.current_player) {
+                    Ok(result) => {
+                        termination = Some("resignation");
+                        say!(ui, "{}", language.describe_ending(result, termination));
+                        break;
+                    },
+                    Err(e) => Err(e.message()),
                 },
-                Cell::Revealed(defender) => {
-                    // Handle capturing moves
-                    if attacker.player == defender.player {
-                        return Err("Cannot capture your own piece.");
+                Decision::OfferDraw => {
+                    tell!(ui, language, "{} offers a draw.", agent.name());
+                    if answer_draw(ui.as_mut(), other.as_mut(), &game, language) {
+                        if let Ok(result) = game.agree_draw() {
+                            termination = Some("draw agreed");
+                            say!(ui, "{}", language.describe_result(result));
+                            break;
+                        }
                     }
-
-                    if (attacker.piece_type != PieceType::Cannon && can_capture(attacker, defender)) ||
-                       (attacker.piece_type == PieceType::Cannon && is_valid_cannon_capture(board, from_x, from_y, to_x, to_y)) {
-                        let game_move = GameMove {
-                            action_type: ActionType::Move { from_x, from_y, to_x, to_y },
-                            piece: Some(attacker),
-                            captured_piece: Some(defender),
-                        };
-                        board[to_y][to_x] = Cell::Revealed(attacker);
-                        board[from_y][from_x] = Cell::Empty;
-                        Ok(Some(game_move))
+                    agent.draw_declined();
+                    continue;
+                },
+                Decision::Leave if !game.legal_actions().is_empty() => {
+                    tell!(ui, language, "{} left the game.", agent.name());
+                    termination = Some("abandoned");
+                    break;
+                },
+                Decision::Leave => Err("No legal actions."),
+            };
+            match choice.and_then(|action| game.apply_action(action).map_err(GameError::message)) {
+                Ok(game_move) => {
+                    if narrate {
+                        say!(ui, "{}", narrate_move(&game_move, mover, style));
                     } else {
-                        Err("Cannot capture this piece.")
+                        tell!(ui, language, "{} plays: {}", agent.name(), language.describe_move(&game_move, style));
+                    }
+                    announce_colors(ui.as_mut(), &game, choosing_colors, language);
+                    if let Some(result) = game.result() {
+                        say!(ui, "{}", language.describe_result(result));
+                        break;
                     }
                 },
-                _ => Err("Invalid target."),
+                Err(e) => {
+                    tell!(ui, language, "Error: {} could not play: {}", agent.name(), language.tr(e));
+                    break;
+                },
             }
-        },
-        _ => Err("No piece to move."),
-    }
-}
+            continue;
+        }
 
-fn undo_last_move(board: &mut Board, moves_history: &mut Vec<GameMove>) -> Result<(), &'static str> {
-    if let Some(last_move) = moves_history.pop() {
-        match last_move.action_type {
-            ActionType::Flip { x, y } => {
-                // If the last action was a flip, simply hide the piece again.
-                board[y][x] = Cell::Hidden(last_move.piece);
+        // Two people sharing the keyboard only hand it over when the turn passes between them
+        if args.hot_seat && seats.iter().all(Option::is_none) && last_seat.is_some_and(|last| last != seat) {
+            let next = if game.colors_unassigned() { language.tr("the first player") } else { language.player_name(game.current_player) };
+            ui.hand_over(&language.format("Pass the keyboard to {} - press Enter when ready.", &[&next]));
+            ui.show_board(&game.board, &[]);
+        }
+        last_seat = Some(seat);
+
+        // Prompt for player action
+        if game.colors_unassigned() {
+            tell!(ui, language, "No colors yet: the first player flips a piece and plays its color.");
+        }
+        let to_move = if game.colors_unassigned() { language.tr("First player").to_string() } else { language.format("Player {}", &[&language.player_name(game.current_player)]) };
+        let prompt = language.format("{}, enter your action (e.g., 'flip row col', 'move from_row from_col to_row to_col', 'flip c2', 'undo', 'draw', 'resign', or 'exit'):", &[&to_move]);
+        let Some(action_input) = ui.read_command(&prompt) else {
+            break;
+        };
+        if out_of_time(&mut game, clock.as_ref(), ui.as_mut(), language) {
+            termination = Some("time forfeit");
+            break;
+        }
+        let trimmed_input = action_input.trim();
+
+        match trimmed_input.to_lowercase().as_str() {
+            "state" => say!(ui, "{}", render_game_state(&game.board, &symbols)),
+            "history" => say!(ui, "{}", render_move_history(&game.history, &symbols, style)),
+            "summary" => say!(ui, "{}", summary_line(&game.board, &game.history, game.current_player)),
+            "remaining" => say!(ui, "{}", describe_remaining(&game)),
+            "odds" => say!(ui, "{}", describe_odds(&game)),
+            "threats" => say!(ui, "{}", describe_threats(&threatened_pieces(&game.board, game.current_player, &game.rules), &symbols, &colors, style)),
+            "help" => say!(ui, "{}", help_text(None, language).unwrap_or_default()),
+            other if other.starts_with("help ") => match help_text(Some(other["help ".len()..].trim()), language) {
+                Ok(text) => say!(ui, "{}", text),
+                Err(e) => say!(ui, "{}", e),
+            },
+            "exit" => {
+                tell!(ui, language, "Exiting game.");
+                break;
+            },
+            "draw" => {
+                tell!(ui, language, "{} offers a draw.", side_name(&game, seat, language));
+                if answer_draw(ui.as_mut(), seats[1 - seat].as_mut(), &game, language) {
+                    match game.agree_draw() {
+                        Ok(result) => {
+                            termination = Some("draw agreed");
+                            say!(ui, "{}", language.describe_result(result));
+                            break;
+                        },
+                        Err(e) => say!(ui, "{}", language.error(e)),
+                    }
+                }
+            },
+            "resign" => match game.resign(game.current_player) {
+                Ok(result) => {
+                    termination = Some("resignation");
+                    say!(ui, "{}", language.describe_ending(result, termination));
+                    break;
+                },
+                Err(e) => say!(ui, "{}", language.error(e)),
             },
-            ActionType::Move { from_x, from_y, to_x, to_y } => {
-                // If the last action was a move, move the piece back to its original position.
-                let piece = last_move.piece.expect("A moved piece must exist.");
-                board[from_y][from_x] = Cell::Revealed(piece);
-
-                // If a piece was captured during the move, restore it to its position.
-                // Otherwise, set the cell to empty.
-                match last_move.captured_piece {
-                    Some(captured_piece) => board[to_y][to_x] = Cell::Revealed(captured_piece),
-                    None => board[to_y][to_x] = Cell::Empty,
+            other if networked && (matches!(other, "undo" | "redo" | "setup" | "flip all") || other.starts_with("position ")) => {
+                tell!(ui, language, "That command is not available in a network game.");
+            },
+            "flip all" => {
+                // Testing aid only: the reveals are not moves, so they neither use up the turn nor enter the history
+                flip_all_pieces(&mut game.board);
+                if game.colors_unassigned() {
+                    // Nothing is left to flip, so the side to move keeps its placeholder color
+                    game.first_player = PlayerColor::Assigned(game.current_player);
+                }
+                tell!(ui, language, "All pieces flipped for testing (not recorded in history).");
+            },
+            "setup" => {
+                // Editing isn't part of anyone's turn
+                if let Some(clock) = clock.as_mut() {
+                    clock.stop();
+                }
+                if let Some(edited) = run_setup(ui.as_mut(), &game, language) {
+                    // History no longer describes the edited position
+                    game = edited;
+                    clock_log = ClockLog::new(clock.as_ref());
+                    tell!(ui, language, "Setup complete.");
+                } else {
+                    tell!(ui, language, "Setup cancelled.");
+                }
+            },
+            "hint" => {
+                // Searches like the hard bot, without playing the suggestion
+                let settings = AiSettings::for_difficulty(Difficulty::Hard);
+                match best_action(&game, settings.depth, settings.flip_policy, &mut rng) {
+                    Some(_) if game.colors_unassigned() => tell!(ui, language, "Hint: any flip will do, the colors aren't decided yet."),
+                    Some(suggestion) => tell!(
+                        ui,
+                        language,
+                        "Hint: {} (evaluation {} for {}, {} positions searched)",
+                        language.describe_action(&game.board, suggestion.action, &game.rules, style),
+                        format!("{:+.1}", suggestion.value),
+                        language.player_name(game.current_player),
+                        suggestion.nodes
+                    ),
+                    None => tell!(ui, language, "No legal actions to suggest."),
+                }
+            },
+            other if other.starts_with("perft ") => match other["perft ".len()..].trim().parse::<u32>() {
+                Ok(depth) => {
+                    for ply in 1..=depth {
+                        let started = std::time::Instant::now();
+                        say!(ui, "perft {}: {} ({:.2?})", ply, perft(&game, ply), started.elapsed());
+                    }
+                },
+                Err(_) => tell!(ui, language, "Usage: perft <depth>"),
+            },
+            other if other.split_whitespace().next() == Some("winrate") => {
+                let words: Vec<&str> = other.split_whitespace().skip(1).collect();
+                let parsed = match words[..] {
+                    [] => Some((WINRATE_PLAYOUTS, PlayoutPolicy::Captures)),
+                    [count] => count.parse().ok().map(|count| (count, PlayoutPolicy::Captures)),
+                    [count, policy] => count.parse().ok().zip(parse_playout_policy(policy)),
+                    _ => None,
+                };
+                match parsed.filter(|&(count, _)| count > 0) {
+                    Some((count, policy)) => {
+                        let estimate = estimate_winrate(&game, count, policy, &mut rng);
+                        tell!(ui, language, "Estimated from {} playouts:", count);
+                        for player in [Player::Red, Player::Black] {
+                            let (wins, draws, losses) = estimate.percentages(player);
+                            tell!(
+                                ui,
+                                language,
+                                "{}: wins {}%, draws {}%, loses {}%",
+                                language.player_name(player),
+                                format!("{:.1}", wins),
+                                format!("{:.1}", draws),
+                                format!("{:.1}", losses)
+                            );
+                        }
+                        if estimate.unfinished > 0 {
+                            tell!(ui, language, "{} of the drawn playouts stopped at the {}-ply limit.", estimate.unfinished, PLAYOUT_PLY_LIMIT);
+                        }
+                    },
+                    None => tell!(ui, language, "Usage: winrate [playouts] [random|captures]"),
+                }
+            },
+            "undo" => {
+                match take_back(&mut game, &seats) {
+                    Ok(undone) => {
+                        for undone in undone {
+                            tell!(ui, language, "Undid: {}", language.describe_move(&undone, style));
+                        }
+                    },
+                    Err(e) => say!(ui, "{}", language.error(e)),
+                }
+            },
+            "redo" => {
+                match replay(&mut game, &seats) {
+                    Ok(redone) => {
+                        if let Some(clock) = clock.as_mut() {
+                            clock_log.redo(clock, redone.len());
+                        }
+                        for redone in redone {
+                            tell!(ui, language, "Redid: {}", language.describe_move(&redone, style));
+                        }
+                        if let Some(result) = game.result() {
+                            say!(ui, "{}", language.describe_result(result));
+                            break;
+                        }
+                    },
+                    Err(e) => say!(ui, "{}", language.error(e)),
+                }
+            },
+            toggle @ ("narrate on" | "narrate off") => {
+                narrate = toggle == "narrate on";
+                ui.set_narration(narrate);
+                tell!(ui, language, if narrate { "Narration is on." } else { "Narration is off." });
+            },
+            "fen" => say!(ui, "{}", game.to_fen(false)),
+            "fen full" => say!(ui, "{}", game.to_fen(true)),
+            other if other.starts_with("position ") => {
+                // Piece letters are case sensitive, so read the position from the original input
+                match GameState::from_fen(&trimmed_input["position ".len()..], game.rules, &mut rng) {
+                    Ok(loaded) => {
+                        game = loaded;
+                        clock_log = ClockLog::new(clock.as_ref());
+                        tell!(ui, language, "Position loaded.");
+                    },
+                    Err(e) => tell!(ui, language, "Error: {}", language.error(e)),
+                }
+            },
+            other if other.starts_with("export ") => {
+                let path = trimmed_input["export ".len()..].trim();
+                match std::fs::write(path, game_record(&game, &seat_names, seed, started, clock.as_ref(), termination)) {
+                    Ok(()) => tell!(ui, language, "Game saved to {}.", path),
+                    Err(e) => tell!(ui, language, "Error: could not write {}: {}", path, e),
+                }
+            },
+            other if other.starts_with("eval ") => {
+                // Score a candidate action on a copy of the board without playing it
+                match parse_eval_action(&other["eval ".len()..]).and_then(|action| game.evaluate_action(action).map_err(GameError::message)) {
+                    Ok(score) => tell!(ui, language, "Evaluation for {}: {}", language.player_name(game.current_player), format!("{:+.1}", score)),
+                    Err(e) => tell!(ui, language, "Error: {}", language.tr(e)),
+                }
+            },
+            _ => {
+                // Handle action input
+                match parse_input(trimmed_input) {
+                    Ok((command, coordinates)) => {
+                        if command == "moves" && coordinates.len() == 2 {
+                            match describe_destinations(&game.board, coordinates[0], coordinates[1], &game.rules, &symbols, &colors, style) {
+                                Ok(report) => {
+                                    say!(ui, "{}", report);
+                                    if let Ok(moves) = moves_from(&game.board, coordinates[0], coordinates[1], &game.rules) {
+                                        marks = moves
+                                            .iter()
+                                            .filter_map(|game_move| match game_move.action_type {
+                                                ActionType::Move { to_x, to_y, .. } => Some((to_x, to_y)),
+                                                ActionType::Flip { .. } => None,
+                                            })
+                                            .collect();
+                                    }
+                                },
+                                Err(e) => tell!(ui, language, "Error: {}", language.error(e)),
+                            }
+                        } else if command == "inspect" && coordinates.len() == 2 {
+                            match inspect_cell(&game.board, coordinates[0], coordinates[1], &game.rules, &symbols, &colors, style) {
+                                Ok(report) => say!(ui, "{}", report),
+                                Err(e) => tell!(ui, language, "Error: {}", language.error(e)),
+                            }
+                        } else if let Some(action) = parse_action(&command, &coordinates) {
+                            let choosing_colors = game.colors_unassigned();
+                            let mover = (!choosing_colors).then_some(game.current_player);
+                            match game.apply_action(action) {
+                                Ok(game_move) => {
+                                    match (action, language.describe_capture(&game_move, &symbols, &colors, style)) {
+                                        _ if narrate => say!(ui, "{}", narrate_move(&game_move, mover, style)),
+                                        (ActionType::Flip { .. }, _) => tell!(ui, language, "Piece flipped."),
+                                        (_, Some(capture_line)) => say!(ui, "{}", capture_line),
+                                        (_, None) => tell!(ui, language, "Piece moved."),
+                                    }
+                                    announce_colors(ui.as_mut(), &game, choosing_colors, language);
+
+                                    // Check for game over condition after a valid turn
+                                    if let Some(result) = game.result() {
+                                        say!(ui, "{}", language.describe_result(result));
+                                        break;
+                                    }
+                                },
+                                Err(e) => tell!(ui, language, "Error: {}", language.error(e)),
+                            }
+                        } else {
+                            tell!(ui, language, "Invalid command or number of coordinates.");
+                        }
+                    },
+                    Err(e) => tell!(ui, language, "Error parsing input: {}", language.tr(e)),
                 }
             }
         }
-        Ok(())
+    }
+
+    // Game is over, either by exit command or natural end
+    for agent in seats.iter_mut().flatten() {
+        agent.game_over(&game, termination);
+    }
+    say!(ui, "{}", language.capture_summary(&game.history, Player::Red));
+    say!(ui, "{}", language.capture_summary(&game.history, Player::Black));
+    if let PlayerColor::Assigned(first) = game.first_player {
+        let plies = game.history.len();
+        tell!(
+            ui,
+            language,
+            "Game length: {} {}. Flips: Red {}, Black {}.",
+            plies,
+            language.tr(if plies == 1 { "ply" } else { "plies" }),
+            flip_count(&game.history, first, Player::Red),
+            flip_count(&game.history, first, Player::Black)
+        );
+    }
+    if game.result().is_some() && (args.review || settings.review) {
+        let depth = AiSettings::for_difficulty(Difficulty::Hard).depth;
+        match review_report(&game, depth, MISTAKE_THRESHOLD, language, style) {
+            Ok(lines) => lines.iter().for_each(|line| say!(ui, "{}", line)),
+            Err(e) => tell!(ui, language, "Error: could not review the game: {}", language.error(e)),
+        }
+    }
+    // Only finished games between two different named players are rated
+    if let (Some(_), PlayerColor::Assigned(first), [Some(first_name), Some(second_name)], Some(path)) = (game.result(), game.first_player, &names, &settings.profiles) {
+        let (red, black) = if first == Player::Red { (first_name, second_name) } else { (second_name, first_name) };
+        if red != black {
+            match rate_game(path, red, black, &game) {
+                Ok(ratings) => tell!(ui, language, "Ratings: {}", ratings),
+                Err(e) => tell!(ui, language, "Error: could not update ratings: {}", e),
+            }
+        }
+    }
+    if let Some(path) = &settings.autosave {
+        match std::fs::write(path, game_record(&game, &seat_names, seed, started, clock.as_ref(), termination)) {
+            Ok(()) => tell!(ui, language, "Game saved to {}.", path.display()),
+            Err(e) => tell!(ui, language, "Error: could not autosave to {}: {}", path.display(), e),
+        }
+    }
+    if game.result().is_some() {
+        let record = game_record(&game, &seat_names, seed, started, clock.as_ref(), termination);
+        if let Some(dir) = &settings.game_log {
+            if let Err(e) = log_game(dir, &record, started, seed) {
+                tell!(ui, language, "Error: could not add the game to the log in {}: {}", dir.display(), e);
+            }
+        }
+        if let Some(path) = &settings.database {
+            match GameDatabase::open(path).and_then(|database| database.add_game(&record)) {
+                Ok(id) => tell!(ui, language, "Stored as game {} in the history.", id),
+                Err(e) => tell!(ui, language, "Error: could not store the game: {}", e),
+            }
+        }
+    }
+    tell!(ui, language, "Game over. Thanks for playing!");
+    ui.finish();
+    game.seat_points()
+}
+
+/// Plays a best-of-`games` match at the keyboard. The two sides swap seats after every game, so
+/// they take turns making the opening flip, and the match stops once one of them can't be caught.
+fn run_match(args: &PlayArgs, bot: bool, games: u32, rules: RuleConfig, settings: &Settings) {
+    // The sides in the seats they take for the first game
+    let sides: [String; 2] = if bot {
+        let human = args.players.first().cloned().unwrap_or_else(|| String::from("Human"));
+        if args.ai.ai_first { [String::from("AI"), human] } else { [human, String::from("AI")] }
     } else {
-        Err("No moves to undo.")
-    }
-}
-
-fn check_game_over(board: &Board) -> bool {
-    let mut red_pieces = 0;
-    let mut black_pieces = 0;
-    let mut hidden_pieces = 0;
-    let mut empty_cells = 0; // Counting empty cells for completeness
-    
-    for row in board {
-        for cell in row {
-            match cell {
-                Cell::Hidden(_) => hidden_pieces += 1,
-                Cell::Revealed(piece) => match piece.player {
-                    Player::Red => red_pieces += 1,
-                    Player::Black => black_pieces += 1,
-                },
-                Cell::Empty => empty_cells += 1,
+        [0, 1].map(|side| args.players.get(side).cloned().unwrap_or_else(|| format!("Player {}", side + 1)))
+    };
+    let language = settings.language;
+    let mut score = [0.0; 2];
+    for played in 1..=games {
+        let swapped = played % 2 == 0;
+        let mut game_args = args.clone();
+        // Each game gets its own deal, which a seed still fixes for the whole match
+        game_args.setup.seed = args.setup.seed.map(|seed| seed.wrapping_add(u64::from(played - 1)));
+        if swapped {
+            game_args.ai.ai_first = !args.ai.ai_first;
+            if !bot {
+                // A single name can't be moved to the second seat without naming the first
+                game_args.players = if args.players.len() == 2 { vec![args.players[1].clone(), args.players[0].clone()] } else { Vec::new() };
             }
         }
+        println!("{}", language.format("Game {} of {}: {} makes the opening flip.", &[&played, &games, &sides[usize::from(swapped)]]));
+
+        let Some(points) = run_game(&game_args, Opponent::at_keyboard(bot), rules, settings) else {
+            println!("{}", language.format("Match abandoned after {} of {} games: {}.", &[&played, &games, &match_score(&sides, score)]));
+            return;
+        };
+        for side in 0..2 {
+            score[side] += points[side ^ usize::from(swapped)];
+        }
+        println!("{}", language.format("Match score after game {} of {}: {}.", &[&played, &games, &match_score(&sides, score)]));
+        if score.iter().any(|&points| points > f64::from(games) / 2.0) {
+            break;
+        }
     }
 
-    // Do not end the game if there are still hidden pieces
-    if hidden_pieces > 0 {
-        return false;
+    match score[0].total_cmp(&score[1]) {
+        std::cmp::Ordering::Greater => println!("{}", language.format("{} wins the match, {}.", &[&sides[0], &match_score(&sides, score)])),
+        std::cmp::Ordering::Less => println!("{}", language.format("{} wins the match, {}.", &[&sides[1], &match_score(&sides, score)])),
+        std::cmp::Ordering::Equal => println!("{}", language.format("The match is drawn, {}.", &[&match_score(&sides, score)])),
     }
+}
 
-    // End the game if either player has no pieces left
-    red_pieces == 0 || black_pieces == 0
+fn match_score(sides: &[String; 2], score: [f64; 2]) -> String {
+    format!("{} {} - {} {}", sides[0], score[0], score[1], sides[1])
 }
 
-fn parse_input(input: &str) -> Result<(String, Vec<usize>), &'static str> {
-    let parts: Vec<&str> = input.trim().split_whitespace().collect();
-    let command = parts.get(0).ok_or("Missing command")?.to_string();
+/// The agent in `seat` and the one opposite it; `None` is a human at the keyboard.
+fn seat_and_opponent(seats: &mut [Seat; 2], seat: usize) -> (&mut Seat, &mut Seat) {
+    let [first, second] = seats;
+    if seat == 0 { (first, second) } else { (second, first) }
+}
 
-    let coordinates = parts[1..]
-        .iter()
-        .map(|&x| x.parse::<usize>())
-        .collect::<Result<Vec<usize>, _>>()
-        .map_err(|_| "Invalid coordinates")?;
+// Whether a bot, rather than someone at the keyboard, is to move
+fn bot_to_move(game: &GameState, seats: &[Seat; 2]) -> bool {
+    seats[if game.first_player_to_move() { 0 } else { 1 }].is_some()
+}
 
-    Ok((command, coordinates))
+/// Undoes actions until someone at the keyboard is to move again, so against a bot the bot's
+/// reply and the player's own action go together instead of the bot replaying straight away.
+/// Returns the undone moves, latest first. If only the bot's actions are left, nothing changes.
+fn take_back(game: &mut GameState, seats: &[Seat; 2]) -> Result<Vec<GameMove>, GameError> {
+    let mut undone = vec![game.undo()?];
+    while bot_to_move(game, seats) {
+        match game.undo() {
+            Ok(game_move) => undone.push(game_move),
+            Err(e) => {
+                for _ in &undone {
+                    game.redo()?;
+                }
+                return Err(e);
+            },
+        }
+    }
+    Ok(undone)
 }
-    
-fn flip_all_pieces(board: &mut Board) {
-    // For testing
-    for row in board.iter_mut() {
-        for cell in row.iter_mut() {
-            if let Cell::Hidden(Some(piece)) = cell {
-                *cell = Cell::Revealed(*piece);
-            }
+
+/// Redoes actions the same way `take_back` undid them, stopping once someone at the keyboard
+/// is to move or nothing is left to redo.
+fn replay(game: &mut GameState, seats: &[Seat; 2]) -> Result<Vec<GameMove>, GameError> {
+    let mut redone = vec![game.redo()?];
+    while bot_to_move(game, seats) && !game.redo_stack.is_empty() && game.result().is_none() {
+        redone.push(game.redo()?);
+    }
+    Ok(redone)
+}
+
+/// Times at the start of every ply so far, so undo can wind the clock back and redo forward again.
+struct ClockLog {
+    times: Vec<[Duration; 2]>,
+    undone: Vec<[Duration; 2]>, // Entries taken off by undo, the next one to redo last
+}
+
+impl ClockLog {
+    fn new(clock: Option<&Clock>) -> Self {
+        ClockLog { times: clock.iter().map(|clock| clock.times()).collect(), undone: Vec::new() }
+    }
+
+    /// A new ply ends the mover's turn on the clock, an undone one winds it back.
+    fn sync(&mut self, clock: &mut Clock, game: &GameState) {
+        let plies = game.history.len();
+        if plies >= self.times.len() {
+            clock.press(opponent(game.current_player));
+            self.times.push(clock.times());
+            self.undone.clear();
+        } else if plies + 1 < self.times.len() {
+            self.undone.extend(self.times.drain(plies + 1..).rev());
+            clock.set_times(self.times[plies]);
+        }
+    }
+
+    /// Puts back the times of `count` redone plies as they stood when the plies were first played.
+    fn redo(&mut self, clock: &mut Clock, count: usize) {
+        for _ in 0..count {
+            self.times.extend(self.undone.pop());
+        }
+        if let Some(&times) = self.times.last() {
+            clock.set_times(times);
         }
     }
 }
 
-fn print_move_history(moves_history: &[GameMove], symbols: &HashMap<(Player, PieceType), &'static str>) {
-    println!("Move History:");
-    for (index, game_move) in moves_history.iter().enumerate() {
-        let player = match game_move.piece {
-            Some(piece) => piece.player,
-            None => continue,
-        };
-        
-        let player_symbol = match player {
-            Player::Red => "Red",
-            Player::Black => "Black",
-        };
+/// The review of a finished game: every action that lost more than `threshold` against the
+/// search's choice, then each side's accuracy.
+fn review_report(game: &GameState, depth: u32, threshold: f64, language: Language, style: CoordinateStyle) -> Result<Vec<String>, GameError> {
+    let reviews = review_game(game, depth, FlipPolicy::All)?;
+    let mut lines = vec![language.format("Review at depth {}, flagging actions that lose more than {}:", &[&depth, &threshold])];
+
+    // The search's choice is described on the board it was made for
+    let mut position = game.clone();
+    while position.undo().is_ok() {}
+    for review in &reviews {
+        while position.history.len() + 1 < review.ply {
+            position.redo()?;
+        }
+        if review.loss() > threshold {
+            lines.push(language.format(
+                "Ply {}, {}: {} loses {}; better was {}.",
+                &[
+                    &review.ply,
+                    &language.player_name(review.player),
+                    &language.describe_move(&review.played, style),
+                    &format!("{:.1}", review.loss()),
+                    &language.describe_action(&position.board, review.best, &position.rules, style),
+                ],
+            ));
+        }
+    }
+    if lines.len() == 1 {
+        lines.push(language.format("No action lost more than {}.", &[&threshold]));
+    }
 
-        let piece_symbol = game_move.piece.map_or("Unknown", |p| symbols.get(&(p.player, p.piece_type)).unwrap_or(&"Unknown"));
-        
-        let action_description = match game_move.action_type {
-            ActionType::Flip { x, y } => format!("Flip at ({}, {})", x, y),
-            ActionType::Move { from_x, from_y, to_x, to_y } => format!("Move from ({}, {}) to ({}, {})", from_x, from_y, to_x, to_y),
-        };
-        
-        let capture_description = match game_move.captured_piece {
-            Some(captured_piece) => format!(", captured {}", symbols.get(&(captured_piece.player, captured_piece.piece_type)).unwrap_or(&"Unknown")),
-            None => String::new(),
-        };
+    for player in [Player::Red, Player::Black] {
+        let accuracy = Accuracy::new(&reviews, player, threshold);
+        lines.push(language.format(
+            "{}: accuracy {}% over {} {}, average loss {}, {} {}.",
+            &[
+                &language.player_name(player),
+                &format!("{:.0}", accuracy.percent()),
+                &accuracy.moves,
+                &language.tr(if accuracy.moves == 1 { "action" } else { "actions" }),
+                &format!("{:.2}", accuracy.average_loss()),
+                &accuracy.mistakes,
+                &language.tr(if accuracy.mistakes == 1 { "mistake" } else { "mistakes" }),
+            ],
+        ));
+    }
+    Ok(lines)
+}
 
-        println!("{}. {} {} made a {}{}", index + 1, player_symbol, piece_symbol, action_description, capture_description);
+/// Who sits in `seat`, by color once the colors are decided.
+fn side_name(game: &GameState, seat: usize, language: Language) -> &'static str {
+    match game.first_player {
+        PlayerColor::Assigned(first) => language.player_name(if seat == 0 { first } else { opponent(first) }),
+        PlayerColor::Unassigned if seat == 0 => language.tr("The first player"),
+        PlayerColor::Unassigned => language.tr("The second player"),
     }
 }
 
-fn print_game_state(board: &Board) {
-    let symbols = piece_symbols(); // Retrieve the symbols mapping
-    println!("Game State:");
-    for row in board {
-        let row_state: Vec<String> = row.iter().map(|cell| match cell {
-            Cell::Hidden(_) => String::from("?"),
-            Cell::Revealed(piece) => symbols.get(&(piece.player, piece.piece_type)).unwrap_or(&" ").to_string(),
-            Cell::Empty => String::from("."),
-        }).collect();
+/// Puts a draw offered by the side to move to the other player, a bot or someone at the keyboard.
+fn answer_draw(ui: &mut dyn Renderer, responder: Option<&mut Box<dyn Agent>>, game: &GameState, language: Language) -> bool {
+    match responder {
+        Some(agent) => {
+            let accepted = agent.accept_draw(game);
+            tell!(ui, language, if accepted { "{} accepts the draw." } else { "{} declines the draw." }, agent.name());
+            accepted
+        },
+        None => {
+            let seat = if game.first_player_to_move() { 1 } else { 0 };
+            let accepted = confirm(ui, &language.format("{}, do you accept the draw? (yes/no)", &[&side_name(game, seat, language)]), language);
+            if !accepted {
+                tell!(ui, language, "Draw declined.");
+            }
+            accepted
+        },
+    }
+}
 
-        // Join the cell states with a comma for readability
-        println!("{}", row_state.join(", "));
+fn announce_colors(ui: &mut dyn Renderer, game: &GameState, choosing_colors: bool, language: Language) {
+    // Only the action that settled the colors announces them
+    if let (true, PlayerColor::Assigned(first)) = (choosing_colors, game.first_player) {
+        tell!(ui, language, "The first player takes {}, the second player takes {}.", language.player_name(first), language.player_name(opponent(first)));
     }
 }
 
-fn print_help() {
-    println!("Available commands:");
-    println!("  flip <row> <col>        - Flips a hidden piece at the specified coordinates.");
-    println!("  move <from_row> <from_col> <to_row> <to_col> - Moves a piece from the starting coordinates to the destination coordinates.");
-    println!("  undo                    - Undo the last move.");
-    println!("  state                   - Prints the current game state in a simple text format.");
-    println!("  history                 - Prints the move history.");
-    println!("  exit                    - Exits the game.");
-    println!("  flip all                - (For Testing) Flips all hidden pieces on the board.");
+// A saved game's time control and where both clocks stood, e.g. "Clocks (300+3): Red 4:12, Black 0:07.3"
+fn clock_line(clock: &Clock) -> String {
+    format!(
+        "Clocks ({}): Red {}, Black {}",
+        clock.control.to_pgn(),
+        format_clock(clock.remaining(Player::Red)),
+        format_clock(clock.remaining(Player::Black))
+    )
+}
 
-    println!("\nGameplay Instructions:");
-    println!("  1. The game starts with all pieces hidden. Players take turns to either flip or move pieces.");
-    println!("  2. Pieces can only move to adjacent squares unless specified by their type (e.g., Chariots and Cannons have special movement rules).");
-    println!("  3. To capture an opponent's piece, move your piece to the same square. Capturing rules vary by piece type.");
-    println!("  4. The winning condition is when the opponent has no legal moves left.");
+fn game_record(game: &GameState, seat_names: &[String; 2], seed: u64, started: u64, clock: Option<&Clock>, termination: Option<&str>) -> String {
+    let mut headers = vec![
+        ("Event", String::from("Casual game")),
+        ("Date", utc_date(started)),
+        ("Red", seat_name(game, seat_names, Player::Red)),
+        ("Black", seat_name(game, seat_names, Player::Black)),
+        ("Seed", seed.to_string()),
+        ("StartTime", utc_time(started)),
+    ];
+    if game.result().is_some() {
+        headers.push(("EndTime", utc_time(unix_time())));
+    }
+    if let Some(clock) = clock {
+        headers.push(("TimeControl", clock.control.to_pgn()));
+        headers.push(("RedClock", format_clock(clock.remaining(Player::Red))));
+        headers.push(("BlackClock", format_clock(clock.remaining(Player::Black))));
+    }
+    if let Some(termination) = termination {
+        headers.push(("Termination", termination.to_string()));
+    }
+    export_record(game, &headers)
+}
 
-    println!("\nPiece Capture Order and Special Rules:");
-    println!("  - General (將/帥): Can be captured by Soldiers (卒/兵) but cannot capture Soldiers directly.");
-    println!("  - Advisor (士/仕), Elephant (象/相), Chariot (車/俥), Horse (馬/傌): Capture pieces of the same or lower rank, except the Soldier-General interaction.");
-    println!("  - Cannon (砲/炮): Can capture any piece by jumping over exactly one piece (of any type) along its movement path.");
-    println!("  - Soldier (卒/兵): Can capture the General and pieces of the same or lower rank.");
-    println!("  - The capture order from highest to lowest is General (將/帥) > Advisor (士/仕) > Elephant (象/相) > Chariot (車/俥) > Horse (馬/傌) > Cannon (砲/炮) > Soldier (卒/兵).");
+fn clock_status(clock: &Clock, language: Language) -> String {
+    // The running side is marked so it's clear whose time is ticking
+    let side = |player: Player| {
+        let marker = if clock.running() == Some(player) { " *" } else { "" };
+        format!("{} {}{}", language.player_name(player), format_clock(clock.remaining(player)), marker)
+    };
+    language.format("Clock: {} | {}", &[&side(Player::Red), &side(Player::Black)])
+}
+
+fn out_of_time(game: &mut GameState, clock: Option<&Clock>, ui: &mut dyn Renderer, language: Language) -> bool {
+    // A flag is only noticed once the player acts, but then it ends the game before the action counts
+    let Some(loser) = clock.and_then(Clock::flagged) else {
+        return false;
+    };
+    game.adjudicated = Some(GameResult::Winner(opponent(loser)));
+    tell!(ui, language, "{} ran out of time.", language.player_name(loser));
+    say!(ui, "{}", language.describe_result(GameResult::Winner(opponent(loser))));
+    true
+}
 
-    println!("\nSpecial Movement Rules:");
-    println!("  - Chariot (車/俥): Moves and captures in any number of unblocked squares vertically or horizontally.");
-    println!("  - Cannon (砲/炮): Moves like the Chariot but must jump over exactly one piece to capture any piece.");
-    println!("\nNote: The game supports two players: Red and Black. Players must alternate turns.");
+fn seat_name(game: &GameState, seat_names: &[String; 2], color: Player) -> String {
+    let seat = match game.first_player {
+        PlayerColor::Assigned(first) if first == color => 0,
+        PlayerColor::Assigned(_) => 1,
+        PlayerColor::Unassigned => return String::from("?"),
+    };
+    seat_names[seat].clone()
 }
 
-fn main() {
-    // Initialize the game board
-    let mut board = init_board();
-    
-    // Decide who starts the game, for simplicity we start with Red
-    let mut current_player = Player::Red;
-
-    // Game loop flag
-    let mut game_over = false;
-
-    // Tracks moves for undo functionality
-    let mut moves_history: Vec<GameMove> = Vec::new();
-
-    let symbols = piece_symbols();
-    
-    // Main game loop
-    while !game_over {
-        let mut turn_completed = false;
-
-        while !turn_completed {
-            // Display the board to the current player
-            print_board(&board);
-            
-            // Prompt for player action
-            println!("Player {:?}, enter your action (e.g., 'flip row col', 'move from_row from_col to_row to_col', 'undo', or 'exit'):", current_player);
-
-            let mut action_input = String::new();
-            io::stdin().read_line(&mut action_input).expect("Failed to read line");
-            let trimmed_input = action_input.trim();
-
-            // Check for the exit command
-            match trimmed_input.to_lowercase().as_str() {
-                "state" => print_game_state(&board),
-                "history" => print_move_history(&moves_history, &symbols),
-                "help" => print_help(),
-                "exit" => {
-                    println!("Exiting game.");
-                    game_over = true;
-                    break;
-                },
-                "flip all" => {
-                    flip_all_pieces(&mut board);
-                    println!("All pieces flipped for testing.");
-                    turn_completed = true;
-                },
-                "undo" => {
-                    if let Err(e) = undo_last_move(&mut board, &mut moves_history) {
-                        println!("{}", e);
-                    } else {
-                        println!("Last move undone.");
-                        // Switch back the player if undo was successful
-                        current_player = match current_player {
-                            Player::Red => Player::Black,
-                            Player::Black => Player::Red,
-                        };
-                        turn_completed = false;
-                    }
-                },
-                _ => {
-                    // Handle action input
-                    match parse_input(trimmed_input) {
-                        Ok((command, coordinates)) => {
-                            if command == "flip" && coordinates.len() == 2 {
-                                match flip_piece(&mut board, coordinates[0], coordinates[1]) {
-                                    Ok(Some(game_move)) => {
-                                        moves_history.push(game_move); // Record the flip move
-                                        println!("Piece flipped.");
-                                        turn_completed = true;
-                                    },
-                                    Ok(None) => println!("No piece to flip here."),
-                                    Err(e) => println!("Error: {}", e),
-                                }
-                            } else if command == "move" && coordinates.len() == 4 {
-                                match move_piece(&mut board, coordinates[0], coordinates[1], coordinates[2], coordinates[3]) {
-                                    Ok(Some(game_move)) => {
-                                        moves_history.push(game_move); // Record the move
-                                        println!("Piece moved.");
-                                        turn_completed = true;
-                                    },
-                                    Ok(None) => println!("Invalid move."),
-                                    Err(e) => println!("Error: {}", e),
-                                }
-                            } else {
-                                println!("Invalid command or number of coordinates.");
-                            }
-                        },
-                        Err(e) => println!("Error parsing input: {}", e),
-                    }
-                }
-            }
+/// Adds a finished game's record to the log directory under a name of its own, made from when
+/// the game started and its seed. Records already in the log are never overwritten.
+fn log_game(dir: &std::path::Path, record: &str, started: u64, seed: u64) -> io::Result<()> {
+    use std::io::Write;
+
+    std::fs::create_dir_all(dir)?;
+    let stem = format!("{}-{}-{}", utc_date(started).replace('.', "-"), utc_time(started).replace(':', ""), seed);
+    let mut attempt = 1;
+    loop {
+        let name = if attempt == 1 { format!("{}.pgn", stem) } else { format!("{}-{}.pgn", stem, attempt) };
+        match std::fs::OpenOptions::new().write(true).create_new(true).open(dir.join(name)) {
+            Ok(mut file) => return file.write_all(record.as_bytes()),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => attempt += 1,
+            Err(e) => return Err(e),
         }
+    }
+}
 
-        if game_over {
-            break;
-        }
+fn unix_time() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
 
-        // Check for game over condition after a valid turn
-        game_over = check_game_over(&board);
+fn utc_time(secs: u64) -> String {
+    format!("{:02}:{:02}:{:02}", secs / 3_600 % 24, secs / 60 % 60, secs % 60)
+}
 
-        // Switch players if the turn was completed successfully and the game isn't over
-        if !game_over {
-            current_player = match current_player {
-                Player::Red => Player::Black,
-                Player::Black => Player::Red,
-            };
+fn utc_date(secs: u64) -> String {
+    // Civil date in UTC from days since the Unix epoch, formatted as PGN expects
+    let days = (secs / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::seq::SliceRandom;
+
+    #[test]
+    fn help_topic_prints_only_that_topic() {
+        let cannon = help_text(Some("cannon"), Language::English).unwrap();
+        assert!(cannon.starts_with("Cannon (砲/炮):"));
+        assert!(cannon.contains("jumping over exactly one piece"));
+        assert!(!cannon.contains("Chariot (車/俥):") && !cannon.contains("Available commands:"));
+
+        assert!(help_text(Some("castling"), Language::English).is_err());
+    }
+
+    #[test]
+    fn help_lists_every_topic() {
+        let index = help_text(None, Language::English).unwrap();
+        for &(name, _, _) in HELP_TOPICS {
+            assert!(index.contains(name), "{} missing from {}", name, index);
         }
+        assert!(!index.contains("Available commands:"));
     }
 
-    // Game is over, either by exit command or natural end
-    println!("Game over. Thanks for playing!");
-}
-
-fn piece_symbols() -> HashMap<(Player, PieceType), &'static str> {
-    use PieceType::*;
-    use Player::*;
-
-    let mut symbols = HashMap::new();
-
-    symbols.insert((Red, General), "帥");
-    symbols.insert((Black, General), "將");
-    symbols.insert((Red, Advisor), "仕");
-    symbols.insert((Black, Advisor), "士");
-    symbols.insert((Red, Elephant), "相");
-    symbols.insert((Black, Elephant), "象");
-    symbols.insert((Red, Chariot), "俥");
-    symbols.insert((Black, Chariot), "車");
-    symbols.insert((Red, Horse), "傌");
-    symbols.insert((Black, Horse), "馬");
-    symbols.insert((Red, Cannon), "炮");
-    symbols.insert((Black, Cannon), "砲");
-    symbols.insert((Red, Soldier), "兵");
-    symbols.insert((Black, Soldier), "卒");
-
-    symbols
-}
-
-fn piece_symbols_eng() -> HashMap<(Player, PieceType), &'static str> {
-    use PieceType::*;
-    use Player::*;
-
-    let mut symbols = HashMap::new();
-
-    symbols.insert((Red, General), "RG");
-    symbols.insert((Black, General), "BG");
-    symbols.insert((Red, Advisor), "RA");
-    symbols.insert((Black, Advisor), "BA");
-    symbols.insert((Red, Elephant), "RE");
-    symbols.insert((Black, Elephant), "BE");
-    symbols.insert((Red, Chariot), "RC");
-    symbols.insert((Black, Chariot), "BC");
-    symbols.insert((Red, Horse), "RH");
-    symbols.insert((Black, Horse), "BH");
-    symbols.insert((Red, Cannon), "RC");
-    symbols.insert((Black, Cannon), "BC");
-    symbols.insert((Red, Soldier), "RS");
-    symbols.insert((Black, Soldier), "BS");
-
-    symbols
-}
-
-fn print_board(board: &Board) {
-    let symbols: HashMap<(Player, PieceType), &str> = piece_symbols(); // Retrieve the symbol mapping
-
-    // Print the column headers
-    print!("   "); // Margin for row labels
-    for x in 0..board[0].len() {
-        print!(" {:^1} ", x); // Adjust to match the cell width
-    }
-    println!();
-
-    // Print the top border of the board
-    print!("  +"); // Start of the top border
-    for _ in 0..board[0].len() {
-        print!("--+"); // Top border for each cell, adjusted for double-width characters
-    }
-    println!();
-
-    for (y, row) in board.iter().enumerate() {
-        // Print the row numbers
-        print!("{:<2}|", y); // Print row labels with space for alignment
-
-        // Print each cell with the appropriate symbol
-        for cell in row {
-            let symbol = match cell {
-                Cell::Hidden(_) => " ?".to_string(),
-                Cell::Revealed(piece) => {
-                    let piece_symbol = symbols.get(&(piece.player, piece.piece_type)).unwrap_or(&" ");
-                    match piece.player {
-                        Player::Red => format!("{}{}{}", RED, piece_symbol, RESET),
-                        Player::Black => piece_symbol.to_string(),
-                    }
-                },
-                Cell::Empty => "  ".to_string(),
-            };
-            print!("{}|", symbol); // Print the cell contents followed by a vertical separator
+    #[test]
+    fn setup_commands_build_the_board_and_hidden_pool() {
+        let mut board = vec![vec![Cell::Empty; BOARD_WIDTH]; BOARD_HEIGHT];
+        let piece = |player, piece_type| Piece { player, piece_type };
+        let commands = [
+            "place a1 red chariot",
+            "hide b1 black horse",
+            "hide c1 black general",
+            "reveal c1",
+            "hide a1",
+            "place d1 red general",
+            "hide a2 black soldier",
+            "remove a2",
+        ];
+        for command in commands {
+            apply_setup_command(&mut board, command, &STANDARD_COMPOSITION).unwrap();
         }
-        println!();
+        assert!(apply_setup_command(&mut board, "hide e1 red general", &STANDARD_COMPOSITION).is_err());
+        assert!(apply_setup_command(&mut board, "reveal e1", &STANDARD_COMPOSITION).is_err());
+
+        assert_eq!(board[0][..5], [
+            Cell::Hidden(Some(piece(Player::Red, PieceType::Chariot))),
+            Cell::Hidden(Some(piece(Player::Black, PieceType::Horse))),
+            Cell::Revealed(piece(Player::Black, PieceType::General)),
+            Cell::Revealed(piece(Player::Red, PieceType::General)),
+            Cell::Empty,
+        ]);
+        assert_eq!(board[1][0], Cell::Empty);
+        assert_eq!(hidden_pieces(&board), vec![piece(Player::Red, PieceType::Chariot), piece(Player::Black, PieceType::Horse)]);
+    }
+
+    #[test]
+    fn clock_times_follow_undo_and_redo_against_a_bot() {
+        let mut rng = StdRng::seed_from_u64(813);
+        let mut game = GameState::with_rng(RuleConfig::default(), &mut rng).unwrap();
+        let seats: [Seat; 2] = [None, Some(Box::new(RandomAgent::new(StdRng::seed_from_u64(813))))];
+        let mut clock = Clock::new(TimeControl::parse("5+3").unwrap());
+        let mut log = ClockLog::new(Some(&clock));
+        // What the game loop does at the top of each turn
+        let turn = |game: &GameState, clock: &mut Clock, log: &mut ClockLog| {
+            log.sync(clock, game);
+            if !game.colors_unassigned() && clock.running() != Some(game.current_player) {
+                clock.start(game.current_player);
+            }
+            assert_eq!(log.times.len(), game.history.len() + 1);
+        };
 
-        // Print the horizontal separator for the board
-        print!("  +"); // Start of the separator
-        for _ in 0..row.len() {
-            print!("--+"); // Separator for each cell, adjusted for double-width characters
+        for _ in 0..4 {
+            let action = *game.legal_actions().choose(&mut rng).unwrap();
+            game.apply_action(action).unwrap();
+            turn(&game, &mut clock, &mut log);
         }
-        println!(); // End the row
+        let played = log.times.clone();
+
+        assert_eq!(take_back(&mut game, &seats).unwrap().len(), 2);
+        turn(&game, &mut clock, &mut log);
+        assert_eq!(log.times, played[..3]);
+
+        // Redoing the player's move and the bot's reply together puts back both plies' times
+        let redone = replay(&mut game, &seats).unwrap();
+        assert_eq!(redone.len(), 2);
+        log.redo(&mut clock, redone.len());
+        assert_eq!(clock.times(), played[4]);
+        turn(&game, &mut clock, &mut log);
+        assert_eq!(log.times, played);
+
+        take_back(&mut game, &seats).unwrap();
+        log.sync(&mut clock, &game);
+        assert_eq!(clock.times(), played[2]);
     }
 }
@@ -0,0 +1,117 @@
+use crate::notation::parse_move_token;
+use crate::ActionType;
+use std::io::{self, Read, Write};
+
+// Network play protocol. Every message is one frame: a 4-byte big-endian length followed by
+// that many bytes of UTF-8 text, the first word of which names the message:
+//   hello <version>     both sides, once, right after connecting
+//   board <fen>         host: the position, hidden pieces written as '?'
+//   info <text>         host: a line to show the player, such as the last move
+//   turn                host: the client should answer with an action
+//   error <text>        host: the client's last action was rejected, a new turn follows
+//   over <text>         host: the game has ended, with the result
+//   action <token>      client: a flip (^x,y) or move (x,y-x,y)
+//   resign              client: giving up, the host then ends the game
+//   draw                either side: offering a draw, on the client's turn or while the host has it
+//   accept / decline    either side: the answer to a draw offer
+//   quit                either side: leaving the game
+// The host keeps the only full copy of the board, so the client never learns a hidden piece
+// before it is flipped.
+
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Longest frame either side will accept; a board fits many times over.
+pub const MAX_FRAME_LEN: usize = 64 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Hello(u32),
+    Board(String),
+    Info(String),
+    Turn,
+    Error(String),
+    GameOver(String),
+    Action(ActionType),
+    Resign,
+    OfferDraw,
+    AcceptDraw,
+    DeclineDraw,
+    Quit,
+}
+
+impl Message {
+    pub fn encode(&self) -> String {
+        match self {
+            Message::Hello(version) => format!("hello {}", version),
+            Message::Board(fen) => format!("board {}", fen),
+            Message::Info(text) => format!("info {}", text),
+            Message::Turn => String::from("turn"),
+            Message::Error(text) => format!("error {}", text),
+            Message::GameOver(text) => format!("over {}", text),
+            Message::Action(ActionType::Flip { x, y }) => format!("action ^{},{}", x, y),
+            Message::Action(ActionType::Move { from_x, from_y, to_x, to_y }) => format!("action {},{}-{},{}", from_x, from_y, to_x, to_y),
+            Message::Resign => String::from("resign"),
+            Message::OfferDraw => String::from("draw"),
+            Message::AcceptDraw => String::from("accept"),
+            Message::DeclineDraw => String::from("decline"),
+            Message::Quit => String::from("quit"),
+        }
+    }
+
+    pub fn decode(text: &str) -> Result<Message, &'static str> {
+        let (kind, rest) = text.split_once(' ').unwrap_or((text, ""));
+        match kind {
+            "hello" => rest.trim().parse().map(Message::Hello).map_err(|_| "Invalid protocol version."),
+            "board" => Ok(Message::Board(rest.to_string())),
+            "info" => Ok(Message::Info(rest.to_string())),
+            "turn" => Ok(Message::Turn),
+            "error" => Ok(Message::Error(rest.to_string())),
+            "over" => Ok(Message::GameOver(rest.to_string())),
+            "action" => parse_move_token(rest.trim()).map(Message::Action),
+            "resign" => Ok(Message::Resign),
+            "draw" => Ok(Message::OfferDraw),
+            "accept" => Ok(Message::AcceptDraw),
+            "decline" => Ok(Message::DeclineDraw),
+            "quit" => Ok(Message::Quit),
+            _ => Err("Unknown message."),
+        }
+    }
+}
+
+pub fn write_frame<W: Write>(writer: &mut W, payload: &str) -> io::Result<()> {
+    if payload.len() > MAX_FRAME_LEN {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "message too long"));
+    }
+    writer.write_all(&(payload.len() as u32).to_be_bytes())?;
+    writer.write_all(payload.as_bytes())?;
+    writer.flush()
+}
+
+/// Reads one frame, or returns `None` if the connection closed cleanly before it started.
+pub fn read_frame<R: Read>(reader: &mut R) -> io::Result<Option<String>> {
+    let mut length = [0u8; 4];
+    match reader.read_exact(&mut length) {
+        Ok(()) => {},
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let length = u32::from_be_bytes(length) as usize;
+    if length > MAX_FRAME_LEN {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "message too long"));
+    }
+    let mut payload = vec![0u8; length];
+    reader.read_exact(&mut payload)?;
+    String::from_utf8(payload).map(Some).map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "message is not UTF-8"))
+}
+
+pub fn send<W: Write>(writer: &mut W, message: &Message) -> io::Result<()> {
+    write_frame(writer, &message.encode())
+}
+
+/// Reads and decodes one message; a frame that isn't a valid message is an `InvalidData` error.
+pub fn receive<R: Read>(reader: &mut R) -> io::Result<Option<Message>> {
+    match read_frame(reader)? {
+        Some(text) => Message::decode(&text).map(Some).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        None => Ok(None),
+    }
+}
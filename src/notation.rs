@@ -0,0 +1,469 @@
+use crate::clock::{parse_clock, Clock, TimeControl};
+use crate::*;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+// Position notation, one field per part separated by a space:
+//   rows     top row first, separated by '/'. Red pieces are upper case and Black lower case
+//            (K general, A advisor, E elephant, R chariot, H horse, C cannon, P soldier),
+//            digits count empty squares, '?' is a hidden square and '*K' a hidden square
+//            whose contents are recorded.
+//   to move  'r' or 'b', or '-' before the first flip has decided the colors.
+// A new game before the first flip is "????????/????????/????????/???????? -".
+
+pub const FEN_UNASSIGNED: char = '-';
+// Widest row a position may describe; rows after the first must match the first's width
+pub const MAX_ROW_SQUARES: usize = 64;
+
+pub fn piece_letter(piece: Piece) -> char {
+    let letter = match piece.piece_type {
+        PieceType::General => 'K',
+        PieceType::Advisor => 'A',
+        PieceType::Elephant => 'E',
+        PieceType::Chariot => 'R',
+        PieceType::Horse => 'H',
+        PieceType::Cannon => 'C',
+        PieceType::Soldier => 'P',
+    };
+    match piece.player {
+        Player::Red => letter,
+        Player::Black => letter.to_ascii_lowercase(),
+    }
+}
+
+pub fn parse_piece_letter(letter: char) -> Option<Piece> {
+    let piece_type = match letter.to_ascii_uppercase() {
+        'K' => PieceType::General,
+        'A' => PieceType::Advisor,
+        'E' => PieceType::Elephant,
+        'R' => PieceType::Chariot,
+        'H' => PieceType::Horse,
+        'C' => PieceType::Cannon,
+        'P' => PieceType::Soldier,
+        _ => return None,
+    };
+    let player = if letter.is_ascii_uppercase() { Player::Red } else { Player::Black };
+    Some(Piece { piece_type, player })
+}
+
+/// Reads a piece set such as "K1A2E2R2H2C2P5": each piece letter, followed by how many of that
+/// piece each player gets. Types left out get none, and G and S may stand for the general and
+/// soldier. Both sets together must fit on the board.
+pub fn parse_composition(text: &str) -> Result<Composition, &'static str> {
+    let mut composition = STANDARD_COMPOSITION.map(|(piece_type, _)| (piece_type, 0));
+    let mut seen = Vec::new();
+    let mut chars = text.trim().chars().peekable();
+    while let Some(letter) = chars.next() {
+        let piece_type = match letter.to_ascii_uppercase() {
+            'G' => PieceType::General,
+            'S' => PieceType::Soldier,
+            other => parse_piece_letter(other).ok_or("Unknown piece letter in the piece set.")?.piece_type,
+        };
+        if seen.contains(&piece_type) {
+            return Err("A piece type appears twice in the piece set.");
+        }
+        seen.push(piece_type);
+
+        let mut digits = String::new();
+        while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+            digits.push(digit);
+        }
+        let count = digits.parse::<usize>().map_err(|_| "Every piece letter needs a count after it, e.g. P5.")?;
+        let slot = composition.iter_mut().find(|(t, _)| *t == piece_type).expect("Every piece type is in the composition.");
+        slot.1 = count;
+    }
+
+    let per_player: usize = composition.iter().map(|&(_, count)| count).sum();
+    if per_player == 0 {
+        return Err("The piece set has no pieces.");
+    }
+    if 2 * per_player > BOARD_WIDTH * BOARD_HEIGHT {
+        return Err("Too many pieces for the board.");
+    }
+    Ok(composition)
+}
+
+/// The position in a position file: the first line that isn't blank or a `#` comment, written
+/// in the notation above. The comments can say what the position is for.
+pub fn parse_position_file(text: &str) -> Result<&str, &'static str> {
+    let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#'));
+    let position = lines.next().ok_or("The position file has no position in it.")?;
+    if lines.next().is_some() {
+        return Err("The position file has more than one position in it.");
+    }
+    parse_fen(position)?;
+    Ok(position)
+}
+
+pub fn export_fen(board: &Board, to_move: Option<Player>, include_hidden: bool) -> String {
+    let rows: Vec<String> = board
+        .iter()
+        .map(|row| {
+            let mut text = String::new();
+            let mut empty_run = 0;
+            for cell in row {
+                if let Cell::Empty = cell {
+                    empty_run += 1;
+                    continue;
+                }
+                if empty_run > 0 {
+                    text.push_str(&empty_run.to_string());
+                    empty_run = 0;
+                }
+                match cell {
+                    Cell::Revealed(piece) => text.push(piece_letter(*piece)),
+                    // Hidden contents are only written for trusted saves, never for positions shared with a player
+                    Cell::Hidden(Some(piece)) if include_hidden => {
+                        text.push('*');
+                        text.push(piece_letter(*piece));
+                    },
+                    Cell::Hidden(_) => text.push('?'),
+                    Cell::Empty => {},
+                }
+            }
+            if empty_run > 0 {
+                text.push_str(&empty_run.to_string());
+            }
+            text
+        })
+        .collect();
+
+    let side = match to_move {
+        Some(Player::Red) => 'r',
+        Some(Player::Black) => 'b',
+        None => FEN_UNASSIGNED,
+    };
+    format!("{} {}", rows.join("/"), side)
+}
+
+// Hidden squares written as '?' parse to Hidden(None); deal_unknown_hidden fills them before play
+pub fn parse_fen(fen: &str) -> Result<(Board, Option<Player>), &'static str> {
+    let mut fields = fen.split_whitespace();
+    let rows_field = fields.next().ok_or("Empty position string.")?;
+    let to_move = match fields.next() {
+        Some("r") => Some(Player::Red),
+        Some("b") => Some(Player::Black),
+        Some("-") => None,
+        Some(_) => return Err("Side to move must be 'r', 'b' or '-'."),
+        None => return Err("Missing side to move."),
+    };
+    if fields.next().is_some() {
+        return Err("Unexpected text after the side to move.");
+    }
+
+    let mut board = Board::new();
+    for row_text in rows_field.split('/') {
+        let width = board.first().map_or(MAX_ROW_SQUARES, Vec::len);
+        let mut row = Vec::new();
+        let mut chars = row_text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '1'..='9' => {
+                    // Digits may run together for runs longer than nine squares
+                    let mut run = c.to_digit(10).unwrap_or(0) as usize;
+                    while let Some(digit) = chars.peek().and_then(|d| d.to_digit(10)) {
+                        run = run.checked_mul(10).and_then(|run| run.checked_add(digit as usize)).ok_or("Run of empty squares is longer than the row.")?;
+                        chars.next();
+                    }
+                    if row.len() + run > width {
+                        return Err("Run of empty squares is longer than the row.");
+                    }
+                    row.extend(std::iter::repeat_n(Cell::Empty, run));
+                },
+                '?' => row.push(Cell::Hidden(None)),
+                '*' => {
+                    let piece = chars.next().and_then(parse_piece_letter).ok_or("'*' must be followed by a piece letter.")?;
+                    row.push(Cell::Hidden(Some(piece)));
+                },
+                _ => row.push(Cell::Revealed(parse_piece_letter(c).ok_or("Unknown piece letter in position.")?)),
+            }
+        }
+        board.push(row);
+    }
+
+    let width = board.first().map_or(0, |row| row.len());
+    if width == 0 || board.iter().any(|row| row.len() != width) {
+        return Err("Every row of the position must have the same number of squares.");
+    }
+    Ok((board, to_move))
+}
+
+pub fn deal_unknown_hidden<R: Rng + ?Sized>(board: &mut Board, composition: &Composition, rng: &mut R) -> Result<(), &'static str> {
+    // Hidden squares without recorded contents get pieces from the set in play that aren't already on the board
+    let mut pool = Vec::new();
+    for &player in &[Player::Red, Player::Black] {
+        for &(piece_type, count) in composition {
+            let on_board = board
+                .iter()
+                .flatten()
+                .filter(|cell| matches!(cell, Cell::Hidden(Some(p)) | Cell::Revealed(p) if p.player == player && p.piece_type == piece_type))
+                .count();
+            pool.extend((on_board..count).map(|_| Piece { piece_type, player }));
+        }
+    }
+    pool.shuffle(rng);
+
+    for cell in board.iter_mut().flatten() {
+        if let Cell::Hidden(None) = cell {
+            *cell = Cell::Hidden(Some(pool.pop().ok_or("More hidden squares than pieces left to hide.")?));
+        }
+    }
+    Ok(())
+}
+
+// Algebraic squares name the column by letter and the row by number, counting from a1 at the
+// top left, so "c2" is the square x 2, y 1. They can stand in for "x y" wherever squares are typed.
+
+pub fn square_name(x: usize, y: usize) -> String {
+    let file = (b'a' + (x % 26) as u8) as char;
+    format!("{}{}", file, y + 1)
+}
+
+pub fn parse_square_name(text: &str) -> Option<(usize, usize)> {
+    let mut chars = text.chars();
+    let file = chars.next()?.to_ascii_lowercase();
+    if !file.is_ascii_lowercase() {
+        return None;
+    }
+    let rank = chars.as_str().parse::<usize>().ok().filter(|&rank| rank >= 1)?;
+    Some((file as usize - 'a' as usize, rank - 1))
+}
+
+// Game records follow PGN: `[Name "value"]` headers, a blank line, then numbered move pairs
+// ending in the result. Squares are written x,y as on the board. Flips name the revealed
+// piece (^3,1=K), moves join the squares with '-' (0,0-0,1) and captures with 'x', naming
+// the captured piece and marking it '*' if it was still face down (2,0x2,2=*p). The Setup
+// header holds the starting position with its hidden contents so the game can be replayed.
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GameRecord {
+    pub headers: Vec<(String, String)>,
+    pub actions: Vec<ActionType>,
+    pub result: String, // Result token: 1-0, 0-1, 1/2-1/2, or * for an unfinished game
+}
+
+impl GameRecord {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+
+    /// Rebuilds the starting position from the Setup header, or failing that the Seed header.
+    pub fn start_position(&self, rules: RuleConfig) -> Result<GameState, &'static str> {
+        match (self.header("Setup"), self.header("Seed")) {
+            (Some(fen), _) => GameState::from_fen(fen, rules, &mut rand::thread_rng()).map_err(GameError::message),
+            (None, Some(seed)) => {
+                let seed = seed.parse::<u64>().map_err(|_| "Invalid Seed header.")?;
+                GameState::with_rng(rules, &mut StdRng::seed_from_u64(seed)).map_err(GameError::message)
+            },
+            (None, None) => Err("The record has neither a Setup nor a Seed header."),
+        }
+    }
+
+    /// The clock as the game was saved, from the TimeControl, RedClock and BlackClock headers, or
+    /// `None` for an untimed game. A side without a clock header starts from the base time.
+    pub fn clock(&self) -> Result<Option<Clock>, &'static str> {
+        let Some(control) = self.header("TimeControl") else { return Ok(None) };
+        let mut clock = Clock::new(TimeControl::from_pgn(control)?);
+        let time = |name| self.header(name).map_or(Ok(clock.control.base), |text| parse_clock(text).ok_or("Invalid clock header."));
+        let times = [time("RedClock")?, time("BlackClock")?];
+        clock.set_times(times);
+        Ok(Some(clock))
+    }
+
+    /// Plays every recorded action from the starting position. A game that ended off the board,
+    /// by agreement, resignation or on time, ends the same way again.
+    pub fn to_game(&self, rules: RuleConfig) -> Result<GameState, &'static str> {
+        let mut game = self.start_position(rules)?;
+        for &action in &self.actions {
+            game.apply_action(action).map_err(|_| "The record contains an illegal action.")?;
+        }
+        if game.result().is_none() {
+            game.adjudicated = match (self.header("Termination"), self.result.as_str()) {
+                (Some("draw agreed"), "1/2-1/2") => Some(GameResult::Draw(DrawReason::Agreement)),
+                (Some("resignation" | "time forfeit"), "1-0") => Some(GameResult::Winner(Player::Red)),
+                (Some("resignation" | "time forfeit"), "0-1") => Some(GameResult::Winner(Player::Black)),
+                _ => None,
+            };
+        }
+        Ok(game)
+    }
+}
+
+pub fn result_token(result: Option<GameResult>) -> &'static str {
+    match result {
+        Some(GameResult::Winner(Player::Red)) => "1-0",
+        Some(GameResult::Winner(Player::Black)) => "0-1",
+        Some(GameResult::Draw(_)) => "1/2-1/2",
+        None => "*",
+    }
+}
+
+pub fn move_token(game_move: &GameMove) -> String {
+    let letter = |piece: Option<Piece>| piece.map_or(String::new(), |piece| piece_letter(piece).to_string());
+
+    match game_move.action_type {
+        ActionType::Flip { x, y } => format!("^{},{}={}", x, y, letter(game_move.piece)),
+        ActionType::Move { from_x, from_y, to_x, to_y } => match game_move.captured_piece {
+            Some(captured) => {
+                let hidden_marker = if game_move.captured_hidden { "*" } else { "" };
+                format!("{},{}x{},{}={}{}", from_x, from_y, to_x, to_y, hidden_marker, letter(Some(captured)))
+            },
+            // A voided cannon capture is written like a hidden capture of the mover's own piece
+            None => match game_move.revealed_own {
+                Some(own) => format!("{},{}x{},{}=*{}", from_x, from_y, to_x, to_y, letter(Some(own))),
+                None => format!("{},{}-{},{}", from_x, from_y, to_x, to_y),
+            },
+        },
+    }
+}
+
+pub fn parse_move_token(token: &str) -> Result<ActionType, &'static str> {
+    // The '=' suffix only annotates the piece involved; the squares alone determine the action
+    let squares = token.split('=').next().unwrap_or(token);
+    let parse_square = |text: &str| -> Result<(usize, usize), &'static str> {
+        let (x, y) = text.split_once(',').ok_or("Squares must be written x,y.")?;
+        Ok((x.parse().map_err(|_| "Invalid square in move.")?, y.parse().map_err(|_| "Invalid square in move.")?))
+    };
+
+    if let Some(square) = squares.strip_prefix('^') {
+        let (x, y) = parse_square(square)?;
+        return Ok(ActionType::Flip { x, y });
+    }
+    let (from, to) = squares.split_once(['-', 'x']).ok_or("Unrecognized move.")?;
+    let ((from_x, from_y), (to_x, to_y)) = (parse_square(from)?, parse_square(to)?);
+    Ok(ActionType::Move { from_x, from_y, to_x, to_y })
+}
+
+pub fn export_record(game: &GameState, headers: &[(&str, String)]) -> String {
+    // Taking every action back recovers the starting position, hidden pieces included
+    let mut start = game.clone();
+    while start.undo().is_ok() {}
+
+    let result = result_token(game.result());
+    let mut out = String::new();
+    for (name, value) in headers {
+        out.push_str(&format!("[{} \"{}\"]\n", name, value));
+    }
+    out.push_str(&format!("[Setup \"{}\"]\n[Result \"{}\"]\n\n", start.to_fen(true), result));
+
+    let mut line = String::new();
+    for (index, game_move) in game.history.iter().enumerate() {
+        let mut token = move_token(game_move);
+        if index % 2 == 0 {
+            token = format!("{}. {}", index / 2 + 1, token);
+        }
+        if !line.is_empty() && line.len() + token.len() + 1 > 80 {
+            out.push_str(&line);
+            out.push('\n');
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&token);
+    }
+    if !line.is_empty() {
+        line.push(' ');
+    }
+    line.push_str(result);
+    out.push_str(&line);
+    out.push('\n');
+    out
+}
+
+pub fn parse_record(text: &str) -> Result<GameRecord, &'static str> {
+    let mut record = GameRecord::default();
+    let mut body = String::new();
+
+    for line in text.lines().map(str::trim) {
+        if let Some(header) = line.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+            let (name, value) = header.split_once(' ').ok_or("Malformed header.")?;
+            let value = value.trim().strip_prefix('"').and_then(|v| v.strip_suffix('"')).ok_or("Header values must be quoted.")?;
+            record.headers.push((name.to_string(), value.to_string()));
+        } else {
+            body.push_str(line);
+            body.push(' ');
+        }
+    }
+
+    for token in body.split_whitespace() {
+        // Move numbers are only there for readers
+        if token.strip_suffix('.').is_some_and(|number| number.parse::<usize>().is_ok()) {
+            continue;
+        }
+        match token {
+            "1-0" | "0-1" | "1/2-1/2" | "*" => record.result = token.to_string(),
+            _ => record.actions.push(parse_move_token(token)?),
+        }
+    }
+    if record.result.is_empty() {
+        record.result = record.header("Result").unwrap_or("*").to_string();
+    }
+    Ok(record)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn parse_fen_rejects_runs_longer_than_the_row() {
+        assert!(parse_fen("9999999999999999999999/8/8/8 r").is_err());
+        assert!(parse_fen("9999999999/8/8/8 r").is_err());
+        assert!(parse_fen("8/9/8/8 r").is_err());
+        assert!(parse_fen("8/4K4/8/8 r").is_err());
+    }
+
+    #[test]
+    fn parse_fen_reads_multi_digit_runs() {
+        let (board, to_move) = parse_fen("12/11k/12/12 b").unwrap();
+        assert_eq!(to_move, Some(Player::Black));
+        assert!(board.iter().all(|row| row.len() == 12));
+        assert_eq!(board[1][11], Cell::Revealed(Piece { player: Player::Black, piece_type: PieceType::General }));
+    }
+
+    #[test]
+    fn records_mark_flips_and_not_moves() {
+        let mut game = GameState::from_fen("Rp5k/8/8/5*h2 r", RuleConfig::default(), &mut StdRng::seed_from_u64(703)).unwrap();
+        game.apply_action(ActionType::Move { from_x: 0, from_y: 0, to_x: 1, to_y: 0 }).unwrap();
+        game.apply_action(ActionType::Flip { x: 5, y: 3 }).unwrap();
+        let (capture, flip) = (move_token(&game.history[0]), move_token(&game.history[1]));
+        assert_eq!(capture, "0,0x1,0=p");
+        assert_eq!(flip, "^5,3=h");
+        assert!(export_record(&game, &[]).contains(&format!("1. {} {}", capture, flip)));
+    }
+
+    #[test]
+    fn record_clock_comes_from_its_headers() {
+        let timed = parse_record("[Seed \"7\"]\n[TimeControl \"300+3\"]\n[RedClock \"4:12\"]\n[BlackClock \"0:07.3\"]\n\n*").unwrap();
+        let clock = timed.clock().unwrap().unwrap();
+        assert_eq!(clock.control.to_pgn(), "300+3");
+        assert_eq!(clock.times(), [std::time::Duration::from_secs(252), std::time::Duration::from_millis(7300)]);
+        assert_eq!(clock.running(), None);
+
+        assert!(parse_record("[Seed \"7\"]\n\n*").unwrap().clock().unwrap().is_none());
+        assert!(parse_record("[TimeControl \"300+3\"]\n[RedClock \"soon\"]\n\n*").unwrap().clock().is_err());
+    }
+
+    #[test]
+    fn replayed_records_keep_results_decided_off_the_board() {
+        let mut game = GameState::from_fen("Rp5k/8/8/5*h2 r", RuleConfig::default(), &mut StdRng::seed_from_u64(790)).unwrap();
+        game.apply_action(ActionType::Move { from_x: 0, from_y: 0, to_x: 1, to_y: 0 }).unwrap();
+        game.agree_draw().unwrap();
+
+        let text = export_record(&game, &[("Termination", String::from("draw agreed"))]);
+        let replayed = parse_record(&text).unwrap().to_game(RuleConfig::default()).unwrap();
+        assert_eq!(replayed.result(), Some(GameResult::Draw(DrawReason::Agreement)));
+        assert_eq!(replayed.history, game.history);
+
+        let resigned = text.replace("draw agreed", "resignation").replace("1/2-1/2", "0-1");
+        assert_eq!(parse_record(&resigned).unwrap().to_game(RuleConfig::default()).unwrap().result(), Some(GameResult::Winner(Player::Black)));
+
+        // Without a termination the unfinished position stays unfinished
+        let unexplained = text.replace("[Termination \"draw agreed\"]\n", "");
+        assert_eq!(parse_record(&unexplained).unwrap().to_game(RuleConfig::default()).unwrap().result(), None);
+    }
+}
@@ -0,0 +1,201 @@
+use crate::display::piece_type_name;
+use crate::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Rating a new profile starts from.
+pub const INITIAL_RATING: f64 = 1500.0;
+
+/// Largest rating change a single game can make.
+pub const K_FACTOR: f64 = 32.0;
+
+/// One named player's rating and lifetime statistics.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub rating: f64,
+    pub games: u32,
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+    pub games_as_red: u32,
+    pub wins_as_red: u32,
+    pub games_as_black: u32,
+    pub wins_as_black: u32,
+    pub plies: u32, // Length of all their games together
+    pub flips: u32,
+    pub captures: BTreeMap<String, u32>, // Pieces they captured, by type name
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Profile {
+            rating: INITIAL_RATING,
+            games: 0,
+            wins: 0,
+            losses: 0,
+            draws: 0,
+            games_as_red: 0,
+            wins_as_red: 0,
+            games_as_black: 0,
+            wins_as_black: 0,
+            plies: 0,
+            flips: 0,
+            captures: BTreeMap::new(),
+        }
+    }
+}
+
+impl Profile {
+    /// Adds a finished game, played as `color`, to the statistics. Ratings and the overall
+    /// tally are updated by `Profiles::record_game`.
+    pub fn record_stats(&mut self, color: Player, game: &GameState) {
+        let won = game.result() == Some(GameResult::Winner(color));
+        let (games, wins) = match color {
+            Player::Red => (&mut self.games_as_red, &mut self.wins_as_red),
+            Player::Black => (&mut self.games_as_black, &mut self.wins_as_black),
+        };
+        *games += 1;
+        *wins += u32::from(won);
+        self.plies += game.history.len() as u32;
+        if let PlayerColor::Assigned(first) = game.first_player {
+            self.flips += flip_count(&game.history, first, color) as u32;
+        }
+        for (piece_type, count) in capture_tally(&game.history, color) {
+            *self.captures.entry(piece_type_name(piece_type, 1).to_lowercase()).or_default() += count as u32;
+        }
+    }
+
+    /// The piece type they have captured most often, with the count.
+    pub fn most_captured(&self) -> Option<(&str, u32)> {
+        self.captures.iter().max_by_key(|(_, &count)| count).map(|(name, &count)| (name.as_str(), count))
+    }
+}
+
+/// Every profile, keyed by name, as stored in the profiles file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profiles {
+    pub players: BTreeMap<String, Profile>,
+}
+
+/// The chance of `rating` beating `opponent_rating`, counting a draw as half a win.
+pub fn expected_score(rating: f64, opponent_rating: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf((opponent_rating - rating) / 400.0))
+}
+
+impl Profiles {
+    /// Reads the profiles file; a missing file is an empty table.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => toml::from_str(&text).map_err(|e| format!("invalid profiles file {}: {}", path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Profiles::default()),
+            Err(e) => Err(format!("could not read {}: {}", path.display(), e)),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(|e| format!("could not create {}: {}", dir.display(), e))?;
+        }
+        let text = toml::to_string(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, text).map_err(|e| format!("could not write {}: {}", path.display(), e))
+    }
+
+    /// Updates both players' ratings and tallies for a finished game, creating profiles as
+    /// needed, and returns the rating changes for Red and Black.
+    pub fn record_game(&mut self, red: &str, black: &str, result: GameResult) -> (f64, f64) {
+        let red_score = match result {
+            GameResult::Winner(Player::Red) => 1.0,
+            GameResult::Winner(Player::Black) => 0.0,
+            GameResult::Draw(_) => 0.5,
+        };
+        // Both changes use the ratings from before the game
+        let red_rating = self.players.get(red).map_or(INITIAL_RATING, |profile| profile.rating);
+        let black_rating = self.players.get(black).map_or(INITIAL_RATING, |profile| profile.rating);
+        let red_change = K_FACTOR * (red_score - expected_score(red_rating, black_rating));
+        let black_change = K_FACTOR * ((1.0 - red_score) - expected_score(black_rating, red_rating));
+
+        for (name, change, score) in [(red, red_change, red_score), (black, black_change, 1.0 - red_score)] {
+            let profile = self.players.entry(name.to_string()).or_default();
+            profile.rating += change;
+            profile.games += 1;
+            match score {
+                1.0 => profile.wins += 1,
+                0.0 => profile.losses += 1,
+                _ => profile.draws += 1,
+            }
+        }
+        (red_change, black_change)
+    }
+
+    /// Profiles from the highest rating down.
+    pub fn ranked(&self) -> Vec<(&String, &Profile)> {
+        let mut ranked: Vec<_> = self.players.iter().collect();
+        ranked.sort_by(|a, b| b.1.rating.total_cmp(&a.1.rating).then_with(|| a.0.cmp(b.0)));
+        ranked
+    }
+}
+
+/// The ratings table printed by the `ratings` command.
+pub fn format_ratings(profiles: &Profiles) -> String {
+    if profiles.players.is_empty() {
+        return String::from("No rated games yet. Name the players with --player to rate their games.");
+    }
+    let mut lines = vec![format!("{:>4}  {:<20} {:>6} {:>6} {:>5} {:>5} {:>5}", "Rank", "Player", "Rating", "Games", "Won", "Lost", "Drawn")];
+    for (rank, (name, profile)) in profiles.ranked().into_iter().enumerate() {
+        lines.push(format!(
+            "{:>4}  {:<20} {:>6.0} {:>6} {:>5} {:>5} {:>5}",
+            rank + 1,
+            name,
+            profile.rating,
+            profile.games,
+            profile.wins,
+            profile.losses,
+            profile.draws
+        ));
+    }
+    lines.join("\n")
+}
+
+/// Loads the profiles file, records a finished game between `red` and `black` in both
+/// players' ratings and statistics, saves it, and describes the new ratings, e.g.
+/// "Alice 1516 (+16), Bob 1484 (-16)".
+pub fn rate_game(path: &Path, red: &str, black: &str, game: &GameState) -> Result<String, String> {
+    let result = game.result().ok_or("the game isn't over")?;
+    let mut profiles = Profiles::load(path)?;
+    let (red_change, black_change) = profiles.record_game(red, black, result);
+    for (name, color) in [(red, Player::Red), (black, Player::Black)] {
+        profiles.players.entry(name.to_string()).or_default().record_stats(color, game);
+    }
+    profiles.save(path)?;
+    let describe = |name: &str, change: f64| format!("{} {:.0} ({:+.0})", name, profiles.players[name].rating, change);
+    Ok(format!("{}, {}", describe(red, red_change), describe(black, black_change)))
+}
+
+fn percent(part: u32, whole: u32) -> String {
+    if whole == 0 {
+        String::from("-")
+    } else {
+        format!("{:.0}%", 100.0 * f64::from(part) / f64::from(whole))
+    }
+}
+
+/// The summary printed by the `stats` command for one player.
+pub fn format_stats(name: &str, profile: &Profile) -> String {
+    let per_game = |total: u32| if profile.games == 0 { 0.0 } else { f64::from(total) / f64::from(profile.games) };
+    let captured: u32 = profile.captures.values().sum();
+    let most_captured = match profile.most_captured() {
+        Some((piece, count)) => format!(", most often {} ({})", piece, count),
+        None => String::new(),
+    };
+    [
+        format!("{} (rating {:.0})", name, profile.rating),
+        format!("  Games: {}, won {}, lost {}, drawn {} ({} won)", profile.games, profile.wins, profile.losses, profile.draws, percent(profile.wins, profile.games)),
+        format!("  As Red: {} games, {} won. As Black: {} games, {} won.", profile.games_as_red, percent(profile.wins_as_red, profile.games_as_red), profile.games_as_black, percent(profile.wins_as_black, profile.games_as_black)),
+        format!("  Average game length: {:.1} plies. Flips per game: {:.1}.", per_game(profile.plies), per_game(profile.flips)),
+        format!("  Captures: {}{}", captured, most_captured),
+    ]
+    .join("\n")
+}
@@ -0,0 +1,199 @@
+use crate::ui::{confirm, tell, Renderer};
+use crate::{parse_action, parse_input};
+use rust_dark_chess::ai::{Agent, Decision};
+use rust_dark_chess::display::*;
+use rust_dark_chess::locale::Language;
+use rust_dark_chess::net::*;
+use rust_dark_chess::notation::parse_fen;
+use rust_dark_chess::*;
+use std::io;
+use std::net::{TcpListener, TcpStream};
+
+/// The player who joined a `serve` game. The host asks them for an action whenever it is their
+/// turn, and checks it against its own board before playing it.
+pub struct RemotePlayer {
+    stream: TcpStream,
+    style: CoordinateStyle,
+    reported: usize, // Moves in the history the client has already been told about
+}
+
+/// Waits on `port` for one player to join and checks they speak the same protocol.
+pub fn accept(port: u16, style: CoordinateStyle) -> io::Result<RemotePlayer> {
+    let listener = TcpListener::bind(("0.0.0.0", port))?;
+    println!("Waiting for a player to join on port {}...", port);
+    let (mut stream, peer) = listener.accept()?;
+    handshake(&mut stream)?;
+    println!("{} joined the game.", peer);
+    Ok(RemotePlayer { stream, style, reported: 0 })
+}
+
+fn handshake(stream: &mut TcpStream) -> io::Result<()> {
+    send(stream, &Message::Hello(PROTOCOL_VERSION))?;
+    match receive(stream)? {
+        Some(Message::Hello(version)) if version == PROTOCOL_VERSION => Ok(()),
+        Some(Message::Hello(version)) => Err(io::Error::other(format!("the other side speaks protocol version {}, not {}", version, PROTOCOL_VERSION))),
+        _ => Err(io::Error::other("the other side is not a dark chess game")),
+    }
+}
+
+impl RemotePlayer {
+    fn update(&mut self, game: &GameState) -> io::Result<()> {
+        // Describe every move since the last update, then send the position as the players see it
+        for game_move in &game.history[self.reported.min(game.history.len())..] {
+            send(&mut self.stream, &Message::Info(describe_move(game_move, self.style)))?;
+        }
+        self.reported = game.history.len();
+        send(&mut self.stream, &Message::Board(game.to_fen(false)))
+    }
+}
+
+impl Agent for RemotePlayer {
+    fn name(&self) -> &str {
+        "Remote player"
+    }
+
+    fn choose_action(&mut self, game: &GameState) -> Option<ActionType> {
+        match self.decide(game) {
+            Decision::Play(action) => Some(action),
+            Decision::Resign | Decision::OfferDraw | Decision::Leave => None,
+        }
+    }
+
+    fn decide(&mut self, game: &GameState) -> Decision {
+        if self.update(game).is_err() {
+            return Decision::Leave;
+        }
+        loop {
+            if send(&mut self.stream, &Message::Turn).is_err() {
+                return Decision::Leave;
+            }
+            let rejection = match receive(&mut self.stream) {
+                // Try the action on a copy so a rejected one leaves the game untouched
+                Ok(Some(Message::Action(action))) => match game.clone().apply_action(action) {
+                    Ok(_) => return Decision::Play(action),
+                    Err(e) => e.to_string(),
+                },
+                Ok(Some(Message::Resign)) if !game.colors_unassigned() => return Decision::Resign,
+                Ok(Some(Message::Resign)) => String::from("Nobody has a color to resign yet."),
+                Ok(Some(Message::OfferDraw)) => return Decision::OfferDraw,
+                Ok(Some(Message::Quit) | None) => return Decision::Leave,
+                Ok(Some(_)) => String::from("Expected an action."),
+                Err(e) if e.kind() == io::ErrorKind::InvalidData => e.to_string(),
+                Err(_) => return Decision::Leave,
+            };
+            if send(&mut self.stream, &Message::Error(rejection)).is_err() {
+                return Decision::Leave;
+            }
+        }
+    }
+
+    fn accept_draw(&mut self, _game: &GameState) -> bool {
+        if send(&mut self.stream, &Message::OfferDraw).is_err() {
+            return false;
+        }
+        // Anything but an answer, such as the player leaving, counts as declining
+        matches!(receive(&mut self.stream), Ok(Some(Message::AcceptDraw)))
+    }
+
+    fn draw_declined(&mut self) {
+        send(&mut self.stream, &Message::Info(String::from("The host declined your draw offer."))).ok();
+    }
+
+    fn game_over(&mut self, game: &GameState, termination: Option<&str>) {
+        let text = game.result().map_or(String::from("The host ended the game."), |result| describe_ending(result, termination));
+        self.update(game).and_then(|()| send(&mut self.stream, &Message::GameOver(text))).ok();
+    }
+}
+
+/// Joins a game hosted with `serve`, showing the host's updates and sending the player's actions.
+pub fn run_client(address: &str, ui: &mut dyn Renderer, language: Language) {
+    let mut stream = match TcpStream::connect(address).and_then(|mut stream| handshake(&mut stream).map(|()| stream)) {
+        Ok(stream) => stream,
+        Err(e) => {
+            tell!(ui, language, "Error: could not join {}: {}", address, e);
+            return;
+        },
+    };
+    tell!(ui, language, "Joined the game at {}. Waiting for the host to move...", address);
+
+    // Side to move in the last position received, for the prompt
+    let mut to_move = None;
+    loop {
+        match receive(&mut stream) {
+            Ok(Some(Message::Board(fen))) => match parse_fen(&fen) {
+                Ok((board, side)) => {
+                    ui.show_board(&board, &[]);
+                    to_move = side;
+                },
+                Err(e) => tell!(ui, language, "Error: the host sent an invalid position: {}", language.tr(e)),
+            },
+            Ok(Some(Message::Info(text))) => ui.message(&text),
+            Ok(Some(Message::Error(text))) => tell!(ui, language, "Error: {}", text),
+            Ok(Some(Message::Turn)) => {
+                let message = read_action(ui, to_move, language);
+                if let Err(e) = send(&mut stream, &message) {
+                    tell!(ui, language, "Error: connection lost: {}", e);
+                    break;
+                }
+                match message {
+                    Message::Quit => {
+                        tell!(ui, language, "You left the game.");
+                        break;
+                    },
+                    // The host answers with the result
+                    Message::Resign => {},
+                    Message::OfferDraw => tell!(ui, language, "Draw offered. Waiting for the host to answer..."),
+                    _ => tell!(ui, language, "Waiting for the host..."),
+                }
+            },
+            Ok(Some(Message::OfferDraw)) => {
+                let answer = if confirm(ui, language.tr("The host offers a draw. Do you accept? (yes/no)"), language) { Message::AcceptDraw } else { Message::DeclineDraw };
+                if let Err(e) = send(&mut stream, &answer) {
+                    tell!(ui, language, "Error: connection lost: {}", e);
+                    break;
+                }
+            },
+            Ok(Some(Message::GameOver(text))) => {
+                ui.message(&text);
+                break;
+            },
+            Ok(Some(Message::Quit) | None) => {
+                tell!(ui, language, "The host left the game.");
+                break;
+            },
+            // Messages only a client sends are ignored
+            Ok(Some(_)) => {},
+            Err(e) => {
+                tell!(ui, language, "Error: connection lost: {}", e);
+                break;
+            },
+        }
+    }
+    ui.finish();
+}
+
+/// The player's answer to a turn: an action, `resign`, or `quit` when they leave.
+fn read_action(ui: &mut dyn Renderer, to_move: Option<Player>, language: Language) -> Message {
+    let prompt = match to_move {
+        Some(player) => language.format("Your turn as {}, enter 'flip row col', 'move from_row from_col to_row to_col', 'draw', 'resign' or 'exit':", &[&language.player_name(player)]),
+        None => language.tr("Your turn: flip a piece to take its color with 'flip row col', or 'exit':").to_string(),
+    };
+    loop {
+        let Some(input) = ui.read_command(&prompt) else {
+            return Message::Quit;
+        };
+        match input.trim().to_lowercase().as_str() {
+            "exit" => return Message::Quit,
+            "resign" => return Message::Resign,
+            "draw" => return Message::OfferDraw,
+            _ => {},
+        }
+        match parse_input(input.trim()) {
+            Ok((command, coordinates)) => match parse_action(&command.to_lowercase(), &coordinates) {
+                Some(action) => return Message::Action(action),
+                None => tell!(ui, language, "Only flip and move can be played in a network game."),
+            },
+            Err(e) => tell!(ui, language, "Error parsing input: {}", language.tr(e)),
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use crate::ai::{best_action, FlipPolicy, Search};
+use crate::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+// Going back over a game with the search: every action is scored against the search's own
+// choice in the same position, and the ones that gave away the most are flagged as mistakes.
+
+/// Loss in evaluation beyond which an action counts as a mistake, about a soldier's worth.
+pub const MISTAKE_THRESHOLD: f64 = 1.5;
+
+// Actions valued within this of the best one count as the best; flip averages rarely tie exactly
+const BEST_MARGIN: f64 = 0.05;
+
+/// How one action compared with the search's choice in the position it was played in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlyReview {
+    pub ply: usize, // 1 for the opening flip
+    pub player: Player,
+    pub played: GameMove,
+    pub value: f64, // Value of the action played, for its player
+    pub best: ActionType,
+    pub best_value: f64,
+}
+
+impl PlyReview {
+    /// How much worse the action played was than the search's choice, never below zero.
+    pub fn loss(&self) -> f64 {
+        (self.best_value - self.value).max(0.0)
+    }
+}
+
+/// Searches `depth` plies at every position of `game` and scores the action played there. A
+/// flip is valued by what it could have turned up rather than by what it did. The opening flip,
+/// made before the colors are known, is left out.
+pub fn review_game(game: &GameState, depth: u32, flip_policy: FlipPolicy) -> Result<Vec<PlyReview>, GameError> {
+    let mut position = game.clone();
+    while position.undo().is_ok() {}
+
+    // Ties between equally good choices are broken the same way on every run
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut reviews = Vec::new();
+    for (index, game_move) in game.history.iter().enumerate() {
+        if !position.colors_unassigned() {
+            let player = position.current_player;
+            let best = best_action(&position, depth, flip_policy, &mut rng).ok_or(GameError::GameOver)?;
+            let value = Search::new(&position.rules, player, flip_policy).action_value(
+                &position.board,
+                player,
+                game_move.action_type,
+                depth.max(1) - 1,
+                f64::NEG_INFINITY,
+                f64::INFINITY,
+            );
+            reviews.push(PlyReview { ply: index + 1, player, played: *game_move, value, best: best.action, best_value: best.value });
+        }
+        position.apply_action(game_move.action_type)?;
+    }
+    Ok(reviews)
+}
+
+/// One player's actions over a reviewed game.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Accuracy {
+    pub moves: usize,
+    pub best_moves: usize, // As good as the search's choice
+    pub mistakes: usize, // Lost more than the threshold
+    pub total_loss: f64,
+}
+
+impl Accuracy {
+    pub fn new(reviews: &[PlyReview], player: Player, threshold: f64) -> Self {
+        let mut accuracy = Accuracy::default();
+        for review in reviews.iter().filter(|review| review.player == player) {
+            let loss = review.loss();
+            accuracy.moves += 1;
+            accuracy.best_moves += usize::from(loss <= BEST_MARGIN);
+            accuracy.mistakes += usize::from(loss > threshold);
+            accuracy.total_loss += loss;
+        }
+        accuracy
+    }
+
+    /// Share of the actions that were as good as the search's choice, as a percentage.
+    pub fn percent(&self) -> f64 {
+        if self.moves == 0 { 100.0 } else { 100.0 * self.best_moves as f64 / self.moves as f64 }
+    }
+
+    pub fn average_loss(&self) -> f64 {
+        if self.moves == 0 { 0.0 } else { self.total_loss / self.moves as f64 }
+    }
+}
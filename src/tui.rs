@@ -0,0 +1,491 @@
+use crate::ui::{Evaluator, Renderer};
+use ratatui::crossterm::event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+use ratatui::crossterm::execute;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::symbols::border;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use rust_dark_chess::display::*;
+use rust_dark_chess::*;
+use std::sync::mpsc;
+use std::time::Duration;
+
+// Columns taken by the row labels left of the board
+pub const LABEL_WIDTH: u16 = 3;
+// Terminal columns per square: a double-width symbol and a space
+pub const CELL_WIDTH: u16 = 3;
+// Messages kept for the log panel
+const LOG_LINES: usize = 200;
+// How often the screen looks for a finished evaluation while waiting for a key
+const EVAL_POLL: Duration = Duration::from_millis(50);
+const ASCII_BORDER: border::Set = border::Set {
+    top_left: "+",
+    top_right: "+",
+    bottom_left: "+",
+    bottom_right: "+",
+    vertical_left: "|",
+    vertical_right: "|",
+    horizontal_top: "-",
+    horizontal_bottom: "-",
+};
+
+/// Full-screen interface: arrow keys move a cursor, Enter flips a hidden piece or selects a
+/// piece and then its destination, and mouse clicks do the same on the square clicked. Any
+/// other typing goes to a command line, so every command of the line interface still works.
+pub struct TuiRenderer {
+    terminal: DefaultTerminal,
+    symbols: Symbols,
+    colors: ColorConfig,
+    style: CoordinateStyle,
+    charset: Charset,
+    rules: RuleConfig,
+    piece_values: PieceValues,
+    board: Board,
+    marks: Vec<(usize, usize)>, // Destinations shown by the moves command
+    cursor: (usize, usize),
+    selected: Option<(usize, usize)>,
+    log: Vec<String>,
+    input: String,
+    prompt: String,
+    status: String,
+    captured: [Vec<Piece>; 2],
+    board_area: Rect, // Where the board was last drawn, for mapping clicks to squares
+    evaluator: Option<(Evaluator, mpsc::Receiver<(u64, f64)>)>, // With the evaluation bar on
+    evaluation: Option<f64>, // For the position on the board, once the search is done
+}
+
+impl TuiRenderer {
+    pub fn new(symbols: Symbols, colors: ColorConfig, style: CoordinateStyle, charset: Charset, rules: RuleConfig, piece_values: PieceValues, eval_bar: bool) -> Self {
+        let evaluator = eval_bar.then(|| {
+            let (results, received) = mpsc::channel();
+            (Evaluator::new(move |number, value| {
+                results.send((number, value)).ok();
+            }), received)
+        });
+        let terminal = ratatui::init();
+        execute!(std::io::stdout(), EnableMouseCapture).ok();
+        TuiRenderer {
+            terminal,
+            symbols,
+            colors,
+            style,
+            charset,
+            rules,
+            piece_values,
+            board: Board::new(),
+            marks: Vec::new(),
+            cursor: (0, 0),
+            selected: None,
+            log: Vec::new(),
+            input: String::new(),
+            prompt: String::new(),
+            status: String::new(),
+            captured: [Vec::new(), Vec::new()],
+            board_area: Rect::default(),
+            evaluator,
+            evaluation: None,
+        }
+    }
+
+    fn draw(&mut self) {
+        if let Some((evaluator, received)) = &self.evaluator {
+            // Results for positions since left behind are ignored
+            for (number, value) in received.try_iter() {
+                if number == evaluator.handed_over {
+                    self.evaluation = Some(value);
+                }
+            }
+        }
+        // Drawing needs the fields while the terminal is borrowed, so render from a snapshot
+        let view = View {
+            board: &self.board,
+            symbols: &self.symbols,
+            colors: &self.colors,
+            style: self.style,
+            charset: self.charset,
+            highlights: self.highlights(),
+            cursor: self.cursor,
+            selected: self.selected,
+            log: &self.log,
+            input: &self.input,
+            prompt: &self.prompt,
+            status: &self.status,
+            captured: &self.captured,
+            piece_values: &self.piece_values,
+            evaluation: self.evaluator.as_ref().map(|_| self.evaluation),
+        };
+        let mut board_area = self.board_area;
+        self.terminal.draw(|frame| board_area = view.render(frame)).ok();
+        self.board_area = board_area;
+    }
+
+    /// Squares to highlight: the selected piece's destinations, or those from the moves command.
+    fn highlights(&self) -> Vec<(usize, usize)> {
+        match self.selected {
+            Some((x, y)) => destinations(&self.board, x, y, &self.rules),
+            None => self.marks.clone(),
+        }
+    }
+
+    fn move_cursor(&mut self, dx: isize, dy: isize) {
+        let (width, height) = (self.board.first().map_or(1, |row| row.len()), self.board.len().max(1));
+        self.cursor.0 = self.cursor.0.saturating_add_signed(dx).min(width - 1);
+        self.cursor.1 = self.cursor.1.saturating_add_signed(dy).min(height - 1);
+    }
+
+    /// Acts on a square as if it had been chosen with Enter, returning a command when the
+    /// choice completes an action.
+    fn choose_square(&mut self, x: usize, y: usize) -> Option<String> {
+        let cell = self.board.get(y).and_then(|row| row.get(x)).copied()?;
+        match (self.selected, cell) {
+            (Some(from), _) if from == (x, y) => {
+                self.selected = None;
+                None
+            },
+            (Some((from_x, from_y)), _) if destinations(&self.board, from_x, from_y, &self.rules).contains(&(x, y)) => {
+                self.selected = None;
+                Some(format!("move {} {} {} {}", from_x, from_y, x, y))
+            },
+            (_, Cell::Hidden(_)) => {
+                self.selected = None;
+                Some(format!("flip {} {}", x, y))
+            },
+            (_, Cell::Revealed(_)) => {
+                self.selected = Some((x, y));
+                None
+            },
+            (Some((from_x, from_y)), Cell::Empty) => {
+                // Not a legal destination; let the game explain why
+                self.selected = None;
+                Some(format!("move {} {} {} {}", from_x, from_y, x, y))
+            },
+            (None, Cell::Empty) => None,
+        }
+    }
+
+    fn handle_mouse(&mut self, mouse: MouseEvent) -> Option<String> {
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                let (x, y) = square_at(self.board_area, &self.board, mouse.column, mouse.row)?;
+                self.cursor = (x, y);
+                self.choose_square(x, y)
+            },
+            MouseEventKind::Down(MouseButton::Right) => {
+                self.selected = None;
+                None
+            },
+            _ => None,
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Option<String> {
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => Some(String::from("exit")),
+            KeyCode::Left => {
+                self.move_cursor(-1, 0);
+                None
+            },
+            KeyCode::Right => {
+                self.move_cursor(1, 0);
+                None
+            },
+            KeyCode::Up => {
+                self.move_cursor(0, -1);
+                None
+            },
+            KeyCode::Down => {
+                self.move_cursor(0, 1);
+                None
+            },
+            KeyCode::Enter if !self.input.is_empty() => Some(std::mem::take(&mut self.input)),
+            KeyCode::Enter => self.choose_square(self.cursor.0, self.cursor.1),
+            KeyCode::Esc => {
+                if self.input.is_empty() {
+                    self.selected = None;
+                } else {
+                    self.input.clear();
+                }
+                None
+            },
+            KeyCode::Backspace => {
+                self.input.pop();
+                None
+            },
+            KeyCode::Char(c) => {
+                self.input.push(c);
+                None
+            },
+            _ => None,
+        }
+    }
+}
+
+impl Renderer for TuiRenderer {
+    fn show_board(&mut self, board: &Board, marks: &[(usize, usize)]) {
+        if *board != self.board {
+            self.selected = None;
+        }
+        self.board = board.clone();
+        self.marks = marks.to_vec();
+        self.move_cursor(0, 0);
+        self.draw();
+    }
+
+    fn set_status(&mut self, status: &str) {
+        self.status = status.to_string();
+    }
+
+    fn set_captured(&mut self, captured: &[Vec<Piece>; 2]) {
+        self.captured = captured.clone();
+    }
+
+    fn evaluate(&mut self, game: &GameState) {
+        if let Some((evaluator, _)) = &mut self.evaluator {
+            if evaluator.evaluate(game) {
+                self.evaluation = None;
+            }
+        }
+    }
+
+    fn message(&mut self, text: &str) {
+        self.log.extend(text.lines().map(str::to_string));
+        let excess = self.log.len().saturating_sub(LOG_LINES);
+        self.log.drain(..excess);
+        self.draw();
+    }
+
+    fn read_command(&mut self, prompt: &str) -> Option<String> {
+        self.prompt = prompt.to_string();
+        loop {
+            self.draw();
+            // Until the evaluation arrives the screen is redrawn between keys to pick it up
+            if self.evaluator.is_some() && self.evaluation.is_none() && !event::poll(EVAL_POLL).unwrap_or(true) {
+                continue;
+            }
+            let command = match event::read().ok()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => self.handle_key(key),
+                Event::Mouse(mouse) => self.handle_mouse(mouse),
+                _ => None,
+            };
+            if let Some(command) = command {
+                self.log.push(format!("> {}", command));
+                return Some(command);
+            }
+        }
+    }
+
+    fn hand_over(&mut self, notice: &str) {
+        // The log holds the previous player's hints and commands, so it goes too
+        self.log.clear();
+        self.input.clear();
+        self.selected = None;
+        self.terminal
+            .draw(|frame| {
+                let area = frame.area();
+                let [_, middle, _] = Layout::vertical([Constraint::Fill(1), Constraint::Length(3), Constraint::Fill(1)]).areas(area);
+                frame.render_widget(Paragraph::new(notice).centered().block(bordered(self.charset)), middle);
+            })
+            .ok();
+        while let Ok(event) = event::read() {
+            if matches!(event, Event::Key(key) if key.kind == KeyEventKind::Press && key.code == KeyCode::Enter) {
+                break;
+            }
+        }
+    }
+
+    fn finish(&mut self) {
+        // Keep the final position and messages up until the player has seen them
+        self.selected = None;
+        self.prompt = String::from("Game over. Press any key to leave.");
+        self.draw();
+        while let Ok(event) = event::read() {
+            if matches!(event, Event::Key(key) if key.kind == KeyEventKind::Press) {
+                break;
+            }
+        }
+    }
+}
+
+impl Drop for TuiRenderer {
+    fn drop(&mut self) {
+        execute!(std::io::stdout(), DisableMouseCapture).ok();
+        ratatui::restore();
+    }
+}
+
+fn destinations(board: &Board, x: usize, y: usize, rules: &RuleConfig) -> Vec<(usize, usize)> {
+    moves_from(board, x, y, rules)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|game_move| match game_move.action_type {
+            ActionType::Move { to_x, to_y, .. } => Some((to_x, to_y)),
+            ActionType::Flip { .. } => None,
+        })
+        .collect()
+}
+
+/// Translates an ANSI SGR color setting like "1;91" into a terminal style.
+fn sgr_style(code: &str) -> Style {
+    let mut style = Style::new();
+    let mut params = code.split(';').filter_map(|param| param.trim().parse::<u8>().ok());
+    while let Some(param) = params.next() {
+        style = match param {
+            1 => style.add_modifier(Modifier::BOLD),
+            4 => style.add_modifier(Modifier::UNDERLINED),
+            7 => style.add_modifier(Modifier::REVERSED),
+            30..=37 => style.fg(Color::Indexed(param - 30)),
+            40..=47 => style.bg(Color::Indexed(param - 40)),
+            90..=97 => style.fg(Color::Indexed(param - 90 + 8)),
+            100..=107 => style.bg(Color::Indexed(param - 100 + 8)),
+            38 if params.next() == Some(5) => params.next().map_or(style, |index| style.fg(Color::Indexed(index))),
+            48 if params.next() == Some(5) => params.next().map_or(style, |index| style.bg(Color::Indexed(index))),
+            _ => style,
+        };
+    }
+    style
+}
+
+struct View<'a> {
+    board: &'a Board,
+    symbols: &'a Symbols,
+    colors: &'a ColorConfig,
+    style: CoordinateStyle,
+    charset: Charset,
+    highlights: Vec<(usize, usize)>,
+    cursor: (usize, usize),
+    selected: Option<(usize, usize)>,
+    log: &'a [String],
+    input: &'a str,
+    prompt: &'a str,
+    status: &'a str,
+    captured: &'a [Vec<Piece>; 2],
+    piece_values: &'a PieceValues,
+    evaluation: Option<Option<f64>>, // With the evaluation bar on, None while it is being searched
+}
+
+impl View<'_> {
+    /// Draws the whole screen and returns the area the board was drawn in.
+    fn render(&self, frame: &mut Frame) -> Rect {
+        let board_height = self.board.len() as u16 + 3; // Header row plus the block's borders
+        let [board_area, log_area, input_area, keys_area] =
+            Layout::vertical([Constraint::Length(board_height), Constraint::Min(3), Constraint::Length(3), Constraint::Length(1)]).areas(frame.area());
+
+        // The capture trays sit to the right of the board
+        let board_width = self.board.first().map_or(0, |row| row.len()) as u16 * CELL_WIDTH + LABEL_WIDTH + 2;
+        let [board_area, tray_area] = Layout::horizontal([Constraint::Length(board_width), Constraint::Fill(1)]).areas(board_area);
+
+        let title = if self.status.is_empty() { String::from(" Dark Chess ") } else { format!(" Dark Chess - {} ", self.status) };
+        frame.render_widget(Paragraph::new(self.board_lines()).block(bordered(self.charset).title(title)), board_area);
+        frame.render_widget(Paragraph::new(self.tray_lines()).block(bordered(self.charset).title(" Captured and material ")), tray_area);
+
+        let visible = log_area.height.saturating_sub(2) as usize;
+        let log: Vec<Line> = self.log.iter().skip(self.log.len().saturating_sub(visible)).map(|line| Line::raw(line.as_str())).collect();
+        frame.render_widget(Paragraph::new(log).block(bordered(self.charset).title(" Messages ")), log_area);
+
+        frame.render_widget(Paragraph::new(format!("> {}", self.input)).block(bordered(self.charset).title(format!(" {} ", self.prompt))), input_area);
+        frame.set_cursor_position((input_area.x + 3 + self.input.chars().count() as u16, input_area.y + 1));
+
+        let keys = "Arrows/click: choose a square   Enter/click: flip, select or move   Esc/right-click: cancel   Type a command and Enter to run it";
+        frame.render_widget(Paragraph::new(keys).style(Style::new().add_modifier(Modifier::DIM)), keys_area);
+        board_area
+    }
+
+    fn board_lines(&self) -> Vec<Line<'_>> {
+        let width = self.board.first().map_or(0, |row| row.len());
+        let mut header = vec![Span::raw(" ".repeat(LABEL_WIDTH as usize))];
+        for x in 0..width {
+            let label = match self.style {
+                CoordinateStyle::Numeric => x.to_string(),
+                CoordinateStyle::Algebraic => notation::square_name(x, 0)[..1].to_string(),
+            };
+            header.push(Span::raw(format!("{:>2} ", label)));
+        }
+
+        let mut lines = vec![Line::from(header)];
+        for (y, row) in self.board.iter().enumerate() {
+            let label = match self.style {
+                CoordinateStyle::Numeric => y,
+                CoordinateStyle::Algebraic => y + 1,
+            };
+            let mut spans = vec![Span::raw(format!("{:<width$}", label, width = LABEL_WIDTH as usize))];
+            for (x, cell) in row.iter().enumerate() {
+                spans.push(self.cell_span(x, y, *cell));
+                spans.push(Span::raw(" "));
+            }
+            lines.push(Line::from(spans));
+        }
+        lines
+    }
+
+    fn tray_lines(&self) -> Vec<Line<'_>> {
+        [(Player::Red, &self.captured[0]), (Player::Black, &self.captured[1])]
+            .into_iter()
+            .map(|(captor, pieces)| {
+                // Captured pieces are the other side's, so they take its color
+                let code = match opponent(captor) {
+                    Player::Red => &self.colors.red,
+                    Player::Black => &self.colors.black,
+                };
+                let style = if self.colors.enabled { sgr_style(code) } else { Style::new() };
+                Line::from(vec![Span::raw(format!("{:<6} took: ", player_name(captor))), Span::styled(capture_tray(pieces, self.symbols), style)])
+            })
+            .chain([Line::raw(material_line(self.board, self.piece_values))])
+            .chain(self.evaluation.map(|evaluation| match evaluation {
+                // The bar is plain text here; the terminal styles come from spans
+                Some(value) => Line::raw(evaluation_bar(value, &ColorConfig { enabled: false, ..self.colors.clone() })),
+                None => Line::raw("Eval: searching..."),
+            }))
+            .collect()
+    }
+
+    fn cell_span(&self, x: usize, y: usize, cell: Cell) -> Span<'_> {
+        let (text, mut style) = match cell {
+            Cell::Hidden(_) => (self.charset.hidden().to_string(), Style::new().add_modifier(Modifier::DIM)),
+            Cell::Revealed(piece) => {
+                let code = match piece.player {
+                    Player::Red => &self.colors.red,
+                    Player::Black => &self.colors.black,
+                };
+                let style = if self.colors.enabled { sgr_style(code) } else { Style::new() };
+                (cell_symbol(piece, self.symbols), style)
+            },
+            Cell::Empty if self.highlights.contains(&(x, y)) => (format!(" {}", MOVE_MARKER), Style::new()),
+            Cell::Empty => (String::from(if self.charset == Charset::Ascii { ".." } else { " ." }), Style::new().add_modifier(Modifier::DIM)),
+        };
+        // Without colors the classic theme's markers still show the cursor and the reachable squares
+        let classic = Theme::Classic.colors();
+        let markers = if self.colors.enabled { self.colors } else { &classic };
+        if self.highlights.contains(&(x, y)) {
+            style = style.patch(sgr_style(&markers.highlight));
+        }
+        if self.selected == Some((x, y)) {
+            style = style.add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+        }
+        if self.cursor == (x, y) {
+            style = style.patch(sgr_style(&markers.cursor));
+        }
+        Span::styled(text, style)
+    }
+}
+
+/// A bordered block, drawn with `+`, `-` and `|` in ASCII mode.
+fn bordered(charset: Charset) -> Block<'static> {
+    match charset {
+        Charset::Unicode => Block::bordered(),
+        Charset::Ascii => Block::bordered().border_set(ASCII_BORDER),
+    }
+}
+
+/// The board square under a terminal cell, given the area the board block was drawn in.
+fn square_at(board_area: Rect, board: &Board, column: u16, row: u16) -> Option<(usize, usize)> {
+    // Inside the border, below the header row and right of the row labels
+    let left = board_area.x + 1 + LABEL_WIDTH;
+    let top = board_area.y + 2;
+    if column < left || row < top {
+        return None;
+    }
+    let (x, y) = (((column - left) / CELL_WIDTH) as usize, (row - top) as usize);
+    (y < board.len() && x < board.first().map_or(0, |cells| cells.len())).then_some((x, y))
+}
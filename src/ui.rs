@@ -0,0 +1,198 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rust_dark_chess::ai::{best_action, AiSettings, Difficulty};
+use rust_dark_chess::display::*;
+use rust_dark_chess::locale::Language;
+use rust_dark_chess::*;
+use std::io::{self, Write};
+use std::sync::mpsc::{self, TryRecvError};
+
+// Plies the evaluation bar searches, few enough to keep up with play
+const EVAL_BAR_DEPTH: u32 = 2;
+
+/// Where the interactive game draws the board and reads commands from. Commands are the
+/// same text either way, so the game loop doesn't care whether they were typed or picked.
+pub trait Renderer {
+    /// Draws the board, highlighting `marks` as the destinations of a selected piece.
+    fn show_board(&mut self, board: &Board, marks: &[(usize, usize)]);
+
+    /// Shows a status line, such as the clocks, with the next board.
+    fn set_status(&mut self, status: &str);
+
+    /// Shows the pieces taken by Red and by Black beside the next board.
+    fn set_captured(&mut self, captured: &[Vec<Piece>; 2]);
+
+    /// Shows one message; it may span several lines.
+    fn message(&mut self, text: &str);
+
+    /// Waits for the next command, or returns `None` once there is no more input.
+    fn read_command(&mut self, prompt: &str) -> Option<String>;
+
+    /// Hides the screen behind `notice` until the next player confirms they have the keyboard,
+    /// so nothing the previous player saw or typed is left on display.
+    fn hand_over(&mut self, notice: &str);
+
+    /// Starts evaluating the position for the evaluation bar, which is shown once the search
+    /// is done without holding up the next command.
+    fn evaluate(&mut self, _game: &GameState) {}
+
+    /// Switches between drawing the board and describing it in words, for screen readers.
+    fn set_narration(&mut self, _narrate: bool) {}
+
+    /// Called once when the game is over, before the renderer is dropped.
+    fn finish(&mut self) {}
+}
+
+/// Shorthand for `renderer.message(&format!(...))`.
+macro_rules! say {
+    ($ui:expr, $($arg:tt)*) => {
+        $ui.message(&format!($($arg)*))
+    };
+}
+pub(crate) use say;
+
+/// Shorthand for `renderer.message(&language.format(template, &[...]))`, for text shown in the
+/// player's language.
+macro_rules! tell {
+    ($ui:expr, $language:expr, $template:expr $(, $arg:expr)* $(,)?) => {
+        $ui.message(&$language.format($template, &[$(&$arg as &dyn std::fmt::Display),*]))
+    };
+}
+pub(crate) use tell;
+
+/// Asks a yes-or-no question until it gets an answer. No more input counts as no.
+pub fn confirm(ui: &mut dyn Renderer, prompt: &str, language: Language) -> bool {
+    loop {
+        match ui.read_command(prompt).map(|answer| answer.trim().to_lowercase()).as_deref() {
+            Some("yes" | "y") => return true,
+            Some("no" | "n") | None => return false,
+            Some(_) => tell!(ui, language, "Please answer yes or no."),
+        }
+    }
+}
+
+/// Searches positions for the evaluation bar on a background thread, so the keyboard is never
+/// kept waiting. Only the newest position counts: positions queued up behind it are skipped,
+/// and a result is dropped if another position arrived while it was being searched.
+pub struct Evaluator {
+    positions: mpsc::Sender<(u64, GameState)>,
+    last: Option<(u64, usize)>, // Position hash and ply of the last position handed over
+    pub handed_over: u64, // Number of positions handed over, which also numbers the latest
+}
+
+impl Evaluator {
+    /// Starts the search thread, which calls `report` with each evaluation, for Red, and the
+    /// number of the position it belongs to.
+    pub fn new(report: impl Fn(u64, f64) + Send + 'static) -> Self {
+        let (positions, queue) = mpsc::channel::<(u64, GameState)>();
+        std::thread::spawn(move || {
+            let flip_policy = AiSettings::for_difficulty(Difficulty::Hard).flip_policy;
+            let mut rng = StdRng::seed_from_u64(0);
+            let mut next = None;
+            loop {
+                let (mut number, mut game) = match next.take().map_or_else(|| queue.recv(), Ok) {
+                    Ok(position) => position,
+                    Err(_) => return,
+                };
+                while let Ok(newer) = queue.try_recv() {
+                    (number, game) = newer;
+                }
+                let Some(suggestion) = best_action(&game, EVAL_BAR_DEPTH, flip_policy, &mut rng) else { continue };
+                let value = if game.current_player == Player::Red { suggestion.value } else { -suggestion.value };
+                match queue.try_recv() {
+                    Ok(newer) => next = Some(newer),
+                    Err(TryRecvError::Empty) => report(number, value),
+                    Err(TryRecvError::Disconnected) => return,
+                }
+            }
+        });
+        Evaluator { positions, last: None, handed_over: 0 }
+    }
+
+    /// Hands `game` to the search thread, returning false if it is the position already handed over.
+    pub fn evaluate(&mut self, game: &GameState) -> bool {
+        let key = (position_hash(&game.board, game.current_player), game.history.len());
+        if self.last == Some(key) {
+            return false;
+        }
+        self.last = Some(key);
+        self.handed_over += 1;
+        self.positions.send((self.handed_over, game.clone())).is_ok()
+    }
+}
+
+/// The plain line-based interface: prints the board and reads commands from standard input.
+pub struct LineRenderer {
+    pub symbols: Symbols,
+    pub colors: ColorConfig,
+    pub style: CoordinateStyle,
+    pub charset: Charset,
+    pub material_bar: bool,
+    pub narrate: bool, // Describe the board in words instead of drawing it
+    pub piece_values: PieceValues,
+    pub captured: Option<[Vec<Piece>; 2]>, // Capture trays for the next board, once they're known
+    pub evaluator: Option<Evaluator>, // Prints the evaluation bar under the board when it is on
+}
+
+impl Renderer for LineRenderer {
+    fn show_board(&mut self, board: &Board, marks: &[(usize, usize)]) {
+        if self.narrate {
+            // Marks are left out, the moves command already lists them
+            println!("{}", narrate_board(board, self.style));
+            if let Some(captured) = &self.captured {
+                println!("{}", narrate_captured(captured));
+            }
+            println!("{}", material_line(board, &self.piece_values));
+            return;
+        }
+        let rendered = render_board_marked(board, &self.symbols, &self.colors, marks, self.style, self.charset);
+        match &self.captured {
+            Some(captured) => print!("{}", beside_board(&rendered, &capture_tray_lines(captured, &self.symbols, &self.colors))),
+            None => print!("{}", rendered),
+        }
+        println!("{}", material_line(board, &self.piece_values));
+        if self.material_bar {
+            println!("{}", material_bar(board, &self.colors));
+        }
+    }
+
+    fn set_status(&mut self, status: &str) {
+        println!("{}", status);
+    }
+
+    fn set_captured(&mut self, captured: &[Vec<Piece>; 2]) {
+        self.captured = Some(captured.clone());
+    }
+
+    fn message(&mut self, text: &str) {
+        println!("{}", text);
+    }
+
+    fn evaluate(&mut self, game: &GameState) {
+        if let Some(evaluator) = &mut self.evaluator {
+            evaluator.evaluate(game);
+        }
+    }
+
+    fn set_narration(&mut self, narrate: bool) {
+        self.narrate = narrate;
+    }
+
+    fn hand_over(&mut self, notice: &str) {
+        // Clear the screen and scrollback, then again once the next player is ready
+        print!("\x1b[2J\x1b[3J\x1b[H{}", notice);
+        io::stdout().flush().ok();
+        io::stdin().read_line(&mut String::new()).ok();
+        print!("\x1b[2J\x1b[3J\x1b[H");
+    }
+
+    fn read_command(&mut self, prompt: &str) -> Option<String> {
+        println!("{}", prompt);
+        io::stdout().flush().ok();
+        let mut input = String::new();
+        match io::stdin().read_line(&mut input) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(input.trim().to_string()),
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use crate::*;
+
+/// How pieces move and capture and when the game is over, as one region plays it. Moves are
+/// always along a row or column, so a rule set only sees how far a piece goes and how many
+/// occupied squares lie between, never the board itself.
+pub trait RuleSet: Sync {
+    /// The name players know the rules by.
+    fn name(&self) -> &'static str;
+
+    /// Whether `attacker` may take `defender` once it can reach it.
+    fn can_capture(&self, attacker: Piece, defender: Piece) -> bool;
+
+    /// Whether a piece of this type can go `distance` squares with `between` occupied squares
+    /// in the way, either onto an empty square or, when `capture` is set, onto an enemy piece,
+    /// and if not, why.
+    fn check_reach(&self, piece_type: PieceType, distance: usize, between: usize, capture: bool) -> Result<(), GameError>;
+
+    /// The result once no pieces are left face down, from how many pieces Red and Black still
+    /// have and whether the player to move has any action left, or `None` while play goes on.
+    fn outcome(&self, pieces: [usize; 2], has_action: &dyn Fn() -> bool, last_mover: Player, rules: &RuleConfig) -> Option<GameResult> {
+        match pieces {
+            [0, 0] => Some(match rules.simultaneous_elimination {
+                EliminationOutcome::Draw => GameResult::Draw(DrawReason::Elimination),
+                EliminationOutcome::LastMoverWins => GameResult::Winner(last_mover),
+            }),
+            [0, _] => Some(GameResult::Winner(Player::Black)),
+            [_, 0] => Some(GameResult::Winner(Player::Red)),
+            // A player who cannot flip or move on their turn, e.g. with every piece blockaded, loses
+            _ if !has_action() => Some(GameResult::Winner(last_mover)),
+            _ => None,
+        }
+    }
+}
+
+/// The rules most often played in Taiwan: a Soldier can take the General but not the other way
+/// round, and a Cannon takes any piece by jumping exactly one piece on the way.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Taiwanese;
+
+impl RuleSet for Taiwanese {
+    fn name(&self) -> &'static str {
+        "Taiwanese"
+    }
+
+    fn can_capture(&self, attacker: Piece, defender: Piece) -> bool {
+        attacker.piece_type == PieceType::Cannon || can_capture(attacker, defender)
+    }
+
+    fn check_reach(&self, piece_type: PieceType, distance: usize, between: usize, capture: bool) -> Result<(), GameError> {
+        match piece_type {
+            // Cannons slide like chariots but only capture over a single screen
+            PieceType::Cannon if capture && between != 1 => Err(GameError::NoScreenForCannon),
+            PieceType::Cannon if capture => Ok(()),
+            PieceType::Chariot | PieceType::Cannon if between > 0 => Err(GameError::PathBlocked),
+            PieceType::Chariot | PieceType::Cannon => Ok(()),
+            _ if distance > 1 => Err(GameError::TooFar),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The rules common in Hong Kong: ranks alone decide captures, so the General takes Soldiers
+/// and Soldiers can't touch the General, and a Cannon steps and captures like any other piece.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HongKong;
+
+impl RuleSet for HongKong {
+    fn name(&self) -> &'static str {
+        "Hong Kong"
+    }
+
+    fn can_capture(&self, attacker: Piece, defender: Piece) -> bool {
+        piece_rank(attacker.piece_type) >= piece_rank(defender.piece_type)
+    }
+
+    fn check_reach(&self, piece_type: PieceType, distance: usize, between: usize, _capture: bool) -> Result<(), GameError> {
+        match piece_type {
+            PieceType::Chariot if between > 0 => Err(GameError::PathBlocked),
+            PieceType::Chariot => Ok(()),
+            _ if distance > 1 => Err(GameError::TooFar),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Which rule set a game is played by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Variant {
+    #[default]
+    Taiwanese,
+    HongKong,
+}
+
+impl Variant {
+    pub fn rule_set(self) -> &'static dyn RuleSet {
+        match self {
+            Variant::Taiwanese => &Taiwanese,
+            Variant::HongKong => &HongKong,
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use crate::arena::parse_engine_spec;
+use crate::display::describe_result;
+use crate::web::{color_name, wire_square, WireAction};
+use crate::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use wasm_bindgen::prelude::*;
+
+// JavaScript bindings, built with `wasm-pack build --target web -- --no-default-features --features wasm`.
+// Actions and squares are JSON in the same shapes as the WebSocket server's messages:
+//   {"kind": "flip", "x": 0, "y": 1}
+//   {"kind": "move", "from_x": 0, "from_y": 1, "to_x": 1, "to_y": 1}
+// and the board is rows from y = 0 of {"revealed": true, "color": "red", "piece": "cannon"},
+// {"revealed": false} or null for an empty square. Unlike the server, the board reports every
+// square exactly as the game holds it, hidden contents excepted.
+
+/// One game and the random numbers it deals and searches with.
+#[wasm_bindgen]
+pub struct Game {
+    state: GameState,
+    rng: StdRng,
+}
+
+fn seeded(seed: Option<u32>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed.into()),
+        None => StdRng::from_entropy(),
+    }
+}
+
+#[wasm_bindgen]
+impl Game {
+    /// Deals a new game under the standard rules, the same deal every time for the same `seed`.
+    #[wasm_bindgen(constructor)]
+    pub fn new(seed: Option<u32>) -> Result<Game, JsError> {
+        let mut rng = seeded(seed);
+        let state = GameState::with_rng(RuleConfig::default(), &mut rng).map_err(JsError::from)?;
+        Ok(Game { state, rng })
+    }
+
+    /// Starts from a position in the notation of `notation::parse_fen`, dealing any hidden
+    /// squares written as '?' from `seed`.
+    #[wasm_bindgen(js_name = fromFen)]
+    pub fn from_fen(fen: &str, seed: Option<u32>) -> Result<Game, JsError> {
+        let mut rng = seeded(seed);
+        let state = GameState::from_fen(fen, RuleConfig::default(), &mut rng).map_err(JsError::from)?;
+        Ok(Game { state, rng })
+    }
+
+    /// Plays an action given as JSON for the side to move.
+    #[wasm_bindgen(js_name = applyAction)]
+    pub fn apply_action(&mut self, action: &str) -> Result<(), JsError> {
+        let action: WireAction = serde_json::from_str(action).map_err(|e| JsError::new(&format!("Invalid action: {}", e)))?;
+        self.state.apply_action(action.into()).map_err(JsError::from)?;
+        Ok(())
+    }
+
+    /// Throws with the reason if the side to move may not play the action given as JSON,
+    /// without playing it.
+    #[wasm_bindgen(js_name = checkAction)]
+    pub fn check_action(&self, action: &str) -> Result<(), JsError> {
+        let action: WireAction = serde_json::from_str(action).map_err(|e| JsError::new(&format!("Invalid action: {}", e)))?;
+        self.state.validate_action(action.into()).map_err(JsError::from)
+    }
+
+    /// Takes back the last action.
+    pub fn undo(&mut self) -> Result<(), JsError> {
+        self.state.undo().map_err(JsError::from)?;
+        Ok(())
+    }
+
+    /// Every action the side to move may take, as a JSON array.
+    #[wasm_bindgen(js_name = legalActions)]
+    pub fn legal_actions(&self) -> String {
+        let actions: Vec<WireAction> = self.state.legal_actions().into_iter().map(WireAction::from).collect();
+        serde_json::to_string(&actions).expect("Actions always serialize")
+    }
+
+    /// The board as JSON rows.
+    #[wasm_bindgen(js_name = boardJson)]
+    pub fn board_json(&self) -> String {
+        let rows: Vec<Vec<_>> = self.state.board.iter().map(|row| row.iter().map(wire_square).collect()).collect();
+        serde_json::to_string(&rows).expect("Squares always serialize")
+    }
+
+    /// The position in the notation of `notation::export_fen`, hidden contents left out.
+    pub fn fen(&self) -> String {
+        self.state.to_fen(false)
+    }
+
+    /// "red" or "black", or undefined until the opening flip decides the colors.
+    #[wasm_bindgen(js_name = toMove)]
+    pub fn to_move(&self) -> Option<String> {
+        (!self.state.colors_unassigned()).then(|| color_name(self.state.current_player))
+    }
+
+    /// How the game ended, e.g. "Red wins!", or undefined while it goes on.
+    pub fn result(&self) -> Option<String> {
+        self.state.result().map(describe_result)
+    }
+
+    /// Searches for an action for the side to move with an engine such as "hard", "search:depth=3"
+    /// or "ismcts:playouts=500,time=200". Resolves to the action as JSON, or null if there is none.
+    /// The search runs once the returned promise is awaited and holds the thread until it is
+    /// done, so long searches belong in a Web Worker.
+    #[wasm_bindgen(js_name = bestAction)]
+    pub fn best_action(&mut self, engine: &str) -> Result<js_sys::Promise, JsError> {
+        let settings = parse_engine_spec(engine).map_err(|e| JsError::new(&e))?;
+        let mut agent = settings.build_agent(StdRng::from_rng(&mut self.rng).expect("Seeded RNG cannot fail"));
+        let game = self.state.clone();
+        Ok(wasm_bindgen_futures::future_to_promise(async move {
+            let action = agent.choose_action(&game).map(WireAction::from);
+            Ok(JsValue::from_str(&serde_json::to_string(&action).expect("Actions always serialize")))
+        }))
+    }
+}
@@ -0,0 +1,279 @@
+use crate::lobby::GameId;
+use crate::*;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "cli")]
+use {
+    crate::lobby::{ClientId, Lobby},
+    std::collections::BTreeMap,
+    std::io,
+    std::net::{TcpListener, TcpStream},
+    std::path::PathBuf,
+    std::sync::mpsc,
+    std::thread,
+    std::time::{Duration, Instant},
+    tungstenite::{Message, WebSocket},
+};
+
+// WebSocket server for browser and mobile clients. Every message is a JSON text frame whose
+// "type" field names it. Clients send:
+//   {"type": "list"}                                         lists the games on the server
+//   {"type": "create", "name": "Alice", "title": "Friday"}   opens a new game, both optional
+//   {"type": "join", "game_id": 3, "name": "Bob"}            takes a seat; without an id, the
+//                                                            oldest open game or a new one
+//   {"type": "spectate", "game_id": 3, "name": "Carol"}      watches; without an id, the oldest
+//                                                            game under way
+//   {"type": "action", "action": {"kind": "flip", "x": 0, "y": 1}}
+//   {"type": "action", "action": {"kind": "move", "from_x": 0, "from_y": 1, "to_x": 1, "to_y": 1}}
+//   {"type": "resign"}                                       gives up the game being played
+//   {"type": "offer_draw"}                                   on your turn; play waits for the answer
+//   {"type": "answer_draw", "accept": true}                  to the opponent's offer
+//   {"type": "leave"}                                        back to the lobby
+// The server sends:
+//   {"type": "games", "games": [...]}  the answer to list, see `GameSummary`
+//   {"type": "joined", "game_id": 3, "title": "...", "seat": 0 | 1 | null}
+//   {"type": "state", ...}      once both seats are filled and after every action, see `ServerMessage::State`
+//   {"type": "draw_offered", "by": "Alice"}   the opponent offered a draw, answer it to go on
+//   {"type": "draw_declined", "by": "Bob"}    your offer was declined, your turn goes on
+//   {"type": "error", "message": "..."}
+//   {"type": "game_over", "winner": "red" | "black" | null, "message": "..."}
+// The player who creates a game makes its opening flip. Hidden squares are sent without their
+// contents, so a client only ever learns what the player could see at the table. Spectators
+// get the same states and result as the players, optionally held back by the host's delay,
+// and can't act. A client is in at most one game at a time, and goes back to the lobby when
+// it leaves or once its game is over. The message types build everywhere; the server itself
+// needs the `cli` feature.
+
+/// An action as it appears in JSON.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum WireAction {
+    Flip { x: usize, y: usize },
+    Move { from_x: usize, from_y: usize, to_x: usize, to_y: usize },
+}
+
+impl From<ActionType> for WireAction {
+    fn from(action: ActionType) -> Self {
+        match action {
+            ActionType::Flip { x, y } => WireAction::Flip { x, y },
+            ActionType::Move { from_x, from_y, to_x, to_y } => WireAction::Move { from_x, from_y, to_x, to_y },
+        }
+    }
+}
+
+impl From<WireAction> for ActionType {
+    fn from(action: WireAction) -> Self {
+        match action {
+            WireAction::Flip { x, y } => ActionType::Flip { x, y },
+            WireAction::Move { from_x, from_y, to_x, to_y } => ActionType::Move { from_x, from_y, to_x, to_y },
+        }
+    }
+}
+
+/// One occupied square. Hidden squares have only `revealed: false`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WireSquare {
+    pub revealed: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>, // "red" or "black"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub piece: Option<String>, // "general", "advisor", ... "soldier"
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    List,
+    Create {
+        #[serde(default)]
+        name: Option<String>,
+        #[serde(default)]
+        title: Option<String>,
+    },
+    Join {
+        #[serde(default)]
+        game_id: Option<GameId>,
+        #[serde(default)]
+        name: Option<String>,
+    },
+    Spectate {
+        #[serde(default)]
+        game_id: Option<GameId>,
+        #[serde(default)]
+        name: Option<String>,
+    },
+    Action {
+        action: WireAction,
+    },
+    Resign,
+    OfferDraw,
+    AnswerDraw {
+        accept: bool,
+    },
+    Leave,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    Games {
+        games: Vec<GameSummary>,
+    },
+    Joined {
+        game_id: GameId,
+        title: String,
+        seat: Option<usize>, // None for a spectator
+    },
+    State {
+        game_id: GameId,
+        board: Vec<Vec<Option<WireSquare>>>, // Rows from y = 0, empty squares are null
+        fen: String,
+        players: [String; 2], // Names in turn order: the opening flip, then the other
+        to_move: Option<String>, // None until the opening flip decides the colors
+        your_color: Option<String>,
+        your_turn: bool,
+        legal_actions: Vec<WireAction>, // Only filled in on the receiver's turn
+        last_move: Option<String>,
+    },
+    DrawOffered {
+        by: String,
+    },
+    DrawDeclined {
+        by: String,
+    },
+    Error {
+        message: String,
+    },
+    GameOver {
+        winner: Option<String>,
+        message: String,
+    },
+}
+
+/// One game in the answer to `list`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameSummary {
+    pub game_id: GameId,
+    pub title: String,
+    pub players: Vec<String>,
+    pub open: bool, // A seat is still free
+    pub spectators: usize,
+}
+
+pub fn color_name(player: Player) -> String {
+    format!("{:?}", player).to_lowercase()
+}
+
+pub fn wire_square(cell: &Cell) -> Option<WireSquare> {
+    match cell {
+        Cell::Empty => None,
+        Cell::Hidden(_) => Some(WireSquare { revealed: false, color: None, piece: None }),
+        Cell::Revealed(piece) => Some(WireSquare {
+            revealed: true,
+            color: Some(color_name(piece.player)),
+            piece: Some(format!("{:?}", piece.piece_type).to_lowercase()),
+        }),
+    }
+}
+
+/// The position as `seat` (0 for the opening flip, 1 for the other player, `None` for a
+/// spectator) is allowed to see it.
+pub fn state_message(game_id: GameId, game: &GameState, seat: Option<usize>, players: &[String; 2], last_move: Option<&str>) -> ServerMessage {
+    let seat_to_move = if game.first_player_to_move() { 0 } else { 1 };
+    let your_turn = seat == Some(seat_to_move) && !game.is_game_over();
+    let your_color = match (game.first_player, seat) {
+        (PlayerColor::Assigned(first), Some(seat)) => Some(color_name(if seat == 0 { first } else { opponent(first) })),
+        _ => None,
+    };
+    ServerMessage::State {
+        game_id,
+        board: game.board.iter().map(|row| row.iter().map(wire_square).collect()).collect(),
+        fen: game.to_fen(false),
+        players: players.clone(),
+        to_move: (!game.colors_unassigned()).then(|| color_name(game.current_player)),
+        your_color,
+        your_turn,
+        legal_actions: if your_turn { game.legal_actions().into_iter().map(WireAction::from).collect() } else { Vec::new() },
+        last_move: last_move.map(str::to_string),
+    }
+}
+
+#[cfg(feature = "cli")]
+fn send(socket: &mut WebSocket<TcpStream>, message: &ServerMessage) {
+    // A non-blocking socket queues what it can't write yet and sends it on the next read
+    let text = serde_json::to_string(message).expect("Server messages always serialize");
+    socket.send(Message::text(text)).ok();
+}
+
+/// Completes WebSocket handshakes off the game loop, so a slow client can't hold it up.
+#[cfg(feature = "cli")]
+fn spawn_acceptor(listener: TcpListener) -> mpsc::Receiver<WebSocket<TcpStream>> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let sender = sender.clone();
+            thread::spawn(move || {
+                stream.set_read_timeout(Some(Duration::from_secs(10))).ok();
+                if let Ok(socket) = tungstenite::accept(stream) {
+                    if socket.get_ref().set_read_timeout(None).and_then(|()| socket.get_ref().set_nonblocking(true)).is_ok() {
+                        sender.send(socket).ok();
+                    }
+                }
+            });
+        }
+    });
+    receiver
+}
+
+/// Hosts games on `port` until the process is stopped. `new_game` deals the starting position
+/// of each game created, and spectators see every update `spectator_delay` after the players.
+/// With a `profiles` file, games between named players are rated.
+#[cfg(feature = "cli")]
+pub fn run_ws_server<F>(port: u16, new_game: F, spectator_delay: Duration, profiles: Option<PathBuf>) -> io::Result<()>
+where
+    F: FnMut() -> Result<GameState, GameError> + 'static,
+{
+    let listener = TcpListener::bind(("0.0.0.0", port))?;
+    println!("Accepting players on ws://0.0.0.0:{}/ ...", port);
+    let connections = spawn_acceptor(listener);
+
+    let mut lobby = Lobby::new(Box::new(new_game), spectator_delay, profiles);
+    let mut sockets: BTreeMap<ClientId, WebSocket<TcpStream>> = BTreeMap::new();
+    let mut next_client: ClientId = 1;
+    loop {
+        while let Ok(socket) = connections.try_recv() {
+            lobby.connect(next_client);
+            sockets.insert(next_client, socket);
+            next_client += 1;
+        }
+
+        // Poll every client in turn; a closed connection counts as leaving
+        let mut closed = Vec::new();
+        for (&client, socket) in sockets.iter_mut() {
+            match socket.read() {
+                Ok(Message::Text(text)) => {
+                    let handled = serde_json::from_str::<ClientMessage>(&text)
+                        .map_err(|e| format!("Invalid message: {}", e))
+                        .and_then(|message| lobby.handle(client, message));
+                    if let Err(message) = handled {
+                        send(socket, &ServerMessage::Error { message });
+                    }
+                },
+                Err(tungstenite::Error::Io(e)) if e.kind() == io::ErrorKind::WouldBlock => {},
+                Ok(Message::Close(_)) | Err(_) => closed.push(client),
+                Ok(_) => {},
+            }
+        }
+        for client in closed {
+            lobby.disconnect(client);
+            sockets.remove(&client);
+        }
+
+        lobby.tick(Instant::now());
+        for (client, message) in lobby.take_outbox() {
+            if let Some(socket) = sockets.get_mut(&client) {
+                send(socket, &message);
+            }
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+}